@@ -0,0 +1,108 @@
+use toyunda_player::VideoMeta;
+use subtitles::*;
+
+use clap::ArgMatches;
+use std::path::PathBuf;
+use std::fs::File;
+use std::io::Write;
+
+/// true on success
+/// false on failure
+pub fn export_subtitles(args: &ArgMatches) -> bool {
+    let json_path = match args.value_of("JSON_FILE") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            println!("A file is required for the subcommand 'export'");
+            // clap shouldn't let this case happen but never too sure
+            return false;
+        }
+    };
+    let format = args.value_of("format").unwrap_or("ass");
+    let extension = match format {
+        "ultrastar" => "txt",
+        format => format,
+    };
+    let output_path = match args.value_of("OUTPUT_FILE") {
+        Some(path) => PathBuf::from(path),
+        None => json_path.with_extension(extension),
+    };
+    let json_file = match File::open(&json_path) {
+        Ok(json_file) => json_file,
+        Err(e) => {
+            println!("file `{}` couldn't be opened : {:?}",
+                     json_path.display(),
+                     e);
+            return false;
+        }
+    };
+    let mut subtitles: Subtitles = match Subtitles::from_json(&json_file) {
+        Ok(subtitles) => subtitles,
+        Err(err) => {
+            println!("error while parsing subtitles : {}", err);
+            return false;
+        }
+    };
+    if let Err(e) = subtitles.check() {
+        println!("invalid subtitles : {}", e);
+        return false;
+    }
+    // the duration is only needed for the end credits, which aren't exported
+    // anyway : don't fail if there is no .yaml next to the .json
+    let yaml_path = json_path.with_extension("yaml");
+    let video_meta = if yaml_path.is_file() {
+        VideoMeta::from_yaml(&yaml_path).ok()
+    } else {
+        None
+    };
+    subtitles.post_init(video_meta.as_ref().map(|v| v.video_duration).unwrap_or(0));
+    let media_file = video_meta.as_ref()
+        .and_then(|v| v.video_path.file_name())
+        .and_then(|f| f.to_str());
+    let exported = match format {
+        "ass" => subtitles.to_ass(),
+        "ultrastar" => subtitles.to_ultrastar(media_file),
+        "lrc" => subtitles.to_enhanced_lrc(),
+        "vtt" => subtitles.to_webvtt(),
+        "lyr" => {
+            let fps = match args.value_of("fps").map(|fps| fps.parse::<f64>()) {
+                Some(Ok(fps)) if fps > 0.0 => fps,
+                Some(_) => {
+                    println!("invalid --fps value");
+                    return false;
+                }
+                None => {
+                    println!("--fps is required by the lyr format");
+                    return false;
+                }
+            };
+            let (lyr, frm) = subtitles.to_lyr_frm(fps);
+            // the .frm file goes next to the .lyr file
+            let frm_path = output_path.with_extension("frm");
+            if let Err(e) = File::create(&frm_path).and_then(|mut f| f.write_all(frm.as_bytes())) {
+                println!("file `{}` couldn't be written : {:?}",
+                         frm_path.display(),
+                         e);
+                return false;
+            }
+            lyr
+        }
+        _ => {
+            println!("unknown export format `{}`", format);
+            return false;
+        }
+    };
+    match File::create(&output_path).and_then(|mut f| f.write_all(exported.as_bytes())) {
+        Ok(()) => {
+            println!("exported `{}` to `{}`",
+                     json_path.display(),
+                     output_path.display());
+            true
+        }
+        Err(e) => {
+            println!("file `{}` couldn't be written : {:?}",
+                     output_path.display(),
+                     e);
+            false
+        }
+    }
+}
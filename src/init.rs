@@ -6,6 +6,7 @@ use std::os::raw::{c_void, c_char};
 use std::ffi::CStr;
 use ::toyunda_player::{StartupParameters, ToyundaPlayer, ToyundaMode};
 use sdl_displayer::SDLDisplayer;
+use mpv_plug::MpvBackend;
 
 unsafe extern "C" fn get_proc_address(arg: *mut c_void, name: *const c_char) -> *mut c_void {
     let arg: &sdl2::VideoSubsystem = &*(arg as *mut sdl2::VideoSubsystem);
@@ -23,8 +24,8 @@ pub fn player_start(startup_parameters: StartupParameters) {
     let mut mpv_builder = mpv::MpvHandlerBuilder::new().expect("Error while creating MPV builder");
     mpv_builder.set_option("sid", "no").unwrap(); // disables subtitles if any
     mpv_builder.set_option("softvol", "yes").unwrap(); // enables softvol so it can go higher than 100%
-    mpv_builder.set_option("softvol-max", 250.0).unwrap(); // makes the max volume at 250%
-    mpv_builder.set_option("aid", 2i64).unwrap(); // aid to 2; normalized audio if there is one
+    mpv_builder.set_option("softvol-max", startup_parameters.max_volume).unwrap(); // 250% by default
+    mpv_builder.set_option("aid", startup_parameters.audio_track).unwrap(); // 2 by default; normalized audio
     mpv_builder.try_hardware_decoding().unwrap(); // try hardware decoding instead of software decoding
     let mpv = mpv_builder.build_with_gl(Some(get_proc_address), video_subsystem_ptr)
         .expect("Error while initializing MPV");
@@ -41,7 +42,8 @@ pub fn player_start(startup_parameters: StartupParameters) {
     }
     // Create a new displayer for the toyunda_player
 
-    let mut toyunda_player = ToyundaPlayer::new(mpv, displayer);
+    let mut toyunda_player = ToyundaPlayer::new(Box::new(MpvBackend::new(mpv)),
+                                                Box::new(displayer));
     match toyunda_player.start(startup_parameters) {
         Err(e) => {
             error!("Failed to start player with given arguments, expect default parameters !\n\
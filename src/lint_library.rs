@@ -0,0 +1,137 @@
+use toyunda_player::{Manager, VideoMeta};
+use subtitles::Error as SubtitlesError;
+
+use clap::ArgMatches;
+use std::path::PathBuf;
+use std::io;
+
+extern crate serde_json;
+
+#[derive(Debug,Serialize)]
+struct SongReport {
+    /// yaml file of the song
+    file: PathBuf,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+#[derive(Debug,Serialize)]
+struct LintReport {
+    checked: usize,
+    errors: usize,
+    warnings: usize,
+    /// yaml files which couldn't even be loaded
+    yaml_errors: Vec<String>,
+    /// only the songs with at least one error or warning
+    songs: Vec<SongReport>,
+}
+
+fn is_empty(field: &Option<String>) -> bool {
+    field.as_ref().map(|s| s.trim().is_empty()).unwrap_or(true)
+}
+
+fn lint_song(video_meta: &VideoMeta) -> SongReport {
+    let mut report = SongReport {
+        file: video_meta.yaml_path.clone().unwrap_or(video_meta.video_path.clone()),
+        errors: vec![],
+        warnings: vec![],
+    };
+    if !video_meta.video_path.is_file() {
+        report.errors.push(format!("video file '{}' not found", video_meta.video_path.display()));
+    }
+    let song_info = &video_meta.song_info;
+    let fields = [("artist", is_empty(&song_info.artist)),
+                  ("media_title", is_empty(&song_info.media_title)),
+                  ("song_name", is_empty(&song_info.song_name)),
+                  ("music_type", song_info.music_type.is_none()),
+                  ("media_type", song_info.media_type.is_none()),
+                  ("language", song_info.language.is_none())];
+    for &(name, empty) in fields.iter() {
+        if empty {
+            report.warnings.push(format!("song_info.{} is empty", name));
+        }
+    }
+    match video_meta.load_subtitles() {
+        Ok(subtitles) => {
+            for error in subtitles.lint(video_meta.video_duration) {
+                if error.is_warning() {
+                    report.warnings.push(error.to_string());
+                } else {
+                    report.errors.push(error.to_string());
+                }
+            }
+        }
+        Err(SubtitlesError::Io { ref error, .. }) if error.kind() == io::ErrorKind::NotFound &&
+                                                     video_meta.lyr_path().is_file() => {
+            report.warnings
+                .push(String::from("only .lyr subtitles, they can't be checked without the \
+                                    fps of the video"));
+        }
+        Err(e) => report.errors.push(e.to_string()),
+    }
+    report
+}
+
+fn print_report(lint_report: &LintReport) {
+    for error in &lint_report.yaml_errors {
+        println!("error: {}", error);
+    }
+    for song in &lint_report.songs {
+        println!("{}", song.file.display());
+        for error in &song.errors {
+            println!("  error: {}", error);
+        }
+        for warning in &song.warnings {
+            println!("  warning: {}", warning);
+        }
+    }
+    println!("{} song(s) checked, {} error(s), {} warning(s)",
+             lint_report.checked,
+             lint_report.errors,
+             lint_report.warnings);
+}
+
+/// true if no error was found, warnings don't count
+pub fn lint_library(args: &ArgMatches) -> bool {
+    let directories: Vec<PathBuf> = match args.values_of("YAML_DIRECTORY") {
+        Some(directories) => directories.map(|d| PathBuf::from(d)).collect(),
+        None => {
+            println!("A directory is required for the subcommand 'lint'");
+            // clap shouldn't let this case happen but never too sure
+            return false;
+        }
+    };
+    let mut lint_report = LintReport {
+        checked: 0,
+        errors: 0,
+        warnings: 0,
+        yaml_errors: vec![],
+        songs: vec![],
+    };
+    for directory in directories {
+        let (video_metas, yaml_errors) = Manager::load_yaml_directory(&directory);
+        lint_report.yaml_errors.extend(yaml_errors);
+        for video_meta in video_metas {
+            let song_report = lint_song(&video_meta);
+            lint_report.checked += 1;
+            lint_report.errors += song_report.errors.len();
+            lint_report.warnings += song_report.warnings.len();
+            if !(song_report.errors.is_empty() && song_report.warnings.is_empty()) {
+                lint_report.songs.push(song_report);
+            }
+        }
+    }
+    lint_report.errors += lint_report.yaml_errors.len();
+    if args.is_present("json") {
+        match serde_json::to_string_pretty(&lint_report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                println!("error while generating the json report : {}", e);
+                return false;
+            }
+        }
+    } else {
+        print_report(&lint_report);
+    }
+    lint_report.errors == 0
+}
@@ -26,9 +26,11 @@ extern crate sdl2;
 extern crate log;
 extern crate fern;
 extern crate chrono;
+extern crate rand;
 #[macro_use]
 extern crate clap;
 use clap::{Arg, App, SubCommand};
+use std::path::Path;
 
 mod utils;
 mod overlay;
@@ -38,13 +40,22 @@ mod subtitles;
 mod toyunda_player;
 mod mpv_plug;
 mod update_json;
+mod export_subtitles;
+mod migrate_json;
+mod lint_library;
+mod render_subtitles;
 
 use update_json::update_json;
+use export_subtitles::export_subtitles;
+use migrate_json::migrate_json;
+use lint_library::lint_library;
+use render_subtitles::render_subtitles;
 
 use toyunda_player::log_messages::{LOG_MESSAGES, LogMessage as ToyundaLogMessage};
 use toyunda_player::StartupOptions;
 
-fn main() {
+/// the player takes the levels from its config, the subcommands use the defaults
+fn init_logger(stdout_level: log::LogLevelFilter, file_level: log::LogLevelFilter) {
     struct _DummyLog {};
     impl fern::Logger for _DummyLog {
         fn log(&self,
@@ -76,7 +87,7 @@ fn main() {
                     msg)
         }),
         output: vec![fern::OutputConfig::file(&toyunda_log_path)],
-        level: log::LogLevelFilter::Info,
+        level: file_level,
     };
     // init the logger
     let stdout_config = fern::DispatchConfig {
@@ -87,7 +98,7 @@ fn main() {
                     msg)
         }),
         output: vec![fern::OutputConfig::stdout()],
-        level: log::LogLevelFilter::Warn,
+        level: stdout_level,
     };
     let logger_config = fern::DispatchConfig {
         format: Box::new(|msg: &str, _level: &log::LogLevel, _: &log::LogLocation| {
@@ -96,12 +107,15 @@ fn main() {
         output: vec![fern::OutputConfig::child(stdout_config),
                      fern::OutputConfig::child(fileout_config),
                      fern::OutputConfig::custom(Box::new(_DummyLog {}))],
-        level: log::LogLevelFilter::Info,
+        level: ::std::cmp::max(stdout_level, file_level),
     };
     if let Err(e) = fern::init_global_logger(logger_config, log::LogLevelFilter::Trace) {
         println!("Failed to initialize logger, no messages will be shown ! Error: {}",
                  e);
     };
+}
+
+fn main() {
     let matches = App::new("Toyunda Player")
         .version(crate_version!())
         .after_help("PLAYER SHORTCUTS :\n    * V : Hides / Shows subtitles\n    * F : Toggles \
@@ -109,6 +123,16 @@ fn main() {
                      arrow : Seek backwards / frontwards\n    ")
         .author("Cobrand")
         .about("A flexible karaoke player for the epitanime association")
+        .arg(Arg::with_name("config")
+            .short("c")
+            .long("config")
+            .takes_value(true)
+            .help("Config file to read instead of toyunda.yaml next to the player and \
+                   $XDG_CONFIG_HOME/toyunda-player/config.yaml ; the flags win over it"))
+        .arg(Arg::with_name("print_config")
+            .long("print-config")
+            .help("Prints the config once the flags, the config files and the defaults are \
+                   merged, then exits"))
         .arg(Arg::with_name("karaoke_mode")
             .short("k")
             .long("karaoke")
@@ -129,11 +153,47 @@ fn main() {
             .multiple(true)
             .help("Where to look the yaml files at")
             .conflicts_with("edit_mode"))
+        .arg(Arg::with_name("watch_directories")
+            .long("watch")
+            .help("Updates the listing when yaml files are added, changed or removed"))
         .arg(Arg::with_name("songs_history")
             .short("s")
             .long("songs-history")
             .takes_value(true)
             .help("Where to look the songs history at"))
+        .arg(Arg::with_name("library_cache")
+            .long("library-cache")
+            .takes_value(true)
+            .help("Where the parsed yaml files are kept, so only the ones changed are parsed \
+                   again on startup; next to the songs history by default"))
+        .arg(Arg::with_name("queue_journal")
+            .long("queue-journal")
+            .takes_value(true)
+            .help("Where the queue is written every time it changes; queue_journal.json next to \
+                   the player by default"))
+        .arg(Arg::with_name("restore_queue")
+            .long("restore-queue")
+            .help("Plays again the queue of the queue journal, starting with the song which was \
+                   interrupted"))
+        .arg(Arg::with_name("auto_dj")
+            .long("auto-dj")
+            .help("In karaoke mode, fills the queue with random songs of the library when it runs \
+                   dry; the weights are set in the config file"))
+        .arg(Arg::with_name("intermission_duration")
+            .long("intermission")
+            .takes_value(true)
+            .help("In karaoke mode, how many seconds the next song is announced before it \
+                   starts"))
+        .arg(Arg::with_name("manager_url")
+            .long("manager-url")
+            .takes_value(true)
+            .help("The address of the manager shown between the songs, guessed from the listen \
+                   address by default"))
+        .arg(Arg::with_name("playlists_directory")
+            .long("playlists-dir")
+            .takes_value(true)
+            .help("Where the playlists saved from the manager are kept; playlists/ next to the \
+                   player by default"))
         .arg(Arg::with_name("manager_port")
             .short("p")
             .long("port")
@@ -146,6 +206,34 @@ fn main() {
         .arg(Arg::with_name("no_manager")
             .long("no-manager")
             .help("Prevents the manager from starting in karaoke or normal mode"))
+        .arg(Arg::with_name("operator_password")
+            .long("operator-password")
+            .takes_value(true)
+            .help("Password needed to control the player from the manager; \
+                   TOYUNDA_OPERATOR_PASSWORD is used if absent. Without it, \
+                   anyone is an operator"))
+        .arg(Arg::with_name("guest_password")
+            .long("guest-password")
+            .takes_value(true)
+            .help("Password needed to add songs from the manager; \
+                   TOYUNDA_GUEST_PASSWORD is used if absent. Without it, \
+                   anyone can add songs"))
+        .arg(Arg::with_name("max_songs_in_queue")
+            .long("max-songs-in-queue")
+            .takes_value(true)
+            .help("How many songs a guest can have in the queue at once"))
+        .arg(Arg::with_name("max_songs_per_hour")
+            .long("max-songs-per-hour")
+            .takes_value(true)
+            .help("How many songs a guest can add in an hour"))
+        .arg(Arg::with_name("fair_queue")
+            .long("fair-queue")
+            .help("Requesters take turns in the queue instead of first come, first served"))
+        .arg(Arg::with_name("replay_cooldown")
+            .long("replay-cooldown")
+            .takes_value(true)
+            .help("How many minutes before a song can be added again once played or queued; \
+                   guests are refused, operators only warned"))
         .arg(Arg::with_name("volume")
             .short("v")
             .long("volume")
@@ -160,7 +248,7 @@ fn main() {
             .conflicts_with("quit")
             .help("Forces keeping alive the player once the waiting queue is finished"))
         .arg(Arg::with_name("VIDEO_FILE")
-            .help("Sets the video file(s) to play")
+            .help("Sets the video file(s) to play; m3u8 and xspf playlists are read as well")
             .use_delimiter(false)
             .multiple(true))
         .subcommand(SubCommand::with_name("update")
@@ -168,8 +256,71 @@ fn main() {
             .arg(Arg::with_name("JSON_FILE")
                 .use_delimiter(false)
                 .required(true)))
+        .subcommand(SubCommand::with_name("export")
+            .about("exports a json subtitles file to another subtitles format")
+            .arg(Arg::with_name("format")
+                .short("t")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["ass", "ultrastar", "lyr", "lrc", "vtt"])
+                .default_value("ass")
+                .help("Format of the exported file"))
+            .arg(Arg::with_name("fps")
+                .long("fps")
+                .takes_value(true)
+                .help("Frames per second of the video, required by the lyr format"))
+            .arg(Arg::with_name("JSON_FILE")
+                .use_delimiter(false)
+                .required(true))
+            .arg(Arg::with_name("OUTPUT_FILE")
+                .use_delimiter(false)
+                .help("Where to write the exported file, defaults to the json file with the \
+                       format's extension")))
+        .subcommand(SubCommand::with_name("migrate")
+            .about("upgrades every json file of a directory to the current format, keeping \
+                    the original files as .json.bak")
+            .arg(Arg::with_name("DIRECTORY")
+                .use_delimiter(false)
+                .required(true)))
+        .subcommand(SubCommand::with_name("lint")
+            .about("checks the timings and the metadata of every song of the yaml directories")
+            .arg(Arg::with_name("json")
+                .long("json")
+                .help("Prints the report as json"))
+            .arg(Arg::with_name("YAML_DIRECTORY")
+                .use_delimiter(false)
+                .multiple(true)
+                .required(true)))
+        .subcommand(SubCommand::with_name("render")
+            .about("renders the subtitles of a json file to png images, without any video")
+            .arg(Arg::with_name("time")
+                .short("t")
+                .long("time")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .required(true)
+                .help("Timestamp to render, in ms or as mm:ss.xx ; can be repeated"))
+            .arg(Arg::with_name("size")
+                .short("s")
+                .long("size")
+                .takes_value(true)
+                .default_value("1280x720")
+                .help("Size of the images, as WIDTHxHEIGHT"))
+            .arg(Arg::with_name("output_dir")
+                .short("o")
+                .long("output-dir")
+                .takes_value(true)
+                .help("Where to write the images, defaults to the directory of the json file"))
+            .arg(Arg::with_name("JSON_FILE")
+                .use_delimiter(false)
+                .required(true)))
         .get_matches();
 
+    if matches.subcommand_name().is_some() {
+        // the subcommands don't read the config files, a bad one doesn't stop them
+        init_logger(log::LogLevelFilter::Warn, log::LogLevelFilter::Info);
+    }
     if let Some(sub_matches) = matches.subcommand_matches("update") {
         if update_json(sub_matches) {
             ::std::process::exit(0);
@@ -177,12 +328,58 @@ fn main() {
             ::std::process::exit(-1);
         }
     }
-    let startup_options = match StartupOptions::from_args(matches) {
+    if let Some(sub_matches) = matches.subcommand_matches("export") {
+        if export_subtitles(sub_matches) {
+            ::std::process::exit(0);
+        } else {
+            ::std::process::exit(-1);
+        }
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("migrate") {
+        if migrate_json(sub_matches) {
+            ::std::process::exit(0);
+        } else {
+            ::std::process::exit(-1);
+        }
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("lint") {
+        if lint_library(sub_matches) {
+            ::std::process::exit(0);
+        } else {
+            ::std::process::exit(-1);
+        }
+    }
+    if let Some(sub_matches) = matches.subcommand_matches("render") {
+        if render_subtitles(sub_matches) {
+            ::std::process::exit(0);
+        } else {
+            ::std::process::exit(-1);
+        }
+    }
+    // the command line wins over the config files
+    let startup_options = StartupOptions::from_args(&matches)
+        .and_then(|options| options.with_config_files(matches.value_of("config").map(Path::new)));
+    let startup_parameters = match startup_options {
         Err(e) => {
-            error!("Error when parsing command line parameters: {}", e);
+            println!("Error when parsing the startup options: {}", e);
             ::std::process::exit(1)
         }
-        Ok(startup_options) => startup_options,
+        Ok(startup_options) => startup_options.to_params(),
     };
-    init::player_start(startup_options.to_params());
+    // already checked when the config files were read
+    init_logger(startup_parameters.log_level.parse().unwrap_or(log::LogLevelFilter::Warn),
+                startup_parameters.log_file_level.parse().unwrap_or(log::LogLevelFilter::Info));
+    if matches.is_present("print_config") {
+        match startup_parameters.to_yaml() {
+            Ok(yaml) => {
+                println!("{}", yaml);
+                ::std::process::exit(0);
+            }
+            Err(e) => {
+                error!("Can't print the config : {}", e);
+                ::std::process::exit(1);
+            }
+        }
+    }
+    init::player_start(startup_parameters);
 }
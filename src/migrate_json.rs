@@ -0,0 +1,96 @@
+use subtitles::*;
+use subtitles::migration::{self, CURRENT_FORMAT_VERSION};
+use utils::for_each_in_dir;
+
+use clap::ArgMatches;
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+
+extern crate serde_json;
+
+/// how deep the directory is searched for json files
+const MAX_DEPTH: u32 = 8;
+
+enum MigrateResult {
+    Migrated,
+    UpToDate,
+}
+
+/// the original file is kept as `<file>.json.bak`
+fn migrate_file(json_path: &Path) -> Result<MigrateResult, String> {
+    let json_file = try!(File::open(json_path).map_err(|e| format!("can't be opened : {}", e)));
+    let mut document: serde_json::Value = try!(serde_json::from_reader(&json_file)
+        .map_err(|e| format!("invalid json : {}", e)));
+    let version = migration::format_version(&document);
+    if version == CURRENT_FORMAT_VERSION {
+        return Ok(MigrateResult::UpToDate);
+    }
+    let changes = try!(migration::migrate(&mut document).map_err(|e| e.to_string()));
+    // make sure the migrated document is valid before touching anything
+    let subtitles: Subtitles = try!(serde_json::from_value(document)
+        .map_err(|e| format!("invalid subtitles after migration : {}", e)));
+    let mut backup_path = json_path.as_os_str().to_owned();
+    backup_path.push(".bak");
+    let backup_path = PathBuf::from(backup_path);
+    if backup_path.exists() {
+        return Err(format!("backup `{}` already exists", backup_path.display()));
+    }
+    try!(fs::copy(json_path, &backup_path)
+        .map_err(|e| format!("backup `{}` couldn't be written : {}", backup_path.display(), e)));
+    let mut json_file = try!(File::create(json_path)
+        .map_err(|e| format!("can't be opened for writing : {}", e)));
+    try!(serde_json::to_writer_pretty(&mut json_file, &subtitles)
+        .map_err(|e| format!("couldn't be written : {}", e)));
+    println!("migrated `{}` from format_version {} to {}",
+             json_path.display(),
+             version,
+             CURRENT_FORMAT_VERSION);
+    for change in changes {
+        println!("  {}", change);
+    }
+    Ok(MigrateResult::Migrated)
+}
+
+/// true on success
+/// false on failure
+pub fn migrate_json(args: &ArgMatches) -> bool {
+    let directory = match args.value_of("DIRECTORY") {
+        Some(directory) => PathBuf::from(directory),
+        None => {
+            println!("A directory is required for the subcommand 'migrate'");
+            // clap shouldn't let this case happen but never too sure
+            return false;
+        }
+    };
+    if !directory.is_dir() {
+        println!("`{}` is not a directory", directory.display());
+        return false;
+    }
+    let (paths, errs) = for_each_in_dir(&directory,
+                                        MAX_DEPTH,
+                                        &|path| {
+                                            match path.extension() {
+                                                Some(s) if s == "json" => true,
+                                                _ => false,
+                                            }
+                                        });
+    for err in &errs {
+        println!("IoError '{}' when reading `{}`", err, directory.display());
+    }
+    let (mut migrated, mut up_to_date, mut failed) = (0, 0, 0);
+    for path in paths {
+        match migrate_file(&path) {
+            Ok(MigrateResult::Migrated) => migrated += 1,
+            Ok(MigrateResult::UpToDate) => up_to_date += 1,
+            Err(e) => {
+                println!("`{}` was not migrated : {}", path.display(), e);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} file(s) migrated, {} already up to date, {} failed",
+             migrated,
+             up_to_date,
+             failed);
+    failed == 0 && errs.is_empty()
+}
@@ -1,5 +1,6 @@
-
 extern crate mpv;
 
 mod mpv_cache;
+mod mpv_backend;
 pub use self::mpv_cache::*;
+pub use self::mpv_backend::*;
@@ -0,0 +1,132 @@
+use mpv::{MpvHandlerWithGl, Event as MpvEvent};
+use mpv::EndFileReason::MPV_END_FILE_REASON_EOF;
+use mpv::Error::{MPV_ERROR_PROPERTY_UNAVAILABLE, MPV_ERROR_LOADING_FAILED};
+use ::toyunda_player::{MediaBackend, MediaEvent};
+use ::toyunda_player::error::{Result, Error};
+use super::MpvCache;
+
+pub struct MpvBackend {
+    mpv: Box<MpvHandlerWithGl>,
+    mpv_cache: MpvCache,
+}
+
+impl MpvBackend {
+    pub fn new(mpv: Box<MpvHandlerWithGl>) -> MpvBackend {
+        MpvBackend {
+            mpv: mpv,
+            mpv_cache: MpvCache::new(),
+        }
+    }
+}
+
+impl MediaBackend for MpvBackend {
+    fn load_file(&mut self, path: &str) -> Result<()> {
+        self.mpv.command(&["loadfile", path]).map_err(Error::MpvError)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.mpv.command(&["stop"]).map_err(Error::MpvError)
+    }
+
+    fn seek(&mut self, delta: f64) -> Result<()> {
+        self.mpv
+            .command(&["seek", delta.to_string().as_str()])
+            .map_err(Error::MpvError)
+    }
+
+    fn set_percent_pos(&mut self, percent_pos: f64) -> Result<()> {
+        match self.mpv.set_property("percent-pos", percent_pos) {
+            // happens when video is paused
+            Err(MPV_ERROR_PROPERTY_UNAVAILABLE) => Ok(()),
+            r => r.map_err(Error::MpvError),
+        }
+    }
+
+    fn framestep(&mut self, step: i32) -> Result<()> {
+        let frame_step_type = if step >= 0 {
+            "frame-step"
+        } else {
+            "frame-back-step"
+        };
+        self.mpv.command(&[frame_step_type]).map_err(Error::MpvError)
+    }
+
+    fn is_paused(&self) -> Result<bool> {
+        self.mpv.get_property("pause").map_err(Error::MpvError)
+    }
+
+    fn set_pause(&mut self, pause: bool) -> Result<()> {
+        self.mpv.set_property_async("pause", pause, 1).map_err(Error::MpvError)
+    }
+
+    fn set_speed(&mut self, speed: f64) -> Result<()> {
+        self.mpv.set_property_async("speed", speed, 1).map_err(Error::MpvError)
+    }
+
+    fn volume(&self) -> Result<f64> {
+        self.mpv.get_property::<f64>("volume").map_err(Error::MpvError)
+    }
+
+    fn max_volume(&self) -> Result<f64> {
+        self.mpv.get_property::<f64>("volume-max").map_err(Error::MpvError)
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<()> {
+        self.mpv.set_property("volume", volume).map_err(Error::MpvError)
+    }
+
+    fn set_loop_file(&mut self, loop_file: bool) -> Result<()> {
+        self.mpv
+            .set_option("loop-file", if loop_file { "inf" } else { "no" })
+            .map_err(Error::MpvError)
+    }
+
+    fn draw(&mut self, width: u32, height: u32) -> Result<()> {
+        self.mpv.draw(0, width as i32, -(height as i32)).map_err(Error::MpvError)
+    }
+
+    fn update(&mut self) {
+        self.mpv_cache.update(&self.mpv);
+    }
+
+    fn time_pos(&self) -> Option<f64> {
+        self.mpv_cache.cached_time_pos()
+    }
+
+    fn percent_pos(&self) -> Option<f64> {
+        self.mpv_cache.cached_percent_pos()
+    }
+
+    fn dimensions(&self) -> Option<(u32, u32)> {
+        match (self.mpv_cache.cached_width(), self.mpv_cache.cached_height()) {
+            (Some(w), Some(h)) => Some((w, h)),
+            _ => None,
+        }
+    }
+
+    fn duration(&self) -> Option<f64> {
+        self.mpv.get_property::<f64>("duration").ok()
+    }
+
+    fn fps(&self) -> Option<f64> {
+        self.mpv.get_property::<f64>("fps").ok()
+    }
+
+    fn poll_event(&mut self) -> Option<MediaEvent> {
+        // skip the events we don't care about, but don't block
+        while let Some(event) = self.mpv.wait_event(0.0) {
+            match event {
+                MpvEvent::Shutdown => return Some(MediaEvent::Shutdown),
+                MpvEvent::EndFile(Ok(MPV_END_FILE_REASON_EOF)) => {
+                    return Some(MediaEvent::EndOfFile)
+                }
+                MpvEvent::EndFile(Err(MPV_ERROR_LOADING_FAILED)) => {
+                    return Some(MediaEvent::LoadingFailed)
+                }
+                MpvEvent::FileLoaded => return Some(MediaEvent::FileLoaded),
+                _ => {}
+            }
+        }
+        None
+    }
+}
@@ -1,5 +1,16 @@
-use super::{OverlayFrame, Rect};
+use super::{OverlayFrame, Rect, Color};
 pub trait Display {
     type Parameters;
     fn display(&mut self, &OverlayFrame, &Self::Parameters) -> Vec<Rect>;
 }
+
+/// A Display which is a window, what the player draws in
+pub trait Screen: Display {
+    /// width and height of the window
+    fn size(&self) -> (u32, u32);
+    fn set_title(&mut self, title: &str) -> Result<(), String>;
+    fn toggle_fullscreen(&mut self) -> Result<(), String>;
+    fn fill_rect(&mut self, rect: Rect, color: Color) -> Result<(), String>;
+    /// shows everything drawn since the last call
+    fn render(&mut self);
+}
@@ -0,0 +1,163 @@
+use toyunda_player::VideoMeta;
+use subtitles::*;
+use overlay::{Display, AlphaColor};
+use sdl_displayer::{SoftwareDisplayer, SDLDisplayParameters};
+
+use clap::ArgMatches;
+use std::path::PathBuf;
+use std::fs::File;
+
+extern crate sdl2;
+
+/// "1500" is in ms, "1:02.5" is 1 min 2.5 s
+fn parse_timestamp(timestamp: &str) -> Result<u32, String> {
+    let invalid = || format!("invalid timestamp `{}`", timestamp);
+    match timestamp.find(':') {
+        None => timestamp.parse::<u32>().map_err(|_| invalid()),
+        Some(i) => {
+            let minutes = try!(timestamp[..i].parse::<u32>().map_err(|_| invalid()));
+            let seconds = try!(timestamp[i + 1..].parse::<f64>().map_err(|_| invalid()));
+            if seconds < 0.0 || seconds >= 60.0 {
+                return Err(invalid());
+            }
+            Ok(minutes * 60000 + (seconds * 1000.0).round() as u32)
+        }
+    }
+}
+
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid size `{}`, expected WIDTHxHEIGHT", size);
+    let mut dims = size.split('x').map(|d| d.parse::<u32>());
+    match (dims.next(), dims.next(), dims.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) if width > 0 && height > 0 => {
+            Ok((width, height))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// true on success
+/// false on failure
+pub fn render_subtitles(args: &ArgMatches) -> bool {
+    let json_path = match args.value_of("JSON_FILE") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            println!("A file is required for the subcommand 'render'");
+            // clap shouldn't let this case happen but never too sure
+            return false;
+        }
+    };
+    let timestamps: Vec<u32> = match args.values_of("time")
+        .map(|t| t.map(parse_timestamp).collect::<Result<Vec<u32>, String>>()) {
+        Some(Ok(timestamps)) => timestamps,
+        Some(Err(e)) => {
+            println!("{}", e);
+            return false;
+        }
+        None => {
+            println!("At least one --time is required for the subcommand 'render'");
+            return false;
+        }
+    };
+    let size = match parse_size(args.value_of("size").unwrap_or("1280x720")) {
+        Ok(size) => size,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+    let output_dir = match args.value_of("output_dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => json_path.parent().map(|p| p.to_path_buf()).unwrap_or(PathBuf::from(".")),
+    };
+    if !output_dir.is_dir() {
+        println!("`{}` is not a directory", output_dir.display());
+        return false;
+    }
+    let json_file = match File::open(&json_path) {
+        Ok(json_file) => json_file,
+        Err(e) => {
+            println!("file `{}` couldn't be opened : {:?}",
+                     json_path.display(),
+                     e);
+            return false;
+        }
+    };
+    let mut subtitles: Subtitles = match Subtitles::from_json(&json_file) {
+        Ok(subtitles) => subtitles,
+        Err(err) => {
+            println!("error while parsing subtitles : {}", err);
+            return false;
+        }
+    };
+    if let Err(e) = subtitles.check() {
+        println!("invalid subtitles : {}", e);
+        return false;
+    }
+    // without a .yaml the credits are simply not shown
+    let yaml_path = json_path.with_extension("yaml");
+    let video_duration = if yaml_path.is_file() {
+        VideoMeta::from_yaml(&yaml_path).ok().map(|v| v.video_duration).unwrap_or(0)
+    } else {
+        0
+    };
+    subtitles.post_init(video_duration);
+    let ttf_context = match sdl2::ttf::init() {
+        Ok(ttf_context) => ttf_context,
+        Err(e) => {
+            println!("Failed to init TTF : {}", e);
+            return false;
+        }
+    };
+    let mut displayer = match SoftwareDisplayer::new(&ttf_context, size) {
+        Ok(displayer) => displayer,
+        Err(e) => {
+            println!("Failed to init the renderer : {}", e);
+            return false;
+        }
+    };
+    let transparent = AlphaColor {
+        red: 0,
+        green: 0,
+        blue: 0,
+        alpha: 0,
+    };
+    let params = SDLDisplayParameters {
+        output_size: None,
+        offset: None,
+    };
+    let file_stem = json_path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("subtitles")
+        .to_string();
+    let mut success = true;
+    for time in timestamps {
+        let overlay_frame = match subtitles.to_overlay_frame(time) {
+            Ok(overlay_frame) => overlay_frame,
+            Err(e) => {
+                println!("frame at {}ms couldn't be computed : {}", time, e);
+                success = false;
+                continue;
+            }
+        };
+        displayer.clear(transparent);
+        displayer.display(&overlay_frame, &params);
+        let png_path = output_dir.join(format!("{}_{}ms.png", file_stem, time));
+        match displayer.save_png(&png_path) {
+            Ok(()) => println!("rendered {}ms to `{}`", time, png_path.display()),
+            Err(e) => {
+                println!("file `{}` couldn't be written : {}", png_path.display(), e);
+                success = false;
+            }
+        }
+    }
+    success
+}
+
+#[test]
+fn test_parse_timestamp() {
+    assert_eq!(parse_timestamp("1500"), Ok(1500));
+    assert_eq!(parse_timestamp("1:02.5"), Ok(62500));
+    assert!(parse_timestamp("1:75").is_err());
+    assert!(parse_timestamp("abc").is_err());
+}
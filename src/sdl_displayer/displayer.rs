@@ -1,16 +1,13 @@
 use sdl2::render::{TextureCreator, Canvas, BlendMode, Texture};
 use sdl2::video::{Window, WindowContext};
 use sdl2::image::{LoadTexture, INIT_PNG, INIT_JPG, init as image_init};
-use sdl2::ttf::{Font, Sdl2TtfContext};
+use sdl2::ttf::Sdl2TtfContext;
 use sdl2::rect::Rect as SdlRect;
-use sdl2::surface::Surface;
 use sdl2::pixels::Color as SdlColor;
-use utils::fit_dims;
 
-use std::ops::DerefMut;
 use ::overlay::*;
-use ::overlay::pos::*;
 use super::font::*;
+use super::text::*;
 use super::*;
 
 impl From<Rect> for SdlRect {
@@ -98,10 +95,6 @@ impl<'r,'ttf> SDLDisplayer<'r,'ttf> {
         }
     }
 
-    pub fn render(&mut self) {
-        self.canvas.window().gl_swap_window();
-    }
-
     #[inline]
     pub fn sdl_canvas_mut(&mut self) -> &mut Canvas<Window> {
         &mut self.canvas
@@ -114,77 +107,37 @@ impl<'r,'ttf> SDLDisplayer<'r,'ttf> {
 
     fn display_unit(&mut self, text_unit: &TextUnit, params: &SDLDisplayParameters) -> Rect {
         let (offset_x, offset_y) = params.offset.unwrap_or((0, 0));
-        let (canevas_width, canevas_height): (u32, u32) = match params.output_size {
+        let canevas_size: (u32, u32) = match params.output_size {
             None => self.sdl_canvas().window().size(),
             Some(e) => e,
         };
-        let (fit_width, fit_height): (Option<u32>, Option<u32>) = match text_unit.size {
-            Size::FitPercent(option_x, option_y) => {
-                fit_dims((canevas_width, canevas_height), option_x, option_y)
-            }
-            Size::Fit(x, y) => (x, y),
-        };
-
-        let max_outline = text_unit.text
-            .iter()
-            .map(|text_element| text_element.outline)
-            .max()
-            .map(|max_outline| max_outline.to_size())
-            .unwrap_or(0);
-        let all_text = text_unit.to_string();
-        let font_set_id = self.fonts
-            .get_fittest_font_set_id(all_text.as_str(), (fit_width, fit_height), max_outline)
-            .unwrap();
-        let (text_width, text_height) = self.fonts
-            .get_font_set(font_set_id)
-            .unwrap()
-            .get_outline_font()
-            .size_of(all_text.as_str())
-            .expect("Unable to get outline pixel size of str");
-        let (text_pos_x, text_pos_y) = real_position((canevas_width, canevas_height),
-                                                     text_unit.pos,
-                                                     text_unit.anchor,
-                                                     (text_width, text_height));
+        let layout = layout_text_unit(&self.fonts, text_unit, canevas_size);
+        let (text_pos_x, text_pos_y) = layout.pos;
         let mut width_offset: u32 = 0;
         for text_subunit in text_unit.text.iter() {
             // for each text element, blit it over
             let syllable_rect = self.blit_text_subunit(&text_subunit,
-                                                       font_set_id,
+                                                       layout.font_set_id,
                                                        (offset_x + text_pos_x + width_offset as i32,
                                                         offset_y + text_pos_y));
             if text_subunit.attach_logo {
-                let (syllable_center_x, _) = syllable_rect.center().into();
-                let syllable_bottom = syllable_rect.bottom();
-                let syllable_height = syllable_rect.height();
-                let logo_height = syllable_height * 70 / 100;
                 match self.lyrics_logo {
                     Some(ref texture) => {
                         self.canvas
-                            .copy(&texture,
-                                  None,
-                                  Some(SdlRect::new(syllable_center_x - (logo_height / 2) as i32,
-                                                    syllable_bottom -
-                                                    syllable_height as i32 / 16,
-                                                    logo_height,
-                                                    logo_height)))
+                            .copy(&texture, None, Some(logo_rect(syllable_rect)))
                             .unwrap();
                     }
                     None => {}
                 };
             };
-            let (w, _): (u32, u32) = self.fonts
-                .get_font_set(font_set_id)
-                .unwrap()
-                .get_regular_font()
-                .size_of(text_subunit.text.as_str())
-                .unwrap_or((0, 0));
-            width_offset = width_offset + w;
+            width_offset = width_offset +
+                           text_subunit_width(&self.fonts[layout.font_set_id], text_subunit);
         }
         Rect {
             x: offset_x + text_pos_x,
             y: offset_y + text_pos_y,
-            width: text_width,
-            height: text_height,
+            width: layout.size.0,
+            height: layout.size.1,
         }
     }
 
@@ -193,72 +146,14 @@ impl<'r,'ttf> SDLDisplayer<'r,'ttf> {
                          font_set_id: usize,
                          origin: (i32, i32))
                          -> SdlRect {
-        use ::sdl2::pixels::PixelFormatEnum::ARGB8888;
-        fn blit_font_text(dest: &mut Surface,
-                          font: &Font,
-                          text: &str,
-                          color: SdlColor,
-                          delta_outline: u32) {
-            let (dest_w, dest_h) = dest.size();
-            let subdest_rect = SdlRect::new(delta_outline as i32,
-                                            delta_outline as i32,
-                                            dest_w - (delta_outline * 2),
-                                            dest_h - (delta_outline * 2));
-            let mut font_surface = font.render(text)
-                .blended(color)
-                .unwrap();
-            let _ = font_surface.set_blend_mode(BlendMode::Blend);
-            font_surface.blit(None, dest.deref_mut(), Some(subdest_rect))
-                .expect("Failed to blit surface, Display error ?");
-        };
-
-        let font_set = self.fonts.get_font_set(font_set_id).unwrap();
-        let outline_width = FontSet::get_outline_width(font_set.get_font_size(), 2) as u32;
-        let regular_font = font_set.get_regular_font();
-        let light_bold_font = font_set.get_light_outline_font();
-        let bold_font = font_set.get_outline_font();
-        let regular_surface = regular_font.render(text_subunit.text.as_str())
-            .blended(text_subunit.color)
-            .unwrap();
-        let (regular_w, regular_h) = regular_surface.size();
-        let mut surface = Surface::new(regular_w + outline_width * 2,
-                                       regular_h + outline_width * 2,
-                                       ARGB8888)
-            .expect("Failed to create new Surface");
-        let _ = surface.set_blend_mode(BlendMode::Blend);
-        match text_subunit.outline {
-            Outline::None => {}
-            Outline::Light(color) => {
-                let light_outline_width =
-                    FontSet::get_outline_width(font_set.get_font_size(), 1) as u32;
-                blit_font_text(&mut surface,
-                               light_bold_font,
-                               &text_subunit.text,
-                               color.to_sdl_color(),
-                               outline_width.saturating_sub(light_outline_width));
-            }
-            Outline::Bold(color) => {
-                blit_font_text(&mut surface,
-                               bold_font,
-                               &text_subunit.text,
-                               color.to_sdl_color(),
-                               0);
-            }
-        };
-        blit_font_text(&mut surface,
-                       regular_font,
-                       &text_subunit.text,
-                       text_subunit.color.to_sdl_color(),
-                       outline_width);
+        let surface = render_text_subunit(&self.fonts[font_set_id], text_subunit);
+        let (surface_w, surface_h) = surface.size();
         let mut texture = self.texture_creator
             .create_texture_from_surface(surface)
             .expect("Failed to create Texture from Surface");
         let _ = texture.set_blend_mode(BlendMode::Blend);
         texture.set_alpha_mod(text_subunit.color.alpha);
-        let text_rect = SdlRect::new(origin.0,
-                                     origin.1,
-                                     regular_w + outline_width * 2,
-                                     regular_h + outline_width * 2);
+        let text_rect = SdlRect::new(origin.0, origin.1, surface_w, surface_h);
         self.canvas
             .copy(&texture, None, Some(text_rect.clone()))
             .unwrap();
@@ -278,3 +173,35 @@ impl<'r,'ttf> Display for SDLDisplayer<'r,'ttf> {
             .collect()
     }
 }
+
+impl<'r,'ttf> Screen for SDLDisplayer<'r,'ttf> {
+    fn size(&self) -> (u32, u32) {
+        self.sdl_canvas().window().size()
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), String> {
+        self.sdl_canvas_mut().window_mut().set_title(title).map_err(|e| format!("{}", e))
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<(), String> {
+        use sdl2::video::FullscreenType;
+        let new_fullscreen_type = match self.sdl_canvas().window().fullscreen_state() {
+            FullscreenType::True | FullscreenType::Desktop => {
+                // TODO warn if 'True'
+                FullscreenType::Off
+            }
+            FullscreenType::Off => FullscreenType::Desktop,
+        };
+        self.sdl_canvas_mut().window_mut().set_fullscreen(new_fullscreen_type)
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Color) -> Result<(), String> {
+        let canvas = self.sdl_canvas_mut();
+        canvas.set_draw_color(color.to_sdl_color());
+        canvas.fill_rect(SdlRect::from(rect))
+    }
+
+    fn render(&mut self) {
+        self.canvas.window().gl_swap_window();
+    }
+}
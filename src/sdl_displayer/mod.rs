@@ -1,5 +1,8 @@
 mod font;
+mod text;
 mod displayer;
+mod software;
 mod display_params;
 pub use self::displayer::*;
+pub use self::software::*;
 pub use self::display_params::*;
@@ -0,0 +1,193 @@
+use sdl2::render::BlendMode;
+use sdl2::image::{LoadSurface, SaveSurface, Sdl2ImageContext, INIT_PNG, init as image_init};
+use sdl2::ttf::Sdl2TtfContext;
+use sdl2::rect::Rect as SdlRect;
+use sdl2::surface::Surface;
+use sdl2::pixels::PixelFormatEnum::ARGB8888;
+
+use std::path::Path;
+use ::overlay::*;
+use super::font::*;
+use super::text::*;
+use super::*;
+
+/// Draws overlay frames in memory instead of a window, the same way
+/// SDLDisplayer does. Nothing here needs the SDL video subsystem, so
+/// it works without any display.
+pub struct SoftwareDisplayer<'ttf> {
+    pub fonts: FontList<'ttf>,
+    surface: Surface<'static>,
+    lyrics_logo: Option<Surface<'static>>,
+    /// SDL_image is shut down when this is dropped
+    #[allow(dead_code)]
+    image_context: Sdl2ImageContext,
+}
+
+impl<'ttf> SoftwareDisplayer<'ttf> {
+    pub fn new(ttf_context: &'ttf Sdl2TtfContext,
+               (width, height): (u32, u32))
+               -> Result<SoftwareDisplayer<'ttf>, String> {
+        let font_list = try!(FontList::new(ttf_context));
+        let image_context = try!(image_init(INIT_PNG));
+        let mut surface = try!(Surface::new(width, height, ARGB8888));
+        let _ = surface.set_blend_mode(BlendMode::Blend);
+        let lyrics_logo = match ::std::env::current_exe() {
+            Ok(current_exe_path) => {
+                match Surface::from_file(&*current_exe_path.with_file_name("logo_toyunda.png")) {
+                    Ok(surface) => Some(surface),
+                    Err(e) => {
+                        warn!("Failed to load logo_toyunda.png : error '{}'", e);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        Ok(SoftwareDisplayer {
+            fonts: font_list,
+            surface: surface,
+            lyrics_logo: lyrics_logo,
+            image_context: image_context,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn size(&self) -> (u32, u32) {
+        self.surface.size()
+    }
+
+    /// fills the whole buffer, use an alpha of 0 for a transparent background
+    pub fn clear(&mut self, color: AlphaColor) {
+        let rect = self.surface.rect();
+        self.surface
+            .fill_rect(rect, color.to_sdl_color())
+            .expect("Failed to clear surface");
+    }
+
+    /// content of the buffer, 4 bytes per pixel in the RGBA order, row by row
+    #[cfg(test)]
+    pub fn to_rgba(&self) -> Vec<u8> {
+        let (width, height) = self.surface.size();
+        let pitch = self.surface.pitch() as usize;
+        let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+        self.surface.with_lock(|pixels| {
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let i = y * pitch + x * 4;
+                    // ARGB8888 is a packed format : the byte order depends
+                    // on the endianness
+                    let argb: u32 = if cfg!(target_endian = "big") {
+                        (pixels[i] as u32) << 24 | (pixels[i + 1] as u32) << 16 |
+                        (pixels[i + 2] as u32) << 8 | pixels[i + 3] as u32
+                    } else {
+                        (pixels[i + 3] as u32) << 24 | (pixels[i + 2] as u32) << 16 |
+                        (pixels[i + 1] as u32) << 8 | pixels[i] as u32
+                    };
+                    rgba.push((argb >> 16) as u8);
+                    rgba.push((argb >> 8) as u8);
+                    rgba.push(argb as u8);
+                    rgba.push((argb >> 24) as u8);
+                }
+            }
+        });
+        rgba
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        self.surface.save(path)
+    }
+
+    fn display_unit(&mut self, text_unit: &TextUnit, params: &SDLDisplayParameters) -> Rect {
+        let (offset_x, offset_y) = params.offset.unwrap_or((0, 0));
+        let canevas_size = params.output_size.unwrap_or(self.surface.size());
+        let layout = layout_text_unit(&self.fonts, text_unit, canevas_size);
+        let (text_pos_x, text_pos_y) = layout.pos;
+        let mut width_offset: u32 = 0;
+        for text_subunit in text_unit.text.iter() {
+            let mut subunit_surface = render_text_subunit(&self.fonts[layout.font_set_id],
+                                                          text_subunit);
+            let _ = subunit_surface.set_alpha_mod(text_subunit.color.alpha);
+            let (w, h) = subunit_surface.size();
+            let syllable_rect = SdlRect::new(offset_x + text_pos_x + width_offset as i32,
+                                             offset_y + text_pos_y,
+                                             w,
+                                             h);
+            subunit_surface.blit(None, &mut self.surface, Some(syllable_rect))
+                .expect("Failed to blit surface, Display error ?");
+            if text_subunit.attach_logo {
+                if let Some(ref logo) = self.lyrics_logo {
+                    logo.blit_scaled(None, &mut self.surface, Some(logo_rect(syllable_rect)))
+                        .expect("Failed to blit logo");
+                }
+            }
+            width_offset = width_offset +
+                           text_subunit_width(&self.fonts[layout.font_set_id], text_subunit);
+        }
+        Rect {
+            x: offset_x + text_pos_x,
+            y: offset_y + text_pos_y,
+            width: layout.size.0,
+            height: layout.size.1,
+        }
+    }
+}
+
+impl<'ttf> Display for SoftwareDisplayer<'ttf> {
+    type Parameters = SDLDisplayParameters;
+    fn display(&mut self,
+               overlay_frame: &OverlayFrame,
+               params: &SDLDisplayParameters)
+               -> Vec<Rect> {
+        overlay_frame.text_units
+            .iter()
+            .map(|text_unit| self.display_unit(text_unit, params))
+            .collect()
+    }
+}
+
+#[test]
+fn test_software_displayer_pixels() {
+    use ::overlay::pos::*;
+    use ::utils::{RGB, RGBA};
+    let ttf_context = ::sdl2::ttf::init().unwrap();
+    let mut displayer = SoftwareDisplayer::new(&ttf_context, (320, 180)).unwrap();
+    assert_eq!(displayer.size(), (320, 180));
+    displayer.clear(AlphaColor::new(255, 0, 0));
+    assert_eq!(&displayer.to_rgba()[0..4], &[255, 0, 0, 255]);
+    displayer.clear(AlphaColor::new_rgba(0, 0, 0, 0));
+    let overlay_frame = OverlayFrame {
+        text_units: vec![TextUnit {
+                             text: vec![TextSubUnit {
+                                            text: String::from("Toyunda"),
+                                            attach_logo: false,
+                                            color: AlphaColor::new(255, 255, 255),
+                                            outline: Outline::None,
+                                            shadow: None,
+                                        }],
+                             size: Size::FitPercent(Some(0.8), Some(0.3)),
+                             pos: (PosX::Centered, PosY::Centered),
+                             anchor: (0.5, 0.5),
+                         }],
+    };
+    let params = SDLDisplayParameters {
+        output_size: None,
+        offset: None,
+    };
+    let rect = displayer.display(&overlay_frame, &params)[0];
+    let rgba = displayer.to_rgba();
+    assert_eq!(rgba.len(), 320 * 180 * 4);
+    let pixel = |x: i32, y: i32| {
+        let i = (y as usize * 320 + x as usize) * 4;
+        &rgba[i..i + 4]
+    };
+    // the text is in the middle, in plain white, over a transparent background
+    assert!((rect.x + rect.width as i32 / 2 - 160).abs() <= 1);
+    assert!((rect.y + rect.height as i32 / 2 - 90).abs() <= 1);
+    assert_eq!(pixel(0, 0), &[0, 0, 0, 0]);
+    assert_eq!(pixel(319, 179), &[0, 0, 0, 0]);
+    let white_pixels = (rect.y..rect.y + rect.height as i32)
+        .flat_map(|y| (rect.x..rect.x + rect.width as i32).map(move |x| (x, y)))
+        .filter(|&(x, y)| pixel(x, y) == &[255, 255, 255, 255])
+        .count();
+    assert!(white_pixels > 100);
+}
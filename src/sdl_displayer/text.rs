@@ -0,0 +1,137 @@
+use sdl2::render::BlendMode;
+use sdl2::ttf::Font;
+use sdl2::rect::Rect as SdlRect;
+use sdl2::surface::Surface;
+use sdl2::pixels::Color as SdlColor;
+use sdl2::pixels::PixelFormatEnum::ARGB8888;
+use utils::fit_dims;
+
+use std::ops::DerefMut;
+use ::overlay::*;
+use ::overlay::pos::*;
+use super::font::*;
+
+/// where a text unit will be drawn, shared by every displayer so they all
+/// look the same
+pub struct UnitLayout {
+    pub font_set_id: usize,
+    /// top left corner of the text, offset not included
+    pub pos: (i32, i32),
+    /// size of the whole text with its outline
+    pub size: (u32, u32),
+}
+
+pub fn layout_text_unit(fonts: &FontList,
+                        text_unit: &TextUnit,
+                        (canevas_width, canevas_height): (u32, u32))
+                        -> UnitLayout {
+    let (fit_width, fit_height): (Option<u32>, Option<u32>) = match text_unit.size {
+        Size::FitPercent(option_x, option_y) => {
+            fit_dims((canevas_width, canevas_height), option_x, option_y)
+        }
+        Size::Fit(x, y) => (x, y),
+    };
+    let max_outline = text_unit.text
+        .iter()
+        .map(|text_element| text_element.outline)
+        .max()
+        .map(|max_outline| max_outline.to_size())
+        .unwrap_or(0);
+    let all_text = text_unit.to_string();
+    let font_set_id = fonts
+        .get_fittest_font_set_id(all_text.as_str(), (fit_width, fit_height), max_outline)
+        .unwrap();
+    let (text_width, text_height) = fonts.get_font_set(font_set_id)
+        .unwrap()
+        .get_outline_font()
+        .size_of(all_text.as_str())
+        .expect("Unable to get outline pixel size of str");
+    let text_pos = real_position((canevas_width, canevas_height),
+                                 text_unit.pos,
+                                 text_unit.anchor,
+                                 (text_width, text_height));
+    UnitLayout {
+        font_set_id: font_set_id,
+        pos: text_pos,
+        size: (text_width, text_height),
+    }
+}
+
+/// width of the subunit without its outline, used to place the next one
+pub fn text_subunit_width(font_set: &FontSet, text_subunit: &TextSubUnit) -> u32 {
+    font_set.get_regular_font()
+        .size_of(text_subunit.text.as_str())
+        .map(|(w, _)| w)
+        .unwrap_or(0)
+}
+
+/// where the logo goes when a syllable has `attach_logo`
+pub fn logo_rect(syllable_rect: SdlRect) -> SdlRect {
+    let (syllable_center_x, _) = syllable_rect.center().into();
+    let syllable_bottom = syllable_rect.bottom();
+    let syllable_height = syllable_rect.height();
+    let logo_height = syllable_height * 70 / 100;
+    SdlRect::new(syllable_center_x - (logo_height / 2) as i32,
+                 syllable_bottom - syllable_height as i32 / 16,
+                 logo_height,
+                 logo_height)
+}
+
+/// renders the text with its outline, the alpha of the text color is not
+/// applied : it's up to the caller
+pub fn render_text_subunit(font_set: &FontSet, text_subunit: &TextSubUnit) -> Surface<'static> {
+    fn blit_font_text(dest: &mut Surface,
+                      font: &Font,
+                      text: &str,
+                      color: SdlColor,
+                      delta_outline: u32) {
+        let (dest_w, dest_h) = dest.size();
+        let subdest_rect = SdlRect::new(delta_outline as i32,
+                                        delta_outline as i32,
+                                        dest_w - (delta_outline * 2),
+                                        dest_h - (delta_outline * 2));
+        let mut font_surface = font.render(text)
+            .blended(color)
+            .unwrap();
+        let _ = font_surface.set_blend_mode(BlendMode::Blend);
+        font_surface.blit(None, dest.deref_mut(), Some(subdest_rect))
+            .expect("Failed to blit surface, Display error ?");
+    };
+
+    let outline_width = FontSet::get_outline_width(font_set.get_font_size(), 2) as u32;
+    let regular_font = font_set.get_regular_font();
+    let light_bold_font = font_set.get_light_outline_font();
+    let bold_font = font_set.get_outline_font();
+    let (regular_w, regular_h) = regular_font.size_of(text_subunit.text.as_str())
+        .expect("Unable to get pixel size of str");
+    let mut surface = Surface::new(regular_w + outline_width * 2,
+                                   regular_h + outline_width * 2,
+                                   ARGB8888)
+        .expect("Failed to create new Surface");
+    let _ = surface.set_blend_mode(BlendMode::Blend);
+    match text_subunit.outline {
+        Outline::None => {}
+        Outline::Light(color) => {
+            let light_outline_width =
+                FontSet::get_outline_width(font_set.get_font_size(), 1) as u32;
+            blit_font_text(&mut surface,
+                           light_bold_font,
+                           &text_subunit.text,
+                           color.to_sdl_color(),
+                           outline_width.saturating_sub(light_outline_width));
+        }
+        Outline::Bold(color) => {
+            blit_font_text(&mut surface,
+                           bold_font,
+                           &text_subunit.text,
+                           color.to_sdl_color(),
+                           0);
+        }
+    };
+    blit_font_text(&mut surface,
+                   regular_font,
+                   &text_subunit.text,
+                   text_subunit.color.to_sdl_color(),
+                   outline_width);
+    surface
+}
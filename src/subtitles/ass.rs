@@ -0,0 +1,682 @@
+use super::{Subtitles, SubtitlesOptions, Sentence, Syllable, SentenceOptions, SentenceParameters,
+            SyllableOptions, SyllableParameters, AsSentenceOptions, AsSyllableOptions, Color,
+            Outline, Error};
+use std::cmp::min;
+use super::pos::{RowPosition, Point};
+
+/// Every position and size of the exported script is computed against this resolution
+const PLAY_RES_X: u32 = 1920;
+const PLAY_RES_Y: u32 = 1080;
+
+const FONT_NAME: &'static str = "DejaVu Sans Mono";
+
+/// outline sizes are 0, 1 or 2 in toyunda, this is how many pixels one
+/// of these is worth at `PLAY_RES_Y`
+const OUTLINE_PIXELS: u8 = 2;
+
+/// color of a style line : &HAABBGGRR
+fn ass_style_color(color: &Color) -> String {
+    format!("&H00{:02X}{:02X}{:02X}", color.blue, color.green, color.red)
+}
+
+/// color of an override tag : &HBBGGRR&
+fn ass_override_color(color: &Color) -> String {
+    format!("&H{:02X}{:02X}{:02X}&", color.blue, color.green, color.red)
+}
+
+/// time in ms to H:MM:SS.cc
+fn ass_time(ms: u32) -> String {
+    let cs = ms / 10;
+    format!("{}:{:02}:{:02}.{:02}",
+            cs / 360000,
+            (cs / 6000) % 60,
+            (cs / 100) % 60,
+            cs % 100)
+}
+
+/// number of centiseconds between 2 times in ms, rounded so that
+/// consecutive durations never drift from the absolute times
+fn ass_duration(from: u32, to: u32) -> u32 {
+    (to / 10).saturating_sub(from / 10)
+}
+
+/// the transition color has no equivalent in ASS : `\kf` fills the syllable
+/// from the secondary color (alive) to the primary color (dead)
+fn same_style(a: &SyllableParameters, b: &SyllableParameters) -> bool {
+    a.alive_color == b.alive_color && a.dead_color == b.dead_color &&
+    a.outline.color == b.outline.color && a.outline.size == b.outline.size
+}
+
+fn style_line(name: &str, params: &SyllableParameters, font_size: u32) -> String {
+    format!("Style: {},{},{},{},{},{},&H00000000,0,0,0,0,100,100,0,0,1,{},0,8,10,10,0,1",
+            name,
+            FONT_NAME,
+            font_size,
+            ass_style_color(&params.dead_color),
+            ass_style_color(&params.alive_color),
+            ass_style_color(&params.outline.color),
+            params.outline.size * OUTLINE_PIXELS)
+}
+
+fn style_name(index: usize) -> String {
+    if index == 0 {
+        String::from("Default")
+    } else {
+        format!("Toyunda{}", index)
+    }
+}
+
+/// returns the index of the style matching these parameters, creating a new one
+/// if none matches
+fn find_or_insert_style(styles: &mut Vec<SyllableParameters>,
+                        params: SyllableParameters)
+                        -> usize {
+    match styles.iter().position(|s| same_style(s, &params)) {
+        Some(index) => index,
+        None => {
+            styles.push(params);
+            styles.len() - 1
+        }
+    }
+}
+
+fn sentence_to_dialogue(sentence: &Sentence,
+                        default_sentence_options: Option<&SentenceOptions>,
+                        styles: &mut Vec<SyllableParameters>)
+                        -> Option<String> {
+    let (first_begin, last_end) = match (sentence.syllables.first(), sentence.syllables.last()) {
+        (Some(&Syllable { begin, .. }), Some(&Syllable { end: Some(end), .. })) => (begin, end),
+        _ => return None,
+    };
+    let sentence_options: Option<SentenceOptions> =
+        sentence.or_sentence_options(default_sentence_options);
+    let sentence_parameters =
+        SentenceParameters::from((sentence_options.clone().unwrap_or(SentenceOptions::default()),
+                                  0));
+    let sentence_syllable_options: Option<SyllableOptions> =
+        sentence_options.as_syllable_options(0);
+    let sentence_syllable_parameters =
+        SyllableParameters::from(sentence_syllable_options.unwrap_or(SyllableOptions::default()));
+    let style = style_name(find_or_insert_style(styles, sentence_syllable_parameters.clone()));
+    let dialogue_begin =
+        first_begin.saturating_sub(sentence_parameters.transition_time_before as u32);
+    let dialogue_end = last_end.saturating_add(sentence_parameters.transition_time_after as u32);
+
+    let mut text = format!("{{\\fad({},{})",
+                           sentence_parameters.fade_time_before,
+                           sentence_parameters.fade_time_after);
+    let mut margin_v = 0;
+    match sentence_parameters.row_position.unwrap_or(sentence.position) {
+        RowPosition::Row(l) => {
+            margin_v = ((l as f32 * 0.15 + 0.01) * PLAY_RES_Y as f32) as u32;
+        }
+        RowPosition::ForcePos(Point { x, y }) => {
+            text.push_str(&format!("\\an8\\pos({},{})",
+                                   (x * PLAY_RES_X as f32) as u32,
+                                   (y * PLAY_RES_Y as f32) as u32));
+        }
+    };
+    text.push('}');
+
+    let mut cursor = dialogue_begin;
+    // override tags last until the end of the line, they must be reset
+    // once the syllable they were set for is over
+    let mut overridden = false;
+    for (n, syllable) in sentence.syllables.iter().enumerate() {
+        let syllable_end = syllable.end
+            .or(sentence.syllables.get(n + 1).map(|s| s.begin.saturating_sub(1)))
+            .unwrap_or(syllable.begin);
+        if syllable.begin > cursor {
+            // nothing is sung between the 2 syllables
+            text.push_str(&format!("{{\\k{}}}", ass_duration(cursor, syllable.begin)));
+            cursor = syllable.begin;
+        }
+        text.push_str(&format!("{{\\kf{}", ass_duration(cursor, syllable_end)));
+        let syllable_parameters = SyllableParameters::from(syllable.syllable_options
+            .or_syllable_options(sentence_syllable_options.as_ref())
+            .unwrap_or(SyllableOptions::default()));
+        if !same_style(&syllable_parameters, &sentence_syllable_parameters) {
+            text.push_str(&format!("\\1c{}\\2c{}\\3c{}\\bord{}",
+                                   ass_override_color(&syllable_parameters.dead_color),
+                                   ass_override_color(&syllable_parameters.alive_color),
+                                   ass_override_color(&syllable_parameters.outline.color),
+                                   syllable_parameters.outline.size * OUTLINE_PIXELS));
+            overridden = true;
+        } else if overridden {
+            text.push_str("\\r");
+            overridden = false;
+        }
+        text.push('}');
+        text.push_str(&syllable.text.replace('\n', " "));
+        if syllable_end > cursor {
+            cursor = syllable_end;
+        }
+    }
+    Some(format!("Dialogue: 0,{},{},{},,0,0,{},,{}",
+                 ass_time(dialogue_begin),
+                 ass_time(dialogue_end),
+                 style,
+                 margin_v,
+                 text))
+}
+
+impl Subtitles {
+    /// Exports the subtitles as an Advanced SubStation Alpha script, with one
+    /// `Dialogue` line per sentence and `\kf` tags for every syllable.
+    ///
+    /// Rows must have been computed beforehand (see `post_init`)
+    pub fn to_ass(&self) -> String {
+        let default_sentence_options: Option<&SentenceOptions> = self.subtitles_options
+            .as_sentence_options();
+        let default_syllable_parameters = SyllableParameters::from(default_sentence_options
+            .and_then(|o| o.syllable_options)
+            .unwrap_or(SyllableOptions::default()));
+        let default_sentence_parameters =
+            SentenceParameters::from((default_sentence_options.cloned()
+                                          .unwrap_or(SentenceOptions::default()),
+                                      0));
+        let font_size = (default_sentence_parameters.size.height.unwrap_or(0.09) *
+                         PLAY_RES_Y as f32) as u32;
+        // the first style is always "Default"
+        let mut styles: Vec<SyllableParameters> = vec![default_syllable_parameters];
+        let events: Vec<String> = self.sentences
+            .iter()
+            .filter_map(|sentence| {
+                sentence_to_dialogue(sentence, default_sentence_options, &mut styles)
+            })
+            .collect();
+
+        let mut ass = String::new();
+        ass.push_str("[Script Info]\n");
+        ass.push_str("; Generated by toyunda-player-rs\n");
+        if let Some((title, _)) = self.credit_sentences() {
+            ass.push_str(&format!("Title: {}\n", title));
+        }
+        ass.push_str("ScriptType: v4.00+\n");
+        ass.push_str("WrapStyle: 2\n");
+        ass.push_str("ScaledBorderAndShadow: yes\n");
+        ass.push_str(&format!("PlayResX: {}\nPlayResY: {}\n\n", PLAY_RES_X, PLAY_RES_Y));
+        ass.push_str("[V4+ Styles]\n");
+        ass.push_str("Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, \
+                      OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, \
+                      ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, \
+                      MarginR, MarginV, Encoding\n");
+        for (n, style) in styles.iter().enumerate() {
+            ass.push_str(&style_line(&style_name(n), style, font_size));
+            ass.push('\n');
+        }
+        ass.push_str("\n[Events]\n");
+        ass.push_str("Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, \
+                      Text\n");
+        for event in events {
+            ass.push_str(&event);
+            ass.push('\n');
+        }
+        ass
+    }
+}
+
+/// Resolution of scripts which do not tell theirs
+const DEFAULT_ASS_PLAY_RES: (u32, u32) = (384, 288);
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum AssSection {
+    ScriptInfo,
+    /// [V4+ Styles]
+    Styles,
+    /// [V4 Styles], alignments are numbered differently
+    LegacyStyles,
+    Events,
+    Other,
+}
+
+#[derive(Debug,Clone)]
+struct AssStyle {
+    name: String,
+    syllable_options: SyllableOptions,
+    top_aligned: bool,
+    margin_v: u32,
+}
+
+/// H:MM:SS.cc to time in ms
+fn parse_ass_time(time: &str) -> Result<u32, String> {
+    let parts: Vec<&str> = time.trim().split(':').collect();
+    if parts.len() != 3 {
+        return Err(format!("invalid time `{}`", time.trim()));
+    }
+    let invalid = |e: &::std::fmt::Display| format!("invalid time `{}` : {}", time.trim(), e);
+    let hours = try!(parts[0].parse::<u32>().map_err(|e| invalid(&e)));
+    let minutes = try!(parts[1].parse::<u32>().map_err(|e| invalid(&e)));
+    let seconds = try!(parts[2].parse::<f64>().map_err(|e| invalid(&e)));
+    Ok(hours * 3600000 + minutes * 60000 + (seconds * 1000.0).round() as u32)
+}
+
+/// &HAABBGGRR, &HBBGGRR& or a decimal BGR value for SSA
+fn parse_ass_color(color: &str) -> Option<Color> {
+    let color = color.trim().trim_matches('&');
+    let value = if color.starts_with('H') || color.starts_with('h') {
+        u32::from_str_radix(&color[1..], 16).ok()
+    } else {
+        color.parse::<i64>().ok().map(|v| v as u32)
+    };
+    value.map(|value| {
+        Color {
+            red: (value & 0xFF) as u8,
+            green: ((value >> 8) & 0xFF) as u8,
+            blue: ((value >> 16) & 0xFF) as u8,
+        }
+    })
+}
+
+/// ASS outline in pixels at `play_res_y` to a toyunda outline size
+fn outline_size(pixels: f32, play_res_y: u32) -> u8 {
+    let pixels = pixels * PLAY_RES_Y as f32 / play_res_y as f32;
+    min((pixels / OUTLINE_PIXELS as f32).round().max(0.0) as u8, 2)
+}
+
+fn field<'a>(format: &[String], values: &[&'a str], name: &str) -> Option<&'a str> {
+    format.iter().position(|f| f == name).and_then(|i| values.get(i)).map(|v| v.trim())
+}
+
+fn parse_format(line: &str) -> Vec<String> {
+    line.split(',').map(|s| s.trim().to_lowercase()).collect()
+}
+
+fn parse_style(format: &[String], line: &str, legacy: bool, play_res_y: u32) -> AssStyle {
+    let values: Vec<&str> = line.splitn(format.len(), ',').collect();
+    let outline_color = field(format, &values, "outlinecolour")
+        .or(field(format, &values, "tertiarycolour"))
+        .and_then(parse_ass_color);
+    let outline = field(format, &values, "outline")
+        .and_then(|o| o.parse::<f32>().ok())
+        .map(|o| {
+            Outline {
+                color: outline_color.unwrap_or(Color::default()),
+                size: outline_size(o, play_res_y),
+            }
+        });
+    let alignment = field(format, &values, "alignment")
+        .and_then(|a| a.parse::<u8>().ok())
+        .unwrap_or(2);
+    AssStyle {
+        name: String::from(field(format, &values, "name").unwrap_or("Default")),
+        syllable_options: SyllableOptions {
+            alive_color: field(format, &values, "secondarycolour").and_then(parse_ass_color),
+            transition_color: None,
+            dead_color: field(format, &values, "primarycolour").and_then(parse_ass_color),
+            outline: outline,
+        },
+        top_aligned: if legacy {
+            alignment >= 5 && alignment <= 7
+        } else {
+            alignment >= 7 && alignment <= 9
+        },
+        margin_v: field(format, &values, "marginv")
+            .and_then(|m| m.parse::<u32>().ok())
+            .unwrap_or(0),
+    }
+}
+
+fn is_empty_options(options: &SyllableOptions) -> bool {
+    options.alive_color.is_none() && options.transition_color.is_none() &&
+    options.dead_color.is_none() && options.outline.is_none()
+}
+
+/// Reads the text of a Dialogue line, syllable by syllable
+struct AssLineParser {
+    syllables: Vec<Syllable>,
+    cursor: u32,
+    text: String,
+    /// duration of the syllable being read, None before the first karaoke tag
+    duration: Option<u32>,
+    overrides: SyllableOptions,
+    position: Option<Point>,
+    fade: Option<(u16, u16)>,
+    play_res_y: u32,
+}
+
+impl AssLineParser {
+    fn flush(&mut self) {
+        if let Some(duration) = self.duration {
+            if !self.text.is_empty() {
+                self.syllables.push(Syllable {
+                    text: self.text.clone(),
+                    begin: self.cursor,
+                    end: Some(self.cursor + duration),
+                    syllable_options: if is_empty_options(&self.overrides) {
+                        None
+                    } else {
+                        Some(self.overrides)
+                    },
+                    pitch: None,
+                });
+                self.text.clear();
+            }
+            // empty karaoke syllables are only there to wait
+            self.cursor = self.cursor + duration;
+        }
+    }
+
+    fn parse_override_block(&mut self, block: &str, play_res: (u32, u32)) {
+        for tag in block.split('\\').map(|t| t.trim()).filter(|t| !t.is_empty()) {
+            let karaoke_duration = if tag.starts_with("kf") || tag.starts_with("ko") {
+                Some(&tag[2..])
+            } else if tag.starts_with('k') || tag.starts_with('K') {
+                Some(&tag[1..])
+            } else {
+                None
+            };
+            if let Some(duration) = karaoke_duration {
+                if let Ok(duration) = duration.parse::<u32>() {
+                    self.flush();
+                    self.duration = Some(duration * 10);
+                    continue;
+                }
+            }
+            if tag.starts_with("1c") {
+                self.overrides.dead_color = parse_ass_color(&tag[2..]);
+            } else if tag.starts_with("c&") {
+                self.overrides.dead_color = parse_ass_color(&tag[1..]);
+            } else if tag.starts_with("2c") {
+                self.overrides.alive_color = parse_ass_color(&tag[2..]);
+            } else if tag.starts_with("3c") {
+                let color = parse_ass_color(&tag[2..]).unwrap_or(Color::default());
+                let size = self.overrides.outline.map(|o| o.size).unwrap_or(1);
+                self.overrides.outline = Some(Outline {
+                    color: color,
+                    size: size,
+                });
+            } else if tag.starts_with("bord") {
+                if let Ok(pixels) = tag[4..].parse::<f32>() {
+                    let color = self.overrides
+                        .outline
+                        .map(|o| o.color)
+                        .unwrap_or(Color::default());
+                    self.overrides.outline = Some(Outline {
+                        color: color,
+                        size: outline_size(pixels, self.play_res_y),
+                    });
+                }
+            } else if tag == "r" {
+                self.overrides = SyllableOptions::default();
+            } else if tag.starts_with("pos(") {
+                let coords: Vec<f32> = tag[4..]
+                    .split(|c| c == ',' || c == ')')
+                    .filter_map(|c| c.trim().parse::<f32>().ok())
+                    .collect();
+                if coords.len() == 2 {
+                    self.position = Some(Point {
+                        x: coords[0] / play_res.0 as f32,
+                        y: coords[1] / play_res.1 as f32,
+                    });
+                }
+            } else if tag.starts_with("fad(") {
+                let times: Vec<u16> = tag[4..]
+                    .split(|c| c == ',' || c == ')')
+                    .filter_map(|c| c.trim().parse::<u16>().ok())
+                    .collect();
+                if times.len() == 2 {
+                    self.fade = Some((times[0], times[1]));
+                }
+            }
+            // other tags don't map to anything in toyunda
+        }
+    }
+}
+
+fn parse_dialogue(text: &str,
+                  begin: u32,
+                  end: u32,
+                  style: Option<&AssStyle>,
+                  margin_v: u32,
+                  play_res: (u32, u32))
+                  -> Option<Sentence> {
+    let mut parser = AssLineParser {
+        syllables: Vec::new(),
+        cursor: begin,
+        text: String::new(),
+        duration: None,
+        overrides: SyllableOptions::default(),
+        position: None,
+        fade: None,
+        play_res_y: play_res.1,
+    };
+    let text = text.replace("\\N", " ").replace("\\n", " ").replace("\\h", " ");
+    let mut rest: &str = &text;
+    while !rest.is_empty() {
+        if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(block_end) => {
+                    parser.parse_override_block(&rest[1..block_end], play_res);
+                    rest = &rest[block_end + 1..];
+                }
+                None => {
+                    // unclosed block, treat it as text
+                    parser.text.push_str(rest);
+                    rest = "";
+                }
+            }
+        } else {
+            let text_end = rest.find('{').unwrap_or(rest.len());
+            parser.text.push_str(&rest[..text_end]);
+            rest = &rest[text_end..];
+        }
+    }
+    if parser.duration.is_some() {
+        parser.flush();
+    } else if !parser.text.is_empty() {
+        // not a karaoke line, the whole line is a single syllable
+        parser.duration = Some(end.saturating_sub(begin));
+        parser.flush();
+    }
+    let (first_begin, last_end) = match (parser.syllables.first(), parser.syllables.last()) {
+        (Some(&Syllable { begin, .. }), Some(&Syllable { end: Some(end), .. })) => (begin, end),
+        _ => return None,
+    };
+    let transition_time_before = min(first_begin.saturating_sub(begin),
+                                     ::std::u16::MAX as u32) as u16;
+    let transition_time_after = min(end.saturating_sub(last_end),
+                                    ::std::u16::MAX as u32) as u16;
+    let (fade_in, fade_out) = parser.fade.unwrap_or((200, 200));
+    let row_position = match (parser.position, style) {
+        (Some(point), _) => Some(RowPosition::ForcePos(point)),
+        (None, Some(style)) if style.top_aligned => {
+            let margin_v = if margin_v != 0 { margin_v } else { style.margin_v };
+            let row = ((margin_v as f32 / play_res.1 as f32 - 0.01) / 0.15).round().max(0.0);
+            Some(RowPosition::Row(row as u8))
+        }
+        _ => None,
+    };
+    Some(Sentence {
+        syllables: parser.syllables,
+        position: RowPosition::default(),
+        sentence_options: Some(SentenceOptions {
+            syllable_options: style.map(|s| s.syllable_options),
+            transition_time_before: Some(transition_time_before),
+            // a fade longer than the transition would underflow
+            fade_time_before: Some(min(fade_in, transition_time_before)),
+            transition_time_after: Some(transition_time_after),
+            fade_time_after: Some(min(fade_out, transition_time_after)),
+            row_position: row_position,
+            ..SentenceOptions::default()
+        }),
+    })
+}
+
+impl Subtitles {
+    /// Imports an Advanced SubStation Alpha (or SubStation Alpha) script.
+    ///
+    /// `\k`, `\kf` and `\ko` tags become syllables, lines without any karaoke
+    /// tag become a sentence with a single syllable.
+    pub fn from_ass(content: &str) -> Result<Subtitles, Error> {
+        let content = if content.starts_with('\u{feff}') {
+            &content['\u{feff}'.len_utf8()..]
+        } else {
+            content
+        };
+        let mut section = AssSection::Other;
+        let mut play_res: (Option<u32>, Option<u32>) = (None, None);
+        let mut styles: Vec<AssStyle> = Vec::new();
+        let mut style_format: Vec<String> = Vec::new();
+        let mut event_format: Vec<String> = Vec::new();
+        let mut events: Vec<(u32, u32, String, u32, String)> = Vec::new();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                section = match line.to_lowercase().as_str() {
+                    "[script info]" => AssSection::ScriptInfo,
+                    "[v4+ styles]" => AssSection::Styles,
+                    "[v4 styles]" => AssSection::LegacyStyles,
+                    "[events]" => AssSection::Events,
+                    _ => AssSection::Other,
+                };
+                continue;
+            }
+            let (key, value) = match line.find(':') {
+                Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+                None => continue,
+            };
+            match (section, key) {
+                (AssSection::ScriptInfo, "PlayResX") => play_res.0 = value.parse::<u32>().ok(),
+                (AssSection::ScriptInfo, "PlayResY") => play_res.1 = value.parse::<u32>().ok(),
+                (AssSection::Styles, "Format") |
+                (AssSection::LegacyStyles, "Format") => style_format = parse_format(value),
+                (AssSection::Styles, "Style") |
+                (AssSection::LegacyStyles, "Style") => {
+                    if style_format.is_empty() {
+                        return Err(Error::parse(Some(line_number + 1), "Style before Format"));
+                    }
+                    let play_res_y = play_res.1.unwrap_or(DEFAULT_ASS_PLAY_RES.1);
+                    styles.push(parse_style(&style_format,
+                                            value,
+                                            section == AssSection::LegacyStyles,
+                                            play_res_y));
+                }
+                (AssSection::Events, "Format") => event_format = parse_format(value),
+                (AssSection::Events, "Dialogue") => {
+                    if event_format.is_empty() {
+                        return Err(Error::parse(Some(line_number + 1), "Dialogue before Format"));
+                    }
+                    let values: Vec<&str> = value.splitn(event_format.len(), ',').collect();
+                    let begin = try!(parse_ass_time(field(&event_format, &values, "start")
+                            .unwrap_or(""))
+                        .map_err(|e| Error::parse(Some(line_number + 1), e)));
+                    let end = try!(parse_ass_time(field(&event_format, &values, "end")
+                            .unwrap_or(""))
+                        .map_err(|e| Error::parse(Some(line_number + 1), e)));
+                    // "*Default" is the SSA way of saying "Default"
+                    let style = field(&event_format, &values, "style").unwrap_or("Default");
+                    let style = String::from(if style.starts_with('*') {
+                        &style[1..]
+                    } else {
+                        style
+                    });
+                    let margin_v = field(&event_format, &values, "marginv")
+                        .and_then(|m| m.parse::<u32>().ok())
+                        .unwrap_or(0);
+                    // the text may contain commas, it is always the last field
+                    let text = match event_format.iter().position(|f| f == "text") {
+                        Some(i) if i + 1 == event_format.len() => values.get(i).cloned(),
+                        _ => None,
+                    };
+                    match text {
+                        Some(text) => {
+                            events.push((begin, end, style, margin_v, String::from(text)))
+                        }
+                        None => {
+                            return Err(Error::parse(Some(line_number + 1),
+                                                    "Dialogue without text"))
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let play_res = match play_res {
+            (Some(x), Some(y)) => (x, y),
+            // if only one is set, the other one is deduced with a 4:3 ratio
+            (Some(x), None) => (x, x * 3 / 4),
+            (None, Some(y)) => (y * 4 / 3, y),
+            (None, None) => DEFAULT_ASS_PLAY_RES,
+        };
+        let default_style: Option<&AssStyle> = styles.iter()
+            .find(|s| s.name == "Default")
+            .or(styles.first());
+        let mut sentences: Vec<Sentence> = events.iter()
+            .filter_map(|&(begin, end, ref style_name, margin_v, ref text)| {
+                let style = styles.iter().find(|s| s.name == *style_name).or(default_style);
+                parse_dialogue(text, begin, end, style, margin_v, play_res)
+            })
+            .collect();
+        // the default style is global, remove it from the sentences using it
+        if let Some(default_style) = default_style {
+            for sentence in sentences.iter_mut() {
+                if let Some(ref mut sentence_options) = sentence.sentence_options {
+                    let uses_default = match sentence_options.syllable_options {
+                        Some(ref o) => same_options(o, &default_style.syllable_options),
+                        None => false,
+                    };
+                    if uses_default {
+                        sentence_options.syllable_options = None;
+                    }
+                }
+            }
+        }
+        sentences.sort_by_key(|s| s.syllables.first().map(|s| s.begin).unwrap_or(0));
+        Ok(Subtitles {
+            sentences: sentences,
+            subtitles_options: SubtitlesOptions {
+                sentence_options: default_style.map(|s| {
+                    SentenceOptions {
+                        syllable_options: Some(s.syllable_options),
+                        ..SentenceOptions::default()
+                    }
+                }),
+                ..SubtitlesOptions::default()
+            },
+            ..Subtitles::default()
+        })
+    }
+}
+
+fn same_options(a: &SyllableOptions, b: &SyllableOptions) -> bool {
+    fn same_outline(a: Option<Outline>, b: Option<Outline>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.color == b.color && a.size == b.size,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+    a.alive_color == b.alive_color && a.transition_color == b.transition_color &&
+    a.dead_color == b.dead_color && same_outline(a.outline, b.outline)
+}
+
+#[test]
+fn test_ass_time() {
+    assert_eq!(ass_time(0), "0:00:00.00");
+    assert_eq!(ass_time(3723456), "1:02:03.45");
+}
+
+#[test]
+fn test_ass_karaoke_line() {
+    let ass = "[Script Info]\nPlayResX: 1920\nPlayResY: 1080\n\n[V4+ Styles]\nFormat: Name, \
+               PrimaryColour, SecondaryColour, OutlineColour, Outline, Alignment, MarginV\nStyle: \
+               Default,&H00FFFF00,&H0000FFFF,&H00000000,2,8,10\n\n[Events]\nFormat: Layer, \
+               Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: \
+               0,0:00:00.20,0:00:02.30,Default,,0,0,0,,{\\k80}{\\kf20}Ka{\\kf30}ra, {\\k10}{\\ko30}o";
+    let subtitles = Subtitles::from_ass(ass).unwrap();
+    assert_eq!(subtitles.sentences.len(), 1);
+    let syllables = &subtitles.sentences[0].syllables;
+    assert_eq!(syllables.len(), 3);
+    assert_eq!((syllables[0].begin, syllables[0].end), (1000, Some(1200)));
+    assert_eq!(syllables[1].text, "ra, ");
+    assert_eq!((syllables[2].begin, syllables[2].end), (1600, Some(1900)));
+    let alive_color = subtitles.subtitles_options
+        .sentence_options
+        .and_then(|o| o.syllable_options)
+        .and_then(|o| o.alive_color);
+    assert_eq!(alive_color, Some(Color { red: 255, green: 255, blue: 0 }));
+}
@@ -0,0 +1,170 @@
+use std::error::Error as StdError;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use serde_json;
+
+#[derive(Debug)]
+pub enum Error {
+    /// the last syllable of the sentence has no end time
+    MissingEndTime { sentence: usize },
+    /// the syllable begins before the end of the previous one
+    OverlappingSyllables { sentence: usize, syllable: usize },
+    EmptySentence { sentence: usize },
+    /// the syllable ends as soon as it begins
+    ZeroLengthSyllable { sentence: usize, syllable: usize },
+    /// the syllable ends after the end of the video, `duration` is in ms
+    SyllableAfterEnd {
+        sentence: usize,
+        syllable: usize,
+        duration: u32,
+    },
+    /// both sentences are displayed on the same row at the same time
+    RowConflict {
+        sentence: usize,
+        other_sentence: usize,
+        row: u8,
+    },
+    /// lines start at 1; the file is only known once the error went
+    /// through `in_file`
+    Parse {
+        file: Option<PathBuf>,
+        line: Option<usize>,
+        message: String,
+    },
+    Io { file: PathBuf, error: io::Error },
+    Json(serde_json::Error),
+    /// the json file was written by a newer version of the player
+    UnsupportedVersion { version: u32, supported: u32 },
+}
+
+impl Error {
+    pub fn parse<S: Into<String>>(line: Option<usize>, message: S) -> Error {
+        Error::Parse {
+            file: None,
+            line: line,
+            message: message.into(),
+        }
+    }
+
+    /// tells which file a parse error comes from
+    pub fn in_file<P: Into<PathBuf>>(self, file: P) -> Error {
+        match self {
+            Error::Parse { file: None, line, message } => {
+                Error::Parse {
+                    file: Some(file.into()),
+                    line: line,
+                    message: message,
+                }
+            }
+            e => e,
+        }
+    }
+
+    /// these don't prevent the song from being played correctly
+    pub fn is_warning(&self) -> bool {
+        match *self {
+            Error::EmptySentence { .. } |
+            Error::ZeroLengthSyllable { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::MissingEndTime { .. } => "no end time for the last syllable of a sentence",
+            Error::OverlappingSyllables { .. } => "overlapping syllables",
+            Error::EmptySentence { .. } => "empty sentence",
+            Error::ZeroLengthSyllable { .. } => "zero-length syllable",
+            Error::SyllableAfterEnd { .. } => "syllable after the end of the video",
+            Error::RowConflict { .. } => "sentences displayed on the same row",
+            Error::Parse { ref message, .. } => message.as_str(),
+            Error::Io { ref error, .. } => error.description(),
+            Error::Json(ref e) => e.description(),
+            Error::UnsupportedVersion { .. } => "unsupported format_version",
+        }
+    }
+
+    fn cause(&self) -> Option<&StdError> {
+        match *self {
+            Error::Io { ref error, .. } => Some(error),
+            Error::Json(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::MissingEndTime { sentence } => {
+                write!(f,
+                       "Error at sentence {}, no 'end' time for the last syllable",
+                       sentence)
+            }
+            Error::OverlappingSyllables { sentence, syllable } => {
+                write!(f,
+                       "Error at sentence {}, syllable {} begins before the end of the \
+                        previous one",
+                       sentence,
+                       syllable)
+            }
+            Error::EmptySentence { sentence } => write!(f, "Sentence {} has no syllable", sentence),
+            Error::ZeroLengthSyllable { sentence, syllable } => {
+                write!(f,
+                       "Error at sentence {}, syllable {} has a length of 0",
+                       sentence,
+                       syllable)
+            }
+            Error::SyllableAfterEnd { sentence, syllable, duration } => {
+                write!(f,
+                       "Error at sentence {}, syllable {} ends after the end of the video ({}ms)",
+                       sentence,
+                       syllable,
+                       duration)
+            }
+            Error::RowConflict { sentence, other_sentence, row } => {
+                write!(f,
+                       "Sentences {} and {} are displayed on row {} at the same time",
+                       sentence,
+                       other_sentence,
+                       row)
+            }
+            Error::Parse { ref file, ref line, ref message } => {
+                match (file, line) {
+                    (&Some(ref file), &Some(line)) => {
+                        write!(f,
+                               "Error while parsing '{}' at line {} : {}",
+                               file.display(),
+                               line,
+                               message)
+                    }
+                    (&Some(ref file), &None) => {
+                        write!(f, "Error while parsing '{}' : {}", file.display(), message)
+                    }
+                    (&None, &Some(line)) => write!(f, "Error at line {} : {}", line, message),
+                    (&None, &None) => write!(f, "{}", message),
+                }
+            }
+            Error::Io { ref file, ref error } => {
+                write!(f, "IoError with '{}' : {}", file.display(), error)
+            }
+            Error::Json(ref e) => write!(f, "JSON Error : {}", e),
+            Error::UnsupportedVersion { version, supported } => {
+                write!(f,
+                       "format_version {} is newer than the supported version {}, the player \
+                        needs to be updated",
+                       version,
+                       supported)
+            }
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Json(e)
+    }
+}
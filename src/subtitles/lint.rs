@@ -0,0 +1,145 @@
+use super::{Subtitles, Sentence, SentenceOptions, SentenceParameters, Syllable,
+            AsSentenceOptions, Error};
+use super::pos::RowPosition;
+
+/// first and last frame where the sentence is displayed, and its position
+fn display_window(sentence: &Sentence,
+                  default_sentence_options: Option<&SentenceOptions>)
+                  -> Option<(u32, u32, RowPosition)> {
+    match (sentence.syllables.first(), sentence.syllables.last()) {
+        (Some(first_syllable), Some(&Syllable { end: Some(end), .. })) => {
+            let sentence_options: Option<SentenceOptions> = sentence.sentence_options
+                .or_sentence_options(default_sentence_options);
+            let sentence_parameters =
+                SentenceParameters::from((sentence_options.unwrap_or(SentenceOptions::default()),
+                                          -10000i32));
+            Some((first_syllable.begin
+                      .saturating_sub(sentence_parameters.transition_time_before as u32),
+                  end.saturating_add(sentence_parameters.transition_time_after as u32),
+                  sentence_parameters.row_position.unwrap_or(sentence.position)))
+        }
+        _ => None,
+    }
+}
+
+impl Subtitles {
+    /// Every problem found in the subtitles, where `check` stops at the first
+    /// fatal one.
+    ///
+    /// `duration` is the length of the video in ms, 0 if it is unknown
+    pub fn lint(&self, duration: u32) -> Vec<Error> {
+        let mut errors = vec![];
+        for (s_number, sentence) in self.sentences.iter().enumerate() {
+            match sentence.syllables.last() {
+                None => errors.push(Error::EmptySentence { sentence: s_number }),
+                Some(&Syllable { end: None, .. }) => {
+                    errors.push(Error::MissingEndTime { sentence: s_number })
+                }
+                Some(_) => {}
+            };
+            for (n, syllable) in sentence.syllables.iter().enumerate() {
+                let next_syllable = sentence.syllables.get(n + 1);
+                if let (Some(end), Some(next_syllable)) = (syllable.end, next_syllable) {
+                    if next_syllable.begin < end {
+                        errors.push(Error::OverlappingSyllables {
+                            sentence: s_number,
+                            syllable: n + 1,
+                        });
+                    }
+                }
+                let end = syllable.end.or(next_syllable.map(|s| s.begin));
+                if end == Some(syllable.begin) {
+                    errors.push(Error::ZeroLengthSyllable {
+                        sentence: s_number,
+                        syllable: n,
+                    });
+                }
+                if duration > 0 && end.unwrap_or(syllable.begin) > duration {
+                    errors.push(Error::SyllableAfterEnd {
+                        sentence: s_number,
+                        syllable: n,
+                        duration: duration,
+                    });
+                }
+            }
+        }
+        // rows can't be computed if a sentence has no end
+        let missing_end_time = errors.iter().any(|e| match *e {
+            Error::MissingEndTime { .. } => true,
+            _ => false,
+        });
+        if !missing_end_time {
+            let mut subtitles = self.clone();
+            subtitles.post_init(duration);
+            let default_sentence_options = subtitles.subtitles_options.as_sentence_options();
+            let windows: Vec<_> = subtitles.sentences
+                .iter()
+                .map(|sentence| display_window(sentence, default_sentence_options))
+                .collect();
+            for (i, window) in windows.iter().enumerate() {
+                for (j, other_window) in windows.iter().enumerate().skip(i + 1) {
+                    if let (&Some((begin, end, RowPosition::Row(row))),
+                            &Some((other_begin, other_end, RowPosition::Row(other_row)))) =
+                           (window, other_window) {
+                        if row == other_row && begin <= other_end && other_begin <= end {
+                            errors.push(Error::RowConflict {
+                                sentence: i,
+                                other_sentence: j,
+                                row: row,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[test]
+fn test_lint() {
+    fn syllable(begin: u32, end: Option<u32>) -> Syllable {
+        Syllable {
+            text: String::from("a"),
+            begin: begin,
+            end: end,
+            syllable_options: None,
+            pitch: None,
+        }
+    }
+    fn sentence(syllables: Vec<Syllable>, row: Option<u8>) -> Sentence {
+        Sentence {
+            syllables: syllables,
+            position: RowPosition::default(),
+            sentence_options: row.map(|row| {
+                SentenceOptions {
+                    row_position: Some(RowPosition::Row(row)),
+                    ..SentenceOptions::default()
+                }
+            }),
+        }
+    }
+    let mut subtitles = Subtitles::default();
+    subtitles.sentences =
+        vec![sentence(vec![syllable(1000, Some(1500)), syllable(1400, Some(2000))], Some(1)),
+             sentence(vec![syllable(2500, None), syllable(2500, Some(9000))], Some(1))];
+    let errors = subtitles.lint(8000);
+    let found = |f: &Fn(&Error) -> bool| errors.iter().any(|e| f(e));
+    assert_eq!(errors.len(), 4);
+    assert!(found(&|e| match *e {
+        Error::OverlappingSyllables { sentence: 0, syllable: 1 } => true,
+        _ => false,
+    }));
+    assert!(found(&|e| match *e {
+        Error::ZeroLengthSyllable { sentence: 1, syllable: 0 } => true,
+        _ => false,
+    }));
+    assert!(found(&|e| match *e {
+        Error::SyllableAfterEnd { sentence: 1, syllable: 1, .. } => true,
+        _ => false,
+    }));
+    assert!(found(&|e| match *e {
+        Error::RowConflict { sentence: 0, other_sentence: 1, row: 1 } => true,
+        _ => false,
+    }));
+}
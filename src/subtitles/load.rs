@@ -1,5 +1,5 @@
-use super::Subtitles;
+use super::{Subtitles, Error};
 
 pub trait Load {
-    fn into_subtitles(&self) -> Result<Subtitles, String>;
+    fn into_subtitles(&self) -> Result<Subtitles, Error>;
 }
@@ -0,0 +1,46 @@
+use super::{Subtitles, Sentence};
+
+/// time in ms to mm:ss.xx, minutes are not wrapped into hours
+fn lrc_time(ms: u32) -> String {
+    let cs = ms / 10;
+    format!("{:02}:{:02}.{:02}", cs / 6000, (cs / 100) % 60, cs % 100)
+}
+
+impl Subtitles {
+    /// Exports the subtitles as Enhanced LRC : one line per sentence, with a
+    /// `<mm:ss.xx>` timestamp before every syllable and one after the last.
+    pub fn to_enhanced_lrc(&self) -> String {
+        let mut sentences: Vec<&Sentence> = self.sentences
+            .iter()
+            .filter(|s| !s.syllables.is_empty())
+            .collect();
+        sentences.sort_by_key(|s| s.syllables[0].begin);
+
+        let mut lrc = String::new();
+        if let Some(ref song_name) = self.song_info.song_name {
+            lrc.push_str(&format!("[ti:{}]\n", song_name));
+        }
+        if let Some(ref artist) = self.song_info.artist {
+            lrc.push_str(&format!("[ar:{}]\n", artist));
+        }
+        if let Some(ref media_title) = self.song_info.media_title {
+            lrc.push_str(&format!("[al:{}]\n", media_title));
+        }
+        for sentence in sentences {
+            lrc.push_str(&format!("[{}]", lrc_time(sentence.syllables[0].begin)));
+            for syllable in sentence.syllables.iter() {
+                lrc.push_str(&format!("<{}>{}", lrc_time(syllable.begin), syllable.text));
+            }
+            let last = sentence.syllables.last().unwrap();
+            lrc.push_str(&format!("<{}>\n", lrc_time(last.end.unwrap_or(last.begin))));
+        }
+        lrc
+    }
+}
+
+#[test]
+fn test_lrc_time() {
+    assert_eq!(lrc_time(0), "00:00.00");
+    assert_eq!(lrc_time(83_456), "01:23.45");
+    assert_eq!(lrc_time(6_000_000), "100:00.00");
+}
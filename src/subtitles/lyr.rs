@@ -0,0 +1,79 @@
+use super::{Subtitles, SyllableOptions, SyllableParameters, AsSentenceOptions,
+            AsSyllableOptions, Color};
+
+/// colors as written in the .lyr files : BBGGRR
+fn bgr(color: &Color) -> String {
+    format!("{:02X}{:02X}{:02X}", color.blue, color.green, color.red)
+}
+
+/// alive / transition / dead colors, in the order of the %color directive
+fn lyr_colors(syllable_options: Option<SyllableOptions>) -> (Color, Color, Color) {
+    let parameters =
+        SyllableParameters::from(syllable_options.unwrap_or(SyllableOptions::default()));
+    (parameters.alive_color, parameters.transition_color, parameters.dead_color)
+}
+
+fn color_directive(colors: &(Color, Color, Color)) -> String {
+    format!("%color {} {} {}\n", bgr(&colors.0), bgr(&colors.1), bgr(&colors.2))
+}
+
+fn ms_to_frame(ms: u32, fps: f64) -> u32 {
+    (ms as f64 * fps / 1000.0).round() as u32
+}
+
+impl Subtitles {
+    /// Exports the subtitles to the legacy toyunda format, returns the content
+    /// of the .lyr file and the one of the .frm file.
+    ///
+    /// Only the colors survive : a `%color` line is written every time they
+    /// change from one sentence to the next. Transitions within a sentence and
+    /// per-syllable options can't be expressed in .lyr files.
+    pub fn to_lyr_frm(&self, fps: f64) -> (String, String) {
+        let global_syllable_options: Option<SyllableOptions> = self.subtitles_options
+            .as_sentence_options()
+            .and_then(|o| o.syllable_options);
+        let mut lyr = String::new();
+        let mut frm = String::new();
+        let mut current_colors = lyr_colors(global_syllable_options);
+        if let Some(ref options) = global_syllable_options {
+            if (options.alive_color.is_some() || options.transition_color.is_some() ||
+                options.dead_color.is_some()) {
+                lyr.push_str(&color_directive(&current_colors));
+            }
+        }
+        for (s_number, sentence) in self.sentences.iter().enumerate() {
+            if sentence.syllables.is_empty() {
+                continue;
+            }
+            let colors = lyr_colors(sentence.as_sentence_options()
+                .and_then(|o| o.syllable_options)
+                .or_syllable_options(global_syllable_options.as_ref()));
+            if colors != current_colors {
+                lyr.push_str(&color_directive(&colors));
+                current_colors = colors;
+            }
+            // the leading '&' keeps sentences starting with '%' or '#' from
+            // being read as directives
+            for syllable in sentence.syllables.iter() {
+                if syllable.text.contains('&') {
+                    warn!("Syllable '{}' of sentence {} contains a '&', the .lyr file will be \
+                           out of sync with the .frm file",
+                          syllable.text,
+                          s_number);
+                }
+                lyr.push('&');
+                lyr.push_str(&syllable.text);
+            }
+            lyr.push('\n');
+            for (i, syllable) in sentence.syllables.iter().enumerate() {
+                let end = syllable.end
+                    .or(sentence.syllables.get(i + 1).map(|s| s.begin))
+                    .unwrap_or(syllable.begin);
+                frm.push_str(&format!("{} {}\n",
+                                      ms_to_frame(syllable.begin, fps),
+                                      ms_to_frame(end, fps)));
+            }
+        }
+        (lyr, frm)
+    }
+}
@@ -0,0 +1,209 @@
+//! Upgrades the json documents written by older versions of the player
+//! before they are deserialized into `Subtitles`.
+//!
+//! Documents without a `format_version` are version 0. Every migration
+//! upgrades a document from one version to the next.
+
+use super::{Subtitles, Error};
+use serde_json::{self, Value, Map};
+use std::io::Read;
+
+/// Version written in every new json file
+pub const CURRENT_FORMAT_VERSION: u32 = 1;
+
+/// a migration returns a description of every change it made
+type Migration = fn(&mut Value) -> Vec<String>;
+
+/// MIGRATIONS[n] upgrades a document from version n to version n + 1
+const MIGRATIONS: [Migration; 1] = [migrate_unversioned];
+
+/// calls `f` on every object of the document, the deepest ones first
+fn for_each_object<F: FnMut(&mut Map<String, Value>)>(value: &mut Value, f: &mut F) {
+    match *value {
+        Value::Object(ref mut object) => {
+            for (_, child) in object.iter_mut() {
+                for_each_object(child, f);
+            }
+            f(object);
+        }
+        Value::Array(ref mut array) => {
+            for child in array.iter_mut() {
+                for_each_object(child, f);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `{"red": 255, "green": 0, "blue": 0}` to `"#FF0000"`
+fn struct_color_to_hex(value: &Value) -> Option<String> {
+    let object = match *value {
+        Value::Object(ref object) if object.len() == 3 => object,
+        _ => return None,
+    };
+    let component = |name: &str| {
+        object.get(name)
+            .and_then(|c| c.as_u64())
+            .and_then(|c| if c <= 0xFF { Some(c) } else { None })
+    };
+    match (component("red"), component("green"), component("blue")) {
+        (Some(red), Some(green), Some(blue)) => {
+            Some(format!("#{:02X}{:02X}{:02X}", red, green, blue))
+        }
+        _ => None,
+    }
+}
+
+/// renames `row_position` to `position` in sentence options and in the
+/// options of their transitions, returns how many were renamed
+fn rename_row_position(sentence_options: Option<&mut Value>) -> usize {
+    let object = match sentence_options {
+        Some(&mut Value::Object(ref mut object)) => object,
+        _ => return 0,
+    };
+    let mut renamed = 0;
+    if !object.contains_key("position") {
+        if let Some(row_position) = object.remove("row_position") {
+            object.insert(String::from("position"), row_position);
+            renamed += 1;
+        }
+    }
+    if let Some(&mut Value::Array(ref mut transitions)) = object.get_mut("transitions") {
+        for transition in transitions.iter_mut() {
+            renamed += rename_row_position(transition.get_mut("new_options"));
+        }
+    }
+    renamed
+}
+
+/// unversioned documents may have :
+///
+/// * `row_position` instead of `position` in their sentence options
+/// * colors written as `{red, green, blue}` structs
+/// * no credits fields in `subtitles_options`
+fn migrate_unversioned(document: &mut Value) -> Vec<String> {
+    let mut renamed = 0;
+    if let Some(subtitles_options) = document.get_mut("subtitles_options") {
+        renamed += rename_row_position(subtitles_options.get_mut("sentence_options"));
+    }
+    if let Some(&mut Value::Array(ref mut sentences)) = document.get_mut("sentences") {
+        for sentence in sentences.iter_mut() {
+            renamed += rename_row_position(sentence.get_mut("sentence_options"));
+        }
+    }
+    let mut colors = 0;
+    for_each_object(document,
+                    &mut |object| {
+        for key in &["alive_color", "transition_color", "dead_color", "color"] {
+            let hex = object.get(*key).and_then(struct_color_to_hex);
+            if let Some(hex) = hex {
+                object.insert(String::from(*key), Value::String(hex));
+                colors += 1;
+            }
+        }
+    });
+    let mut changes = vec![];
+    if renamed > 0 {
+        changes.push(format!("renamed {} `row_position` field(s) to `position`", renamed));
+    }
+    if colors > 0 {
+        changes.push(format!("converted {} color(s) to #RRGGBB", colors));
+    }
+    if let Value::Object(ref mut root) = *document {
+        let subtitles_options = root.entry(String::from("subtitles_options"))
+            .or_insert(Value::Object(Map::new()));
+        if let Value::Object(ref mut subtitles_options) = *subtitles_options {
+            let mut added = vec![];
+            for key in &["start_credits_time", "end_credits_time", "credits_time"] {
+                if !subtitles_options.contains_key(*key) {
+                    subtitles_options.insert(String::from(*key), Value::from(0));
+                    added.push(*key);
+                }
+            }
+            if !added.is_empty() {
+                changes.push(format!("added the missing credits field(s) {}", added.join(", ")));
+            }
+        }
+    }
+    changes
+}
+
+/// format_version of a json document, 0 if there is none
+pub fn format_version(document: &Value) -> u32 {
+    document.get("format_version").and_then(|v| v.as_u64()).unwrap_or(0) as u32
+}
+
+/// Upgrades the document to `CURRENT_FORMAT_VERSION`, returns what was changed.
+///
+/// Fails for documents written by a newer version of the player.
+pub fn migrate(document: &mut Value) -> Result<Vec<String>, Error> {
+    if !document.is_object() {
+        return Err(Error::parse(None, "The json document is not an object"));
+    }
+    let version = format_version(document);
+    if version > CURRENT_FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion {
+            version: version,
+            supported: CURRENT_FORMAT_VERSION,
+        });
+    }
+    let mut changes = vec![];
+    for migration in MIGRATIONS[version as usize..].iter() {
+        changes.extend(migration(document));
+    }
+    if let Value::Object(ref mut root) = *document {
+        root.insert(String::from("format_version"),
+                    Value::from(CURRENT_FORMAT_VERSION));
+    }
+    Ok(changes)
+}
+
+impl Subtitles {
+    /// Reads a json document and migrates it to the current format if needed;
+    /// this is how every .json file should be loaded.
+    pub fn from_json<R: Read>(reader: R) -> Result<Subtitles, Error> {
+        let mut document: Value = try!(serde_json::from_reader(reader));
+        let version = format_version(&document);
+        let changes = try!(migrate(&mut document));
+        if version < CURRENT_FORMAT_VERSION {
+            info!("Migrated subtitles from format_version {} to {}",
+                  version,
+                  CURRENT_FORMAT_VERSION);
+            for change in changes {
+                info!("  {}", change);
+            }
+        }
+        serde_json::from_value(document).map_err(Error::Json)
+    }
+}
+
+#[test]
+fn test_migrate_unversioned() {
+    let mut document: Value = serde_json::from_str(r##"{
+        "sentences": [{
+            "syllables": [{"text": "a", "begin": 0, "end": 10,
+                           "syllable_options": {"alive_color": {"red": 255, "green": 0, "blue": 16}}}],
+            "sentence_options": {"row_position": 1,
+                                 "transitions": [{"offset": 0,
+                                                  "new_options": {"row_position": 2}}]}
+        }],
+        "editor": {"row_position": 3}
+    }"##)
+        .unwrap();
+    let changes = migrate(&mut document).unwrap();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(format_version(&document), CURRENT_FORMAT_VERSION);
+    let sentence = &document["sentences"][0];
+    assert_eq!(sentence["syllables"][0]["syllable_options"]["alive_color"],
+               Value::from("#FF0010"));
+    assert_eq!(sentence["sentence_options"]["position"], Value::from(1));
+    assert_eq!(sentence["sentence_options"]["transitions"][0]["new_options"]["position"],
+               Value::from(2));
+    // only the sentence options are renamed
+    assert_eq!(document["editor"]["row_position"], Value::from(3));
+    assert_eq!(document["subtitles_options"]["credits_time"], Value::from(0));
+    // already migrated documents are left untouched
+    assert!(migrate(&mut document).unwrap().is_empty());
+    let subtitles = Subtitles::from_json(document.to_string().as_bytes()).unwrap();
+    assert_eq!(subtitles.format_version, CURRENT_FORMAT_VERSION);
+}
@@ -3,10 +3,19 @@ mod sentence;
 mod syllable;
 mod sub;
 mod load;
+mod error;
+mod ass;
+mod ultrastar;
+mod lyr;
+mod lrc;
+mod webvtt;
+mod lint;
 pub mod pos;
 pub mod song_info;
+pub mod migration;
 pub use self::sub::*;
 pub use self::misc::*;
 pub use self::syllable::*;
 pub use self::sentence::*;
 pub use self::load::*;
+pub use self::error::Error;
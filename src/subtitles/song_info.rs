@@ -31,15 +31,7 @@ impl<'de> Deserialize<'de> for Language {
             fn visit_str<E>(self, value: &str) -> Result<Language, E>
                 where E: ::serde::de::Error
             {
-                Ok(match value {
-                    "JAP" | "Jp" | "JP" | "jp" | "jap" => Language::Jp,
-                    "FRA" | "Fr" | "FR" | "fr" | "fra" => Language::Fr,
-                    "GER" | "Ger" | "ger" => Language::Ger,
-                    "RUS" | "Rus" | "rus" => Language::Rus,
-                    "EN" | "ENG" | "En" | "en" => Language::En,
-                    "INSTRUMENTAL" => Language::Instrumental,
-                    s => Language::Other(String::from(s)),
-                })
+                Ok(Language::from(value))
             }
         }
 
@@ -78,14 +70,7 @@ impl<'de> Deserialize<'de> for MusicType {
             fn visit_str<E>(self, value: &str) -> Result<MusicType, E>
                 where E: ::serde::de::Error
             {
-                Ok(match value {
-                    "AMV" | "Amv" | "amv" => MusicType::AMV,
-                    "OP" | "OPENING" | "Op" | "Opening" => MusicType::Opening,
-                    "ED" | "ENDING" | "Ed" | "Ending" => MusicType::Ending,
-                    "INS" | "INSERT" | "Insert" => MusicType::Insert,
-                    "OST" | "Ost" => MusicType::OST,
-                    s => MusicType::Other(String::from(s)),
-                })
+                Ok(MusicType::from(value))
             }
         }
 
@@ -93,7 +78,7 @@ impl<'de> Deserialize<'de> for MusicType {
     }
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum Language {
     Fr,
     En,
@@ -105,7 +90,22 @@ pub enum Language {
     Other(String),
 }
 
-#[derive(Debug,Clone)]
+/// accepts the different spellings found in the yaml files
+impl<'a> From<&'a str> for Language {
+    fn from(value: &'a str) -> Language {
+        match value {
+            "JAP" | "Jp" | "JP" | "jp" | "jap" => Language::Jp,
+            "FRA" | "Fr" | "FR" | "fr" | "fra" => Language::Fr,
+            "GER" | "Ger" | "ger" => Language::Ger,
+            "RUS" | "Rus" | "rus" => Language::Rus,
+            "EN" | "ENG" | "En" | "en" => Language::En,
+            "INSTRUMENTAL" => Language::Instrumental,
+            s => Language::Other(String::from(s)),
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
 pub enum MusicType {
     AMV,
     Opening,
@@ -116,6 +116,19 @@ pub enum MusicType {
     Other(String),
 }
 
+impl<'a> From<&'a str> for MusicType {
+    fn from(value: &'a str) -> MusicType {
+        match value {
+            "AMV" | "Amv" | "amv" => MusicType::AMV,
+            "OP" | "OPENING" | "Op" | "Opening" => MusicType::Opening,
+            "ED" | "ENDING" | "Ed" | "Ending" => MusicType::Ending,
+            "INS" | "INSERT" | "Insert" => MusicType::Insert,
+            "OST" | "Ost" => MusicType::OST,
+            s => MusicType::Other(String::from(s)),
+        }
+    }
+}
+
 impl MusicType {
     pub fn short(&self) -> &str {
         match *self {
@@ -169,15 +182,7 @@ impl<'de> Deserialize<'de> for MediaType {
             fn visit_str<E>(self, value: &str) -> Result<MediaType, E>
                 where E: ::serde::de::Error
             {
-                Ok(match value {
-                    "ANIME" | "Anime" => MediaType::Anime,
-                    "VideoGame" | "VG" | "videogame" | "VIDEOGAME" | "Video Game" => {
-                        MediaType::VideoGame
-                    }
-                    "Movie" | "MOVIE" => MediaType::Movie,
-                    "Original" | "ORIGINAL" => MediaType::Original,
-                    s => MediaType::Other(String::from(s)),
-                })
+                Ok(MediaType::from(value))
             }
         }
 
@@ -187,7 +192,7 @@ impl<'de> Deserialize<'de> for MediaType {
 
 /// Case of an AMV, please tell the source of the visual material
 #[allow(dead_code)]
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum MediaType {
     Anime,
     Movie,
@@ -198,6 +203,18 @@ pub enum MediaType {
     Other(String),
 }
 
+impl<'a> From<&'a str> for MediaType {
+    fn from(value: &'a str) -> MediaType {
+        match value {
+            "ANIME" | "Anime" => MediaType::Anime,
+            "VideoGame" | "VG" | "videogame" | "VIDEOGAME" | "Video Game" => MediaType::VideoGame,
+            "Movie" | "MOVIE" => MediaType::Movie,
+            "Original" | "ORIGINAL" => MediaType::Original,
+            s => MediaType::Other(String::from(s)),
+        }
+    }
+}
+
 #[derive(Debug,Clone,Default,Serialize,Deserialize)]
 pub struct SongInfo {
     pub artist: Option<String>,
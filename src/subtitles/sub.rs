@@ -1,14 +1,18 @@
 use super::song_info::SongInfo;
+use super::migration::CURRENT_FORMAT_VERSION;
 use super::pos::{RowPosition, Point};
 use super::{Sentence, SentenceOptions, SentenceParameters, Syllable, SyllableOptions,
-            SyllableParameters, AsSentenceOptions, AsSyllableOptions};
+            SyllableParameters, AsSentenceOptions, AsSyllableOptions, Error};
 use ::overlay::*;
 use ::overlay::pos::*;
 use std::ops::Deref;
 use ::utils::*;
 
-#[derive(Debug,Default,Serialize,Deserialize,Clone)]
+#[derive(Debug,Serialize,Deserialize,Clone)]
 pub struct Subtitles {
+    /// 0 for files written before the format was versioned, see `migration`
+    #[serde(default)]
+    pub format_version: u32,
     pub sentences: Vec<Sentence>,
     #[serde(default)]
     pub subtitles_options: SubtitlesOptions,
@@ -16,6 +20,17 @@ pub struct Subtitles {
     pub song_info: SongInfo,
 }
 
+impl Default for Subtitles {
+    fn default() -> Subtitles {
+        Subtitles {
+            format_version: CURRENT_FORMAT_VERSION,
+            sentences: Vec::new(),
+            subtitles_options: SubtitlesOptions::default(),
+            song_info: SongInfo::default(),
+        }
+    }
+}
+
 /// subtitles : already stored Subtitles
 /// sentence : Sentence to add to the subtitles
 fn set_best_sentence_row(sentences: (&[Sentence], &[Sentence]),
@@ -118,18 +133,30 @@ impl Subtitles {
         self.song_info.credit_sentences()
     }
 
-    pub fn check(&self) -> Result<(), String> {
+    /// only a missing end time is fatal, empty sentences and overlapping
+    /// syllables are logged
+    pub fn check(&self) -> Result<(), Error> {
         for (s_number, sentence) in self.sentences.iter().enumerate() {
             match (sentence.syllables.first(), sentence.syllables.last()) {
                 (Some(_), Some(&Syllable { end: Some(_), .. })) => {}
                 (Some(_), Some(&Syllable { end: None, .. })) => {
-                    return Err(format!("Error at sentence {}, no 'end' time for the last syllable",
-                                       s_number))
+                    return Err(Error::MissingEndTime { sentence: s_number })
                 }
                 _ => {
-                    warn!("Empty sentence {} when checking", s_number);
+                    warn!("{}", Error::EmptySentence { sentence: s_number });
                 }
             };
+            for (n, syllables) in sentence.syllables.windows(2).enumerate() {
+                if let Some(end) = syllables[0].end {
+                    if syllables[1].begin < end {
+                        warn!("{}",
+                              Error::OverlappingSyllables {
+                                  sentence: s_number,
+                                  syllable: n + 1,
+                              });
+                    }
+                }
+            }
         }
         Ok(())
     }
@@ -160,11 +187,20 @@ impl Subtitles {
         }
     }
 
-    // TODO create a  subtitles::Error type and replace String with this
-    pub fn to_overlay_frame(&self, current_time: u32) -> Result<OverlayFrame, String> {
+    pub fn to_overlay_frame(&self, current_time: u32) -> Result<OverlayFrame, Error> {
+        self.to_overlay_frame_with_defaults(current_time, None)
+    }
+
+    /// `player_defaults` are used for what neither the sentences nor the
+    /// subtitles set; they aren't saved with the subtitles
+    pub fn to_overlay_frame_with_defaults(&self,
+                                          current_time: u32,
+                                          player_defaults: Option<&SentenceOptions>)
+                                          -> Result<OverlayFrame, Error> {
         let mut text_units: Vec<TextUnit> = vec![];
-        let default_sentence_options: Option<&SentenceOptions> = self.subtitles_options
-            .as_sentence_options();
+        let default_sentence_options: Option<SentenceOptions> = self.subtitles_options
+            .or_sentence_options(player_defaults);
+        let default_sentence_options = default_sentence_options.as_ref();
         let sentence_iter = self.sentences.iter().enumerate().filter(|&(_, ref sentence)| {
             let sentence_options: Option<SentenceOptions> = sentence.sentence_options
                 .or_sentence_options(default_sentence_options);
@@ -10,6 +10,9 @@ pub struct Syllable {
     pub end: Option<u32>,
     #[serde(skip_serializing_if="Option::is_none")]
     pub syllable_options: Option<SyllableOptions>,
+    /// Pitch of the note, only known when imported from UltraStar
+    #[serde(default,skip_serializing_if="Option::is_none")]
+    pub pitch: Option<i32>,
 }
 
 #[derive(Debug,Clone,Copy,Default,Serialize,Deserialize)]
@@ -0,0 +1,226 @@
+use super::{Subtitles, Sentence, Syllable, Error};
+use super::pos::RowPosition;
+use super::song_info::{SongInfo, Language};
+use std::cmp::min;
+
+/// BPM used when exporting : one beat is worth 10ms
+const EXPORT_BPM: f64 = 1500.0;
+
+/// UltraStar has no "unknown pitch", this one is used for the syllables
+/// which weren't imported from UltraStar
+const PLACEHOLDER_PITCH: i32 = 0;
+
+/// length of a beat in ms; BPM in UltraStar files are quarter notes
+fn beat_duration(bpm: f64) -> f64 {
+    60000.0 / (bpm * 4.0)
+}
+
+/// UltraStar files write "0,5" as often as "0.5"
+fn parse_decimal(value: &str) -> Option<f64> {
+    value.trim().replace(',', ".").parse::<f64>().ok()
+}
+
+/// splits "  12 rest" into ("12", " rest")
+fn next_token(s: &str) -> (&str, &str) {
+    let start = s.find(|c: char| !c.is_whitespace()).unwrap_or(s.len());
+    let s = &s[start..];
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    (&s[..end], &s[end..])
+}
+
+fn language_from_ultrastar(language: &str) -> Language {
+    match language.trim() {
+        "Japanese" => Language::Jp,
+        "English" => Language::En,
+        "French" => Language::Fr,
+        "German" => Language::Ger,
+        "Russian" => Language::Rus,
+        s => Language::Other(String::from(s)),
+    }
+}
+
+fn language_to_ultrastar(language: &Language) -> &str {
+    match *language {
+        Language::Jp => "Japanese",
+        Language::En => "English",
+        Language::Fr => "French",
+        Language::Ger => "German",
+        Language::Rus => "Russian",
+        Language::Instrumental => "Instrumental",
+        Language::Other(ref s) => s.as_str(),
+    }
+}
+
+fn push_sentence(sentences: &mut Vec<Sentence>, syllables: &mut Vec<Syllable>) {
+    if !syllables.is_empty() {
+        sentences.push(Sentence {
+            syllables: syllables.drain(..).collect(),
+            position: RowPosition::default(),
+            sentence_options: None,
+        });
+    }
+}
+
+impl Subtitles {
+    /// Imports an UltraStar song file. Notes become syllables, line breaks
+    /// become sentences and the pitch of every note is kept in the syllables.
+    pub fn from_ultrastar(content: &str) -> Result<Subtitles, Error> {
+        let mut song_info = SongInfo::default();
+        let mut bpm: Option<f64> = None;
+        let mut gap: f64 = 0.0;
+        let mut relative = false;
+        let mut relative_offset: i64 = 0;
+        let mut sentences: Vec<Sentence> = Vec::new();
+        let mut syllables: Vec<Syllable> = Vec::new();
+        for (line_number, line) in content.lines().enumerate() {
+            if line.starts_with('#') {
+                let (key, value) = match line.find(':') {
+                    Some(i) => (line[1..i].trim().to_uppercase(), line[i + 1..].trim()),
+                    None => continue,
+                };
+                match key.as_str() {
+                    "TITLE" => song_info.song_name = Some(String::from(value)),
+                    "ARTIST" => song_info.artist = Some(String::from(value)),
+                    "YEAR" => song_info.year = value.parse::<u32>().ok(),
+                    "LANGUAGE" => song_info.language = Some(language_from_ultrastar(value)),
+                    "BPM" => bpm = parse_decimal(value),
+                    "GAP" => gap = parse_decimal(value).unwrap_or(0.0),
+                    "RELATIVE" => relative = value.to_lowercase() == "yes",
+                    _ => {}
+                }
+            } else if line.starts_with(':') || line.starts_with('*') || line.starts_with('F') ||
+                      line.starts_with('R') || line.starts_with('G') {
+                let beat = match bpm {
+                    Some(bpm) if bpm > 0.0 => beat_duration(bpm),
+                    _ => {
+                        return Err(Error::parse(Some(line_number + 1), "Note without a valid #BPM"))
+                    }
+                };
+                let (start, rest) = next_token(&line[1..]);
+                let (length, rest) = next_token(rest);
+                let (pitch, rest) = next_token(rest);
+                let (start, length, pitch) =
+                    match (start.parse::<i64>(), length.parse::<u32>(), pitch.parse::<i32>()) {
+                        (Ok(start), Ok(length), Ok(pitch)) => (start, length, pitch),
+                        _ => return Err(Error::parse(Some(line_number + 1), "Invalid note")),
+                    };
+                // exactly one space separates the pitch from the text,
+                // the text itself may begin with a space
+                let text = if rest.is_empty() { rest } else { &rest[1..] };
+                let start = if relative { start + relative_offset } else { start };
+                let begin = (gap + start as f64 * beat).max(0.0);
+                syllables.push(Syllable {
+                    text: String::from(text),
+                    begin: begin.round() as u32,
+                    end: Some((begin + length as f64 * beat).round() as u32),
+                    syllable_options: None,
+                    pitch: Some(pitch),
+                });
+            } else if line.starts_with('-') {
+                push_sentence(&mut sentences, &mut syllables);
+                if relative {
+                    let (first, rest) = next_token(&line[1..]);
+                    let (second, _) = next_token(rest);
+                    let offset = if second.is_empty() { first } else { second };
+                    relative_offset += offset.parse::<i64>().unwrap_or(0);
+                }
+            } else if line.starts_with('E') {
+                break;
+            }
+        }
+        push_sentence(&mut sentences, &mut syllables);
+        if bpm.is_none() {
+            return Err(Error::parse(None, "No #BPM header, this isn't an UltraStar file"));
+        }
+        Ok(Subtitles {
+            sentences: sentences,
+            song_info: song_info,
+            ..Subtitles::default()
+        })
+    }
+
+    /// Exports the subtitles as an UltraStar song file. Syllables without
+    /// a pitch are given a placeholder one.
+    ///
+    /// `media_file` is the name of the audio/video file, as UltraStar expects it
+    /// to be next to the .txt file
+    pub fn to_ultrastar(&self, media_file: Option<&str>) -> String {
+        let beat = beat_duration(EXPORT_BPM);
+        let mut sentences: Vec<&Sentence> = self.sentences
+            .iter()
+            .filter(|s| !s.syllables.is_empty())
+            .collect();
+        sentences.sort_by_key(|s| s.syllables[0].begin);
+        let gap: u32 = sentences.first().map(|s| s.syllables[0].begin).unwrap_or(0);
+        let to_beat = |ms: u32| (ms.saturating_sub(gap) as f64 / beat).round() as u32;
+
+        let mut ultrastar = String::new();
+        let title = self.song_info
+            .song_name
+            .as_ref()
+            .or(self.song_info.media_title.as_ref());
+        ultrastar.push_str(&format!("#TITLE:{}\n", title.map(|s| s.as_str()).unwrap_or("Unknown")));
+        ultrastar.push_str(&format!("#ARTIST:{}\n",
+                                    self.song_info
+                                        .artist
+                                        .as_ref()
+                                        .map(|s| s.as_str())
+                                        .unwrap_or("Unknown")));
+        if let Some(ref language) = self.song_info.language {
+            ultrastar.push_str(&format!("#LANGUAGE:{}\n", language_to_ultrastar(language)));
+        }
+        if let Some(year) = self.song_info.year {
+            ultrastar.push_str(&format!("#YEAR:{}\n", year));
+        }
+        if let Some(media_file) = media_file {
+            ultrastar.push_str(&format!("#MP3:{}\n#VIDEO:{}\n", media_file, media_file));
+        }
+        ultrastar.push_str(&format!("#BPM:{}\n#GAP:{}\n", EXPORT_BPM, gap));
+        for (n, sentence) in sentences.iter().enumerate() {
+            if n > 0 {
+                let previous_end = sentences[n - 1]
+                    .syllables
+                    .last()
+                    .and_then(|s| s.end)
+                    .unwrap_or(sentences[n - 1].syllables[0].begin);
+                // a line break can't be after the next line began
+                ultrastar.push_str(&format!("- {}\n",
+                                            min(to_beat(previous_end),
+                                                to_beat(sentence.syllables[0].begin))));
+            }
+            for (i, syllable) in sentence.syllables.iter().enumerate() {
+                if syllable.text.is_empty() {
+                    continue;
+                }
+                let end = syllable.end
+                    .or(sentence.syllables.get(i + 1).map(|s| s.begin.saturating_sub(1)))
+                    .unwrap_or(syllable.begin);
+                let start_beat = to_beat(syllable.begin);
+                let length = to_beat(end).saturating_sub(start_beat);
+                ultrastar.push_str(&format!(": {} {} {} {}\n",
+                                            start_beat,
+                                            if length == 0 { 1 } else { length },
+                                            syllable.pitch.unwrap_or(PLACEHOLDER_PITCH),
+                                            syllable.text));
+            }
+        }
+        ultrastar.push_str("E\n");
+        ultrastar
+    }
+}
+
+#[test]
+fn test_ultrastar_round_trip() {
+    let ultrastar = "#TITLE:Song\n#ARTIST:Someone\n#LANGUAGE:Japanese\n#BPM:300\n#GAP:1000\n\
+                     : 0 4 5 Ka\n* 4 2 7 ra \n- 8\n: 10 4 -2 o\nE\n";
+    let subtitles = Subtitles::from_ultrastar(ultrastar).unwrap();
+    assert_eq!(subtitles.sentences.len(), 2);
+    // a beat is 50ms at 300 BPM
+    let syllable = &subtitles.sentences[0].syllables[1];
+    assert_eq!((syllable.begin, syllable.end, syllable.pitch), (1200, Some(1300), Some(7)));
+    assert_eq!(syllable.text, "ra ");
+    let exported = Subtitles::from_ultrastar(&subtitles.to_ultrastar(None)).unwrap();
+    let syllable = &exported.sentences[1].syllables[0];
+    assert_eq!((syllable.begin, syllable.end, syllable.pitch), (1500, Some(1700), Some(-2)));
+    assert_eq!(exported.song_info.song_name, Some(String::from("Song")));
+}
@@ -0,0 +1,50 @@
+use super::{Subtitles, Sentence};
+
+/// time in ms to hh:mm:ss.ttt
+fn vtt_time(ms: u32) -> String {
+    format!("{:02}:{:02}:{:02}.{:03}",
+            ms / 3600000,
+            (ms / 60000) % 60,
+            (ms / 1000) % 60,
+            ms % 1000)
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+impl Subtitles {
+    /// Exports the subtitles as WebVTT : one cue per sentence, lasting from
+    /// the first to the last syllable, with an inline `<hh:mm:ss.ttt>` timestamp
+    /// before every syllable but the first.
+    pub fn to_webvtt(&self) -> String {
+        let mut sentences: Vec<&Sentence> = self.sentences
+            .iter()
+            .filter(|s| !s.syllables.is_empty())
+            .collect();
+        sentences.sort_by_key(|s| s.syllables[0].begin);
+
+        let mut vtt = String::from("WEBVTT\n");
+        for sentence in sentences {
+            let begin = sentence.syllables[0].begin;
+            let last = sentence.syllables.last().unwrap();
+            let end = last.end.unwrap_or(last.begin);
+            vtt.push_str(&format!("\n{} --> {}\n", vtt_time(begin), vtt_time(end)));
+            for (i, syllable) in sentence.syllables.iter().enumerate() {
+                // timestamps have to be strictly within the cue
+                if (i > 0 && syllable.begin > begin && syllable.begin < end) {
+                    vtt.push_str(&format!("<{}>", vtt_time(syllable.begin)));
+                }
+                vtt.push_str(&escape_vtt(&syllable.text));
+            }
+            vtt.push('\n');
+        }
+        vtt
+    }
+}
+
+#[test]
+fn test_vtt_time() {
+    assert_eq!(vtt_time(0), "00:00:00.000");
+    assert_eq!(vtt_time(3_723_004), "01:02:03.004");
+}
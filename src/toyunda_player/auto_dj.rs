@@ -0,0 +1,147 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use ::subtitles::song_info::{Language, MusicType};
+use std::collections::BTreeMap;
+use rand::Rng;
+
+/// Picks songs from the library when the queue runs dry.
+///
+/// The weight of a song is the weight of its language times the weight of
+/// its music type, 1.0 when they aren't listed; a song with a weight of 0 is
+/// never picked.
+#[derive(Debug)]
+pub struct AutoDj {
+    /// in seconds
+    recent: i64,
+    languages: Vec<(Language, f64)>,
+    music_types: Vec<(MusicType, f64)>,
+    only_valid_subtitles: bool,
+}
+
+/// the subtitles can be loaded and have no more than warnings; songs with
+/// only .lyr subtitles can't be checked and aren't valid here
+pub fn has_valid_subtitles(video_meta: &VideoMeta) -> bool {
+    match video_meta.load_subtitles() {
+        Ok(subtitles) => {
+            subtitles.lint(video_meta.video_duration).iter().all(|error| error.is_warning())
+        }
+        Err(_) => false,
+    }
+}
+
+impl AutoDj {
+    /// the keys of `languages` and `music_types` are spelled as in the yaml
+    /// files
+    pub fn new(recent_minutes: u32,
+               languages: &BTreeMap<String, f64>,
+               music_types: &BTreeMap<String, f64>,
+               only_valid_subtitles: bool)
+               -> AutoDj {
+        AutoDj {
+            recent: recent_minutes as i64 * 60,
+            languages: languages.iter().map(|(k, v)| (Language::from(k.as_str()), *v)).collect(),
+            music_types: music_types.iter()
+                .map(|(k, v)| (MusicType::from(k.as_str()), *v))
+                .collect(),
+            only_valid_subtitles: only_valid_subtitles,
+        }
+    }
+
+    pub fn weight(&self, video_meta: &VideoMeta) -> f64 {
+        let song_info = &video_meta.song_info;
+        let language_weight = self.languages
+            .iter()
+            .find(|l| Some(&l.0) == song_info.language.as_ref())
+            .map_or(1.0, |l| l.1);
+        let music_type_weight = self.music_types
+            .iter()
+            .find(|m| Some(&m.0) == song_info.music_type.as_ref())
+            .map_or(1.0, |m| m.1);
+        language_weight * music_type_weight
+    }
+
+    /// A random song of `songs` which wasn't played recently, None if there is
+    /// none left. `last_played` is a UNIX TIMESTAMP, like `now`.
+    pub fn pick<'a, I, F, R>(&self,
+                             songs: I,
+                             last_played: F,
+                             now: i64,
+                             rng: &mut R)
+                             -> Option<&'a VideoMeta>
+        where I: IntoIterator<Item = &'a VideoMeta>,
+              F: Fn(&VideoMeta) -> Option<i64>,
+              R: Rng
+    {
+        let mut candidates: Vec<(&VideoMeta, f64)> = songs.into_iter()
+            .filter(|video_meta| last_played(video_meta).map_or(true, |t| now - t >= self.recent))
+            .map(|video_meta| (video_meta, self.weight(video_meta)))
+            .filter(|&(_, weight)| weight > 0.0)
+            .collect();
+        // checking the subtitles of the whole library would take too long, only
+        // the picked song is checked and another one is picked if it's invalid
+        while !candidates.is_empty() {
+            let total: f64 = candidates.iter().map(|c| c.1).sum();
+            let mut target = rng.gen_range(0.0, total);
+            let mut picked = candidates.len() - 1;
+            for (i, &(_, weight)) in candidates.iter().enumerate() {
+                if target < weight {
+                    picked = i;
+                    break;
+                }
+                target -= weight;
+            }
+            let (video_meta, _) = candidates.swap_remove(picked);
+            if !self.only_valid_subtitles || has_valid_subtitles(video_meta) {
+                return Some(video_meta);
+            }
+            debug!("Auto-DJ skipped '{}', its subtitles aren't valid", video_meta);
+        }
+        None
+    }
+}
+
+#[test]
+fn test_auto_dj_pick() {
+    use rand::{SeedableRng, XorShiftRng};
+    let songs: Vec<VideoMeta> = vec![("jp_op", Language::Jp, MusicType::Opening),
+                                     ("fr_ed", Language::Fr, MusicType::Ending),
+                                     ("recent", Language::Jp, MusicType::Opening),
+                                     ("amv", Language::Jp, MusicType::AMV)]
+        .into_iter()
+        .map(|(name, language, music_type)| {
+            let mut video_meta = VideoMeta::test_song(name);
+            video_meta.song_info.language = Some(language);
+            video_meta.song_info.music_type = Some(music_type);
+            video_meta
+        })
+        .collect();
+    let mut languages = BTreeMap::new();
+    languages.insert(String::from("JAP"), 3.0);
+    let mut music_types = BTreeMap::new();
+    music_types.insert(String::from("OP"), 3.0);
+    music_types.insert(String::from("AMV"), 0.0);
+    let auto_dj = AutoDj::new(60, &languages, &music_types, false);
+    assert_eq!(auto_dj.weight(&songs[0]), 9.0);
+    assert_eq!(auto_dj.weight(&songs[1]), 1.0);
+    assert_eq!(auto_dj.weight(&songs[3]), 0.0);
+    let last_played = |video_meta: &VideoMeta| if video_meta.video_path.ends_with("recent.mp4") {
+        Some(10000 - 1800)
+    } else {
+        None
+    };
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let mut counts = [0; 4];
+    for _ in 0..1000 {
+        let picked = auto_dj.pick(&songs, &last_played, 10000, &mut rng).unwrap();
+        counts[songs.iter().position(|s| s.video_path == picked.video_path).unwrap()] += 1;
+    }
+    assert_eq!((counts[2], counts[3]), (0, 0));
+    assert!(counts[0] > 5 * counts[1] && counts[1] > 0);
+    // an hour later, the recent song can be picked again
+    assert!((0..100).any(|_| {
+        auto_dj.pick(&songs, &last_played, 10000 + 1800, &mut rng).unwrap().video_path ==
+        songs[2].video_path
+    }));
+    // none of them has subtitles
+    let strict = AutoDj::new(60, &languages, &music_types, true);
+    assert!(strict.pick(&songs, &last_played, 10000, &mut rng).is_none());
+}
@@ -1,8 +1,8 @@
 use ::toyunda_player::*;
 use ::toyunda_player::error::{Result, Error};
-use std::cmp::{min, max};
 use ::toyunda_player::playing_state::*;
 use chrono::{DateTime, Local};
+use std::sync::mpsc::Sender;
 
 #[derive(Debug)]
 pub enum Command {
@@ -15,9 +15,15 @@ pub enum Command {
     ToggleDisplaySubtitles,
     ToggleQuitOnFinish,
     PauseBeforeNext,
-    AddToQueue(VideoMeta),
-    AddToQueueWithPos(VideoMeta, usize),
-    DeleteFromQueue(usize),
+    AddToQueue(QueueEntry),
+    AddToQueueWithPos(QueueEntry, usize),
+    DeleteFromQueue(QueueId),
+    /// moves the entry to the given position
+    MoveInQueue(QueueId, usize),
+    SwapInQueue(QueueId, QueueId),
+    MoveToTop(QueueId),
+    /// the ids of every entry of the queue, in the new order
+    ReorderQueue(Vec<QueueId>),
     ReloadSubtitles,
     /// Stops the queue, but doesnt empty it
     /// Use PlayNext to play the queue again
@@ -26,87 +32,164 @@ pub enum Command {
     ClearQueue,
     Quit,
     Announcement(String, DateTime<Local>),
+    /// reads the yaml directories of the manager again
+    RescanLibrary,
 }
 
-impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
+/// The commands of a request of the manager, which waits for the result to
+/// answer : they are all done, or none of them if one is about an entry the
+/// queue doesn't have (anymore). PlayNext has to come alone.
+pub struct ManagerRequest {
+    pub commands: Vec<Command>,
+    pub reply: Sender<Result<()>>,
+}
+
+impl Command {
+    /// the ones after which the queue journal is written again
+    pub fn changes_queue(&self) -> bool {
+        match *self {
+            Command::AddToQueue(_) |
+            Command::AddToQueueWithPos(_, _) |
+            Command::DeleteFromQueue(_) |
+            Command::MoveInQueue(_, _) |
+            Command::SwapInQueue(_, _) |
+            Command::MoveToTop(_) |
+            Command::ReorderQueue(_) |
+            Command::Stop |
+            Command::PlayNext |
+            Command::ClearQueue => true,
+            _ => false,
+        }
+    }
+
+    /// PlayNext skips the songs which fail to load, and the auto-DJ may add
+    /// some : the queue after it can't be told beforehand
+    pub fn queue_unpredictable(&self) -> bool {
+        match *self {
+            Command::PlayNext => true,
+            _ => false,
+        }
+    }
+
+    /// checks the command against a copy of the queue, and changes it the
+    /// way the command would change the queue
+    pub fn check_queue(&self,
+                       queue: &mut Playlist,
+                       fair_queue: bool)
+                       -> ::std::result::Result<(), QueueError> {
+        match *self {
+            Command::AddToQueue(ref queue_entry) => {
+                let pos = add_position(queue, queue_entry, fair_queue);
+                queue.insert(pos, queue_entry.clone());
+                Ok(())
+            }
+            Command::AddToQueueWithPos(ref queue_entry, pos) => {
+                insert_in_queue(queue, pos, queue_entry.clone())
+            }
+            Command::DeleteFromQueue(id) => remove_from_queue(queue, id).map(|_| ()),
+            Command::MoveToTop(id) => move_in_queue(queue, id, 0),
+            Command::MoveInQueue(id, to) => move_in_queue(queue, id, to),
+            Command::SwapInQueue(id, other_id) => swap_in_queue(queue, id, other_id),
+            Command::ReorderQueue(ref order) => reorder_queue(queue, order),
+            Command::ClearQueue => {
+                queue.clear();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> ToyundaPlayer<'a> {
     pub fn execute_command(&mut self, command: Command) -> Result<ToyundaAction> {
+        let changes_queue = command.changes_queue();
+        let result = self.run_command(command);
+        if changes_queue {
+            self.write_queue_journal();
+        }
+        result
+    }
+
+    /// runs the commands of the manager and sends back how it went; the
+    /// action is Terminate if one of them asked to quit
+    pub fn execute_manager_request(&mut self, request: ManagerRequest) -> ToyundaAction {
+        let ManagerRequest { commands, reply } = request;
+        let checked = if commands.len() > 1 && commands.iter().any(|c| c.queue_unpredictable()) {
+            Err(QueueError::NotAlone)
+        } else {
+            let mut queue = self.state.read().unwrap().playlist.clone();
+            commands.iter()
+                .map(|command| command.check_queue(&mut queue, self.fair_queue))
+                .collect::<::std::result::Result<Vec<()>, QueueError>>()
+        };
+        if let Err(e) = checked {
+            // the web client acted on a queue which changed in the meantime
+            let _ = reply.send(Err(Error::QueueError(e)));
+            return ToyundaAction::Nothing;
+        }
+        let mut action = ToyundaAction::Nothing;
+        let mut result = Ok(());
+        for command in commands {
+            match self.execute_command(command) {
+                Ok(ToyundaAction::Terminate) => action = ToyundaAction::Terminate,
+                Ok(ToyundaAction::Nothing) => {}
+                Err(e) => {
+                    error!("{}", e);
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        // the request may have given up waiting, nothing to do then
+        let _ = reply.send(result);
+        action
+    }
+
+    fn run_command(&mut self, command: Command) -> Result<ToyundaAction> {
         match command {
             Command::SetSpeed(speed) => {
-                self.mpv
-                    .set_property_async("speed", speed, 1)
-                    .map_err(|e| Error::MpvError(e))
-                    .map(|_| ToyundaAction::Nothing)
+                self.media.set_speed(speed).map(|_| ToyundaAction::Nothing)
             }
             Command::TogglePause => {
-                match self.mpv.get_property("pause") {
-                        Ok(true) => self.mpv.set_property_async("pause", false, 1),
-                        Ok(false) => self.mpv.set_property_async("pause", true, 1),
-                        e @ Err(_) => e.map(|_| ()),
-                    }
-                    .map_err(|e| Error::MpvError(e))
-                    .map(|_| ToyundaAction::Nothing)
+                let paused = try!(self.media.is_paused());
+                self.media.set_pause(!paused).map(|_| ToyundaAction::Nothing)
             }
             Command::AddVolume(delta) => {
-                let max_volume = self.mpv.get_property::<i64>("volume-max");
-                let current_volume = self.mpv.get_property::<i64>("volume");
-                match (max_volume, current_volume) {
-                        (e @ Err(_), _) => e.map(|_| ToyundaAction::Nothing),
-                        (_, e @ Err(_)) => e.map(|_| ToyundaAction::Nothing),
-                        (Ok(max_volume), Ok(current_volume)) => {
-                            let new_volume = min(max(current_volume + delta, 0), max_volume);
-                            self.mpv
-                                .set_property("volume", new_volume)
-                                .map(|_| ToyundaAction::Nothing)
-                        }
-                    }
-                    .map_err(|e| Error::MpvError(e))
+                let max_volume = try!(self.media.max_volume());
+                let current_volume = try!(self.media.volume());
+                let new_volume = (current_volume + delta as f64).max(0.0).min(max_volume);
+                self.media.set_volume(new_volume).map(|_| ToyundaAction::Nothing)
             }
             Command::ToggleFullscreen => {
-                use sdl2::video::FullscreenType;
-                let new_fullscreen_type =
-                    match self.displayer.sdl_canvas().window().fullscreen_state() {
-                        FullscreenType::True | FullscreenType::Desktop => {
-                            // TODO warn if 'True'
-                            FullscreenType::Off
-                        }
-                        FullscreenType::Off => FullscreenType::Desktop,
-                    };
                 self.displayer
-                    .sdl_canvas_mut()
-                    .window_mut()
-                    .set_fullscreen(new_fullscreen_type)
+                    .toggle_fullscreen()
                     .map_err(|e| Error::Text(e))
                     .map(|_| ToyundaAction::Nothing)
             }
             Command::Framestep(step) => {
-                let (frame_step_type, _step) = if step >= 0 {
-                    ("frame-step", step)
-                } else {
-                    ("frame-back-step", -step)
-                };
-                self.mpv
-                    .command(&[frame_step_type])
-                    .map_err(|e| Error::MpvError(e))
-                    .map(|_| ToyundaAction::Nothing)
-            }
-            Command::Seek(delta) => {
-                self.mpv
-                    .command(&["seek", delta.to_string().as_str()])
-                    .map_err(|e| Error::MpvError(e))
-                    .map(|_| ToyundaAction::Nothing)
+                self.media.framestep(step).map(|_| ToyundaAction::Nothing)
             }
+            Command::Seek(delta) => self.media.seek(delta).map(|_| ToyundaAction::Nothing),
             Command::ToggleDisplaySubtitles => {
                 let current_value = self.state.read().unwrap().display_subtitles;
                 self.state.write().unwrap().display_subtitles = !current_value;
+                self.notify_options();
                 Ok(ToyundaAction::Nothing)
             }
             Command::PlayNext => {
+                // the operator doesn't have to wait for the end of the intermission
+                self.intermission_until = None;
                 if self.state.read().unwrap().pause_before_next == true {
                     self.state.write().unwrap().pause_before_next = false;
+                    self.notify_options();
                     return self.execute_command(Command::Stop);
                 }
-                let video_meta = self.state.write().unwrap().playlist.pop_front();
-                match video_meta {
+                let mut queue_entry = self.state.write().unwrap().playlist.pop_front();
+                if queue_entry.is_none() && self.fill_auto_dj() {
+                    queue_entry = self.state.write().unwrap().playlist.pop_front();
+                }
+                match queue_entry {
                     None => {
                         try!(self.execute_command(Command::Stop));
                         match self.state.read().unwrap().quit_when_finished {
@@ -129,39 +212,48 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                             }
                         }
                     }
-                    Some(video_meta) => self.load_media_from_video_meta(video_meta),
+                    Some(queue_entry) => {
+                        self.notify(StateEvent::QueueDelete { id: queue_entry.id });
+                        let result = self.load_media_from_video_meta(queue_entry.video_meta);
+                        // the next song is known before this one ends
+                        self.fill_auto_dj();
+                        result
+                    }
                 }
             }
             Command::Stop => {
+                self.intermission_until = None;
                 self.state.write().unwrap().playing_state = PlayingState::Idle;
-                if let Err(mpv_err) = self.mpv.command(&["stop"]) {
-                    Err(mpv_err.into())
-                } else {
-                    Ok(ToyundaAction::Nothing)
-                }
+                self.notify(StateEvent::Idle);
+                self.media.stop().map(|_| ToyundaAction::Nothing)
             }
             Command::ClearQueue => {
                 self.state.write().unwrap().playlist.clear();
+                self.notify(StateEvent::QueueClear);
                 Ok(ToyundaAction::Nothing)
             }
-            Command::AddToQueue(video_meta) => {
-                self.state.write().unwrap().playlist.push_back(video_meta);
+            Command::AddToQueue(queue_entry) => {
+                let pos = {
+                    let playlist = &mut self.state.write().unwrap().playlist;
+                    let pos = add_position(playlist, &queue_entry, self.fair_queue);
+                    playlist.insert(pos, queue_entry.clone());
+                    pos
+                };
+                self.notify(StateEvent::QueueAdd {
+                    pos: pos,
+                    entry: queue_entry,
+                });
                 Ok(ToyundaAction::Nothing)
             }
-            Command::AddToQueueWithPos(video_meta, pos) => {
-                if let Ok(mut state) = self.state.write() {
-                    if state.playlist.len() >= pos {
-                        state.playlist.insert(pos, video_meta);
-                        Ok(ToyundaAction::Nothing)
-                    } else {
-                        Err(format!("Trying to insert element at {} while playlist is of size {}",
-                                    pos,
-                                    state.playlist.len())
-                            .into())
-                    }
-                } else {
-                    Err(format!("Unable to get state object, is the lock poisoned ?").into())
-                }
+            Command::AddToQueueWithPos(queue_entry, pos) => {
+                try!(insert_in_queue(&mut self.state.write().unwrap().playlist,
+                                     pos,
+                                     queue_entry.clone()));
+                self.notify(StateEvent::QueueAdd {
+                    pos: pos,
+                    entry: queue_entry,
+                });
+                Ok(ToyundaAction::Nothing)
             }
             Command::ReloadSubtitles => {
                 try!(self.import_cur_file_subtitles());
@@ -170,26 +262,49 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
             Command::Quit => Ok(ToyundaAction::Terminate),
             Command::PauseBeforeNext => {
                 self.state.write().unwrap().pause_before_next = true;
+                self.notify_options();
                 Ok(ToyundaAction::Nothing)
             }
             Command::ToggleQuitOnFinish => {
                 let b: bool = self.state.read().unwrap().quit_when_finished.unwrap_or(false);
                 self.state.write().unwrap().quit_when_finished = Some(!b);
+                self.notify_options();
+                Ok(ToyundaAction::Nothing)
+            }
+            Command::RescanLibrary => {
+                match self.manager {
+                    Some(ref manager) => manager.rescan(),
+                    None => warn!("There is no library to rescan without the manager"),
+                };
                 Ok(ToyundaAction::Nothing)
             }
             Command::Announcement(text, datetime) => {
+                self.notify(StateEvent::Announcement { text: text.clone() });
                 self.announcements.push((text, datetime));
                 Ok(ToyundaAction::Nothing)
             }
-            Command::DeleteFromQueue(p) => {
-                if let Ok(mut state) = self.state.write() {
-                    if p < state.playlist.len() {
-                        state.playlist.remove(p);
-                    } else {
-                        warn!("Trying to remove {}th element from playlist, but is out of bounds",
-                              p);
-                    }
-                }
+            Command::DeleteFromQueue(id) => {
+                try!(remove_from_queue(&mut self.state.write().unwrap().playlist, id));
+                self.notify(StateEvent::QueueDelete { id: id });
+                Ok(ToyundaAction::Nothing)
+            }
+            Command::MoveInQueue(id, to) => {
+                try!(move_in_queue(&mut self.state.write().unwrap().playlist, id, to));
+                self.notify(StateEvent::QueueMove { id: id, to: to });
+                Ok(ToyundaAction::Nothing)
+            }
+            Command::MoveToTop(id) => self.run_command(Command::MoveInQueue(id, 0)),
+            Command::SwapInQueue(id, other_id) => {
+                try!(swap_in_queue(&mut self.state.write().unwrap().playlist, id, other_id));
+                self.notify(StateEvent::QueueSwap {
+                    id: id,
+                    other_id: other_id,
+                });
+                Ok(ToyundaAction::Nothing)
+            }
+            Command::ReorderQueue(order) => {
+                try!(reorder_queue(&mut self.state.write().unwrap().playlist, &order));
+                self.notify(StateEvent::QueueReorder { order: order });
                 Ok(ToyundaAction::Nothing)
             }
         }
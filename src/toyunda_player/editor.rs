@@ -1,4 +1,4 @@
-use ::subtitles::{Subtitles, Sentence, Syllable};
+use ::subtitles::{Subtitles, Sentence, Syllable, Error as SubtitlesError};
 use ::overlay::{Outline, OverlayFrame, TextUnit, TextSubUnit, AlphaColor, Color};
 use ::overlay::pos::*;
 use ::utils::{RGBA, RGB};
@@ -163,7 +163,10 @@ impl EditorState {
         };
     }
 
-    pub fn to_overlay_frame(&self, now: u32, subs: &Subtitles) -> Result<OverlayFrame, String> {
+    pub fn to_overlay_frame(&self,
+                            now: u32,
+                            subs: &Subtitles)
+                            -> Result<OverlayFrame, SubtitlesError> {
         let mut text_units: Vec<TextUnit> = vec![];
         let cur_syl = self.current_syllable;
         let cur_sen = self.current_sentence;
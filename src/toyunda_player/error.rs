@@ -1,6 +1,8 @@
 use std::error::Error as StdError;
 use mpv::Error as MpvError;
 use serde_json::error::Error as SerdeJsonError;
+use ::subtitles::Error as SubtitlesError;
+use ::toyunda_player::playlist::QueueError;
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -10,6 +12,8 @@ pub enum Error {
     FileNotFound(String),
     MpvError(MpvError),
     JsonError(SerdeJsonError),
+    SubtitlesError(SubtitlesError),
+    QueueError(QueueError),
     UnknownError,
 }
 
@@ -20,6 +24,8 @@ impl StdError for Error {
             Error::MpvError(ref mpv_error) => mpv_error.description(),
             Error::FileNotFound(_) => "given file was not found",
             Error::JsonError(ref e) => e.description(),
+            Error::SubtitlesError(ref e) => e.description(),
+            Error::QueueError(_) => "invalid queue command",
             Error::UnknownError => "unknown error",
         }
     }
@@ -29,6 +35,8 @@ impl StdError for Error {
             Error::Text(_) => None,
             Error::MpvError(ref mpv_error) => Some(mpv_error),
             Error::JsonError(ref serde_json_error) => Some(serde_json_error),
+            Error::SubtitlesError(ref subtitles_error) => Some(subtitles_error),
+            Error::QueueError(_) => None,
             Error::FileNotFound(_) => None,
             Error::UnknownError => None,
         }
@@ -48,6 +56,8 @@ impl ::std::fmt::Display for Error {
             Error::MpvError(ref mpv_error) => write!(f, "Error from Mpv : {}", mpv_error),
             Error::FileNotFound(ref e) => write!(f, "File {} not found", e),
             Error::JsonError(ref e) => write!(f, "JSON Error : {}", e),
+            Error::SubtitlesError(ref e) => write!(f, "{}", e),
+            Error::QueueError(ref e) => write!(f, "{}", e),
             Error::UnknownError => write!(f, "Unknown Error"), 
         }
     }
@@ -64,3 +74,15 @@ impl From<SerdeJsonError> for Error {
         Error::JsonError(s)
     }
 }
+
+impl From<SubtitlesError> for Error {
+    fn from(e: SubtitlesError) -> Error {
+        Error::SubtitlesError(e)
+    }
+}
+
+impl From<QueueError> for Error {
+    fn from(e: QueueError) -> Error {
+        Error::QueueError(e)
+    }
+}
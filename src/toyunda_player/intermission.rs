@@ -0,0 +1,144 @@
+use ::overlay::{OverlayFrame, TextUnit, TextSubUnit, Outline, Color, AlphaColor};
+use ::overlay::pos::*;
+use ::toyunda_player::playlist::Playlist;
+use ::utils::RGB;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+
+/// how many songs are listed after the next one
+const UPCOMING_ENTRIES: usize = 4;
+
+/// What is shown while the player is idle, or between two songs
+pub struct Intermission<'a> {
+    pub playlist: &'a Playlist,
+    /// seconds before the next song starts, None if it waits for the operator
+    pub countdown: Option<u64>,
+    /// where the guests can add songs
+    pub manager_url: Option<&'a str>,
+}
+
+fn line(text: String, color: AlphaColor, height: f32, pos_y: f32) -> TextUnit {
+    TextUnit {
+        text: vec![TextSubUnit {
+                       text: text,
+                       attach_logo: false,
+                       color: color,
+                       outline: Outline::Light(Color::new(0, 0, 0)),
+                       shadow: None,
+                   }],
+        size: Size::FitPercent(Some(0.9), Some(height)),
+        pos: (PosX::Centered, PosY::FromTopPercent(pos_y)),
+        anchor: (0.5, 0.0),
+    }
+}
+
+impl<'a> Intermission<'a> {
+    pub fn to_overlay_frame(&self) -> OverlayFrame {
+        let mut overlay_frame = OverlayFrame::new();
+        if let Some(url) = self.manager_url {
+            overlay_frame.text_units.push(line(format!("Add your songs at {}", url),
+                                               AlphaColor::new(200, 200, 200),
+                                               0.045,
+                                               0.03));
+        }
+        let next = match self.playlist.front() {
+            Some(next) => next,
+            None => {
+                overlay_frame.text_units.push(line(String::from("No song in the queue"),
+                                                   AlphaColor::new(255, 255, 255),
+                                                   0.08,
+                                                   0.4));
+                return overlay_frame;
+            }
+        };
+        let title = match self.countdown {
+            Some(secs) => format!("Next up in {}s", secs),
+            None => String::from("Next up"),
+        };
+        overlay_frame.text_units.push(line(title, AlphaColor::new(255, 255, 255), 0.07, 0.12));
+        // same colors as the credits shown when the song begins
+        let (first_credits, second_credits) = next.video_meta
+            .song_info
+            .credit_sentences()
+            .unwrap_or((next.video_meta.to_string(), None));
+        overlay_frame.text_units
+            .push(line(first_credits, AlphaColor::new(128, 255, 128), 0.08, 0.22));
+        if let Some(second_credits) = second_credits {
+            overlay_frame.text_units
+                .push(line(second_credits, AlphaColor::new(128, 255, 128), 0.06, 0.31));
+        }
+        let requester = match next.added_by {
+            _ if next.auto_dj => Some(String::from("Picked by the auto-DJ")),
+            Some(ref added_by) => Some(format!("Asked by {}", added_by)),
+            None => None,
+        };
+        if let Some(requester) = requester {
+            overlay_frame.text_units
+                .push(line(requester, AlphaColor::new(255, 140, 0), 0.05, 0.39));
+        }
+        for (n, entry) in self.playlist.iter().skip(1).take(UPCOMING_ENTRIES).enumerate() {
+            let text = match entry.added_by {
+                Some(ref added_by) if !entry.auto_dj => {
+                    format!("{} ({})", entry.video_meta, added_by)
+                }
+                _ => entry.video_meta.to_string(),
+            };
+            overlay_frame.text_units.push(line(text,
+                                               AlphaColor::new(200, 200, 200),
+                                               0.045,
+                                               0.52 + 0.06 * n as f32));
+        }
+        overlay_frame
+    }
+}
+
+/// The address the guests can reach when the manager listens on every
+/// interface : the one used to reach the outside. Nothing is sent, connecting
+/// an udp socket only picks the interface.
+pub fn default_manager_url(listen_address: IpAddr, port: u16) -> String {
+    let address = if listen_address.is_unspecified() {
+        UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| socket.connect("192.0.2.1:80").and_then(|_| socket.local_addr()))
+            .map(|local_addr| local_addr.ip())
+            .unwrap_or(listen_address)
+    } else {
+        listen_address
+    };
+    format!("http://{}", SocketAddr::new(address, port))
+}
+
+#[test]
+fn test_intermission_overlay() {
+    use ::toyunda_player::playlist::QueueEntry;
+    use ::toyunda_player::video_meta::VideoMeta;
+    fn texts(intermission: &Intermission) -> Vec<String> {
+        intermission.to_overlay_frame().text_units.iter().map(|t| t.to_string()).collect()
+    }
+    let mut playlist = Playlist::new();
+    let idle = Intermission {
+        playlist: &playlist,
+        countdown: None,
+        manager_url: Some("http://192.168.1.2:8080"),
+    };
+    assert_eq!(texts(&idle),
+               vec!["Add your songs at http://192.168.1.2:8080", "No song in the queue"]);
+    let mut next = VideoMeta::new("next.mp4").unwrap();
+    next.song_info.media_title = Some(String::from("Title"));
+    next.song_info.artist = Some(String::from("Artist"));
+    next.song_info.song_name = Some(String::from("Song"));
+    playlist.push_back(QueueEntry::new(next, Some(String::from("guest"))));
+    playlist.push_back(QueueEntry::new(VideoMeta::new("after.mp4").unwrap(),
+                                       Some(String::from("other"))));
+    playlist.push_back(QueueEntry::auto_dj(VideoMeta::new("auto.mp4").unwrap()));
+    let countdown = Intermission {
+        playlist: &playlist,
+        countdown: Some(7),
+        manager_url: None,
+    };
+    assert_eq!(texts(&countdown),
+               vec!["Next up in 7s",
+                    "Title",
+                    "Artist - Song",
+                    "Asked by guest",
+                    "after (other)",
+                    "auto"]);
+}
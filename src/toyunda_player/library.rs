@@ -0,0 +1,172 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use ::toyunda_player::search_index::SearchIndex;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The songs of the yaml directories, and what's needed to search them.
+///
+/// The id of a song is its index in `songs` : it never changes as long as
+/// the player runs, so the clients can keep using the ids they know after
+/// a rescan. The songs removed since are None.
+pub struct Library {
+    pub songs: Vec<Option<VideoMeta>>,
+    pub index: SearchIndex,
+}
+
+/// what a rescan changed
+#[derive(Debug,Clone,Default,PartialEq,Serialize)]
+pub struct LibraryChanges {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl Library {
+    pub fn new(songs: Vec<VideoMeta>) -> Library {
+        Self::from_songs(songs.into_iter().map(Some).collect())
+    }
+
+    fn from_songs(songs: Vec<Option<VideoMeta>>) -> Library {
+        Library {
+            index: SearchIndex::new(songs.iter().map(|s| s.as_ref())),
+            songs: songs,
+        }
+    }
+
+    /// None if the song was removed or never existed
+    pub fn get(&self, id: usize) -> Option<&VideoMeta> {
+        self.songs.get(id).and_then(|song| song.as_ref())
+    }
+
+    /// a new library with the songs of a rescan : songs with the same yaml
+    /// file keep their id, new ones are added at the end
+    pub fn updated(&self, songs: Vec<VideoMeta>) -> (Library, LibraryChanges) {
+        let ids: HashMap<&PathBuf, usize> = self.songs
+            .iter()
+            .enumerate()
+            .filter_map(|(id, song)| {
+                song.as_ref().and_then(|song| song.yaml_path.as_ref()).map(|path| (path, id))
+            })
+            .collect();
+        let mut new_songs: Vec<Option<VideoMeta>> = self.songs.iter().map(|_| None).collect();
+        let mut changes = LibraryChanges::default();
+        for mut song in songs {
+            match song.yaml_path.as_ref().and_then(|path| ids.get(path)).cloned() {
+                Some(id) => {
+                    // the history isn't read again
+                    song.last_played = self.songs[id].as_ref().and_then(|old| old.last_played);
+                    new_songs[id] = Some(song);
+                }
+                None => {
+                    new_songs.push(Some(song));
+                    changes.added += 1;
+                }
+            }
+        }
+        changes.removed = self.songs
+            .iter()
+            .zip(new_songs.iter())
+            .filter(|&(old, new)| old.is_some() && new.is_none())
+            .count();
+        (Self::from_songs(new_songs), changes)
+    }
+}
+
+/// The library used by the manager, replaced all at once by a rescan
+#[derive(Clone)]
+pub struct SharedLibrary {
+    current: Arc<RwLock<Arc<Library>>>,
+    rescanning: Arc<AtomicBool>,
+}
+
+/// clears the flag of a rescan when it ends, even if the rescan panicked
+struct RescanGuard<'a>(&'a AtomicBool);
+
+impl<'a> Drop for RescanGuard<'a> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl SharedLibrary {
+    pub fn new(library: Library) -> SharedLibrary {
+        SharedLibrary {
+            current: Arc::new(RwLock::new(Arc::new(library))),
+            rescanning: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// a request keeps the library it started with, even if a rescan ends
+    /// in the meantime
+    pub fn current(&self) -> Arc<Library> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// The library stays usable while `load` reads the yaml files again.
+    /// None if another rescan is already running.
+    pub fn rescan<F: FnOnce() -> Vec<VideoMeta>>(&self, load: F) -> Option<LibraryChanges> {
+        if self.rescanning.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        let _guard = RescanGuard(&self.rescanning);
+        let songs = load();
+        let (library, changes) = self.current().updated(songs);
+        *self.current.write().unwrap() = Arc::new(library);
+        Some(changes)
+    }
+}
+
+#[test]
+fn test_library_update() {
+    // a rescan finds the songs by their yaml file
+    let songs = |yamls: &[&str]| -> Vec<VideoMeta> {
+        yamls.iter()
+            .map(|yaml| {
+                VideoMeta { yaml_path: Some(PathBuf::from(yaml)), ..VideoMeta::test_song(yaml) }
+            })
+            .collect()
+    };
+    let mut library = Library::new(songs(&["a.yaml", "b.yaml", "c.yaml"]));
+    library.songs[2].as_mut().unwrap().last_played = Some(42);
+    let (library, changes) = library.updated(songs(&["d.yaml", "c.yaml", "a.yaml"]));
+    assert_eq!(changes,
+               LibraryChanges {
+                   added: 1,
+                   removed: 1,
+               });
+    let paths: Vec<Option<PathBuf>> = library.songs
+        .iter()
+        .map(|s| s.as_ref().and_then(|s| s.yaml_path.clone()))
+        .collect();
+    assert_eq!(paths,
+               vec![Some(PathBuf::from("a.yaml")),
+                    None,
+                    Some(PathBuf::from("c.yaml")),
+                    Some(PathBuf::from("d.yaml"))]);
+    assert_eq!(library.get(2).unwrap().last_played, Some(42));
+    assert!(library.get(1).is_none());
+    let listed: Vec<usize> = ::toyunda_player::listing::ListingQuery::default()
+        .apply(&library)
+        .entries
+        .iter()
+        .map(|entry| entry.id)
+        .collect();
+    assert_eq!(listed, vec![0, 2, 3]);
+    // the whole listing keeps the ids as positions
+    let whole = ::serde_json::to_value(&library.songs).unwrap();
+    assert!(whole[1].is_null() && whole[3].is_object());
+    // the removed song can't be found anymore, the new one can
+    assert_eq!(library.index.search("b yaml"), Vec::<usize>::new());
+    assert_eq!(library.index.search("d yaml"), vec![3]);
+}
+
+#[test]
+fn test_rescan_after_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    let shared = SharedLibrary::new(Library::new(vec![]));
+    assert!(catch_unwind(AssertUnwindSafe(|| shared.rescan(|| panic!("unreadable directory"))))
+        .is_err());
+    let changes = shared.rescan(|| vec![VideoMeta::test_song("song")]);
+    assert_eq!(changes.map(|c| c.added), Some(1));
+}
@@ -0,0 +1,151 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use serde_json;
+
+/// changed every time the format of the cache or of VideoMeta changes, the
+/// cache is rebuilt from scratch then
+const LIBRARY_CACHE_VERSION: u32 = 1;
+
+#[derive(Debug,Serialize,Deserialize)]
+struct CachedYaml {
+    /// seconds and nanoseconds since UNIX_EPOCH
+    modified: (u64, u32),
+    size: u64,
+    video_meta: VideoMeta,
+}
+
+/// The yaml files already parsed, so only the ones changed since have to be
+/// parsed again when the player starts
+#[derive(Debug,Default,Serialize,Deserialize)]
+pub struct LibraryCache {
+    version: u32,
+    files: HashMap<PathBuf, CachedYaml>,
+    /// the files which still exist, the others are forgotten when saved
+    #[serde(skip_serializing,skip_deserializing)]
+    seen: HashSet<PathBuf>,
+}
+
+/// what tells a file changed : its modification time and its size
+fn file_state(path: &Path) -> Option<((u64, u32), u64)> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return None,
+    };
+    let modified = metadata.modified().ok().and_then(|m| m.duration_since(UNIX_EPOCH).ok());
+    let modified = match modified {
+        Some(modified) => modified,
+        None => return None,
+    };
+    Some(((modified.as_secs(), modified.subsec_nanos()), metadata.len()))
+}
+
+impl LibraryCache {
+    pub fn new() -> LibraryCache {
+        LibraryCache {
+            version: LIBRARY_CACHE_VERSION,
+            files: HashMap::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// an empty cache if there is none yet, or if it can't be used
+    pub fn open<P: AsRef<Path>>(path: P) -> LibraryCache {
+        let path = path.as_ref();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return LibraryCache::new(),
+        };
+        match serde_json::from_reader::<_, LibraryCache>(file) {
+            Ok(ref cache) if cache.version != LIBRARY_CACHE_VERSION => {
+                info!("The library cache '{}' is outdated, rebuilding it", path.display());
+                LibraryCache::new()
+            }
+            Ok(cache) => cache,
+            Err(e) => {
+                warn!("The library cache '{}' is corrupt, rebuilding it : {}",
+                      path.display(),
+                      e);
+                LibraryCache::new()
+            }
+        }
+    }
+
+    /// written somewhere else first, so a crash never leaves half a cache
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let seen = &self.seen;
+        self.files.retain(|yaml_path, _| seen.contains(yaml_path));
+        let tmp_path = path.with_extension("tmp");
+        let file = try!(File::create(&tmp_path).map_err(|e| format!("{}", e)));
+        try!(serde_json::to_writer(file, self).map_err(|e| format!("{}", e)));
+        fs::rename(&tmp_path, path).map_err(|e| format!("{}", e))
+    }
+
+    /// the song of the cache if the file didn't change since, the one
+    /// given by `parse` otherwise
+    pub fn get_or_parse<F>(&mut self, yaml_path: &Path, parse: F) -> Result<VideoMeta, String>
+        where F: FnOnce(&Path) -> Result<VideoMeta, String>
+    {
+        self.seen.insert(yaml_path.to_path_buf());
+        let state = file_state(yaml_path);
+        if let (Some((modified, size)), Some(cached)) = (state, self.files.get(yaml_path)) {
+            if cached.modified == modified && cached.size == size {
+                let mut video_meta = cached.video_meta.clone();
+                // not serialized
+                video_meta.yaml_path = Some(yaml_path.to_path_buf());
+                return Ok(video_meta);
+            }
+        }
+        let video_meta = try!(parse(yaml_path));
+        match state {
+            Some((modified, size)) => {
+                self.files.insert(yaml_path.to_path_buf(),
+                                  CachedYaml {
+                                      modified: modified,
+                                      size: size,
+                                      video_meta: video_meta.clone(),
+                                  });
+            }
+            None => {
+                self.files.remove(yaml_path);
+            }
+        };
+        Ok(video_meta)
+    }
+}
+
+#[test]
+fn test_library_cache() {
+    use std::io::Write;
+    let dir = ::utils::TempDir::new("library_cache_test");
+    let yaml_path = dir.path().join("song.yaml");
+    let cache_path = dir.path().join("cache.json");
+    let write_yaml = |content: &str| {
+        File::create(&yaml_path).unwrap().write_all(content.as_bytes()).unwrap()
+    };
+    let parse = |path: &Path| VideoMeta::from_yaml(path);
+    write_yaml("video_path: song.mp4\n");
+
+    let mut cache = LibraryCache::open(&cache_path);
+    let video_meta = cache.get_or_parse(&yaml_path, &parse).unwrap();
+    assert_eq!(video_meta.video_path, PathBuf::from("song.mp4"));
+    cache.save(&cache_path).unwrap();
+
+    // unchanged : not parsed again
+    let mut cache = LibraryCache::open(&cache_path);
+    let video_meta = cache.get_or_parse(&yaml_path, |_| Err(String::from("parsed"))).unwrap();
+    assert_eq!(video_meta.yaml_path, Some(yaml_path.clone()));
+
+    // the size changed
+    write_yaml("video_path: other_song.mp4\n");
+    let video_meta = cache.get_or_parse(&yaml_path, &parse).unwrap();
+    assert_eq!(video_meta.video_path, PathBuf::from("other_song.mp4"));
+
+    // a corrupt cache is ignored
+    File::create(&cache_path).unwrap().write_all(b"{\"version\": 1, \"fil").unwrap();
+    let mut cache = LibraryCache::open(&cache_path);
+    assert!(cache.get_or_parse(&yaml_path, |_| Err(String::from("parsed"))).is_err());
+}
@@ -0,0 +1,190 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use ::toyunda_player::library::Library;
+use ::subtitles::song_info::{Language, MusicType, MediaType};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum ListingSort {
+    /// best matches of the search first, in the order of the yaml
+    /// directories otherwise
+    Default,
+    /// the songs never played come first
+    LastPlayed,
+    /// the songs played most recently come first
+    LastPlayedDesc,
+}
+
+/// What `/api/listing` is asked for through its query string
+#[derive(Debug,Clone)]
+pub struct ListingQuery {
+    /// searched in the artist, the titles and the song name, with typos
+    /// and different romanizations allowed
+    pub text: String,
+    pub language: Option<Language>,
+    pub music_type: Option<MusicType>,
+    pub media_type: Option<MediaType>,
+    pub year_min: Option<u32>,
+    pub year_max: Option<u32>,
+    pub sort: ListingSort,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug,Serialize)]
+pub struct ListingEntry<'a> {
+    /// index in the whole listing, this is what `add_to_queue` expects
+    pub id: usize,
+    pub video_meta: &'a VideoMeta,
+}
+
+#[derive(Debug,Serialize)]
+pub struct ListingPage<'a> {
+    /// how many songs match, regardless of offset and limit
+    pub total: usize,
+    pub offset: usize,
+    pub entries: Vec<ListingEntry<'a>>,
+}
+
+fn parse_param<T: ::std::str::FromStr>(key: &str, value: &str) -> Result<T, String>
+    where T::Err: ::std::fmt::Display
+{
+    value.parse::<T>().map_err(|e| format!("Invalid value '{}' for '{}' : {}", value, key, e))
+}
+
+impl Default for ListingQuery {
+    fn default() -> ListingQuery {
+        ListingQuery {
+            text: String::new(),
+            language: None,
+            music_type: None,
+            media_type: None,
+            year_min: None,
+            year_max: None,
+            sort: ListingSort::Default,
+            offset: 0,
+            limit: None,
+        }
+    }
+}
+
+impl ListingQuery {
+    /// the pairs of the query string; unknown keys are refused so typos don't
+    /// go unnoticed
+    pub fn from_pairs<I, K, V>(pairs: I) -> Result<ListingQuery, String>
+        where I: IntoIterator<Item = (K, V)>,
+              K: AsRef<str>,
+              V: AsRef<str>
+    {
+        let mut query = ListingQuery::default();
+        for (key, value) in pairs {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            match key {
+                "q" => query.text = value.to_string(),
+                "language" => query.language = Some(Language::from(value)),
+                "music_type" => query.music_type = Some(MusicType::from(value)),
+                "media_type" => query.media_type = Some(MediaType::from(value)),
+                "year_min" => query.year_min = Some(try!(parse_param(key, value))),
+                "year_max" => query.year_max = Some(try!(parse_param(key, value))),
+                "sort" => {
+                    query.sort = match value {
+                        "last_played" => ListingSort::LastPlayed,
+                        "-last_played" => ListingSort::LastPlayedDesc,
+                        _ => return Err(format!("Unknown sort '{}'", value)),
+                    }
+                }
+                "offset" => query.offset = try!(parse_param(key, value)),
+                "limit" => query.limit = Some(try!(parse_param(key, value))),
+                // the password of the manager, already checked
+                "token" => {}
+                _ => return Err(format!("Unknown parameter '{}'", key)),
+            }
+        }
+        Ok(query)
+    }
+
+    /// everything but the text, which goes through the index
+    pub fn matches(&self, video_meta: &VideoMeta) -> bool {
+        let song_info = &video_meta.song_info;
+        fn same<T: PartialEq>(wanted: &Option<T>, value: &Option<T>) -> bool {
+            wanted.is_none() || wanted == value
+        }
+        let year_ok = match (song_info.year, self.year_min, self.year_max) {
+            (_, None, None) => true,
+            (None, _, _) => false,
+            (Some(year), min, max) => {
+                min.map_or(true, |min| year >= min) && max.map_or(true, |max| year <= max)
+            }
+        };
+        year_ok && same(&self.language, &song_info.language) &&
+        same(&self.music_type, &song_info.music_type) &&
+        same(&self.media_type, &song_info.media_type)
+    }
+
+    pub fn apply<'a>(&self, library: &'a Library) -> ListingPage<'a> {
+        let ids: Vec<usize> = if self.text.trim().is_empty() {
+            (0..library.songs.len()).collect()
+        } else {
+            library.index.search(&self.text)
+        };
+        let mut entries: Vec<ListingEntry<'a>> = ids.into_iter()
+            .filter_map(|id| {
+                library.get(id).map(|video_meta| {
+                    ListingEntry {
+                        id: id,
+                        video_meta: video_meta,
+                    }
+                })
+            })
+            .filter(|entry| self.matches(entry.video_meta))
+            .collect();
+        // sort_by_key is stable, so the listing order is kept for equal dates
+        match self.sort {
+            ListingSort::Default => {}
+            ListingSort::LastPlayed => entries.sort_by_key(|e| e.video_meta.last_played),
+            ListingSort::LastPlayedDesc => {
+                entries.sort_by_key(|e| ::std::cmp::Reverse(e.video_meta.last_played))
+            }
+        }
+        let total = entries.len();
+        let entries = entries.into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(total))
+            .collect();
+        ListingPage {
+            total: total,
+            offset: self.offset,
+            entries: entries,
+        }
+    }
+}
+
+#[test]
+fn test_listing_query() {
+    let songs = vec![("Shingeki no Kyojin", "JAP", 2013, Some(10)),
+                     ("Cowboy Bebop", "ENG", 1998, None),
+                     ("Kyojin no Hoshi", "JAP", 1968, Some(5))];
+    let library = Library::new(songs.into_iter()
+        .map(|(title, language, year, last_played)| {
+            let mut video_meta = VideoMeta::test_song(title);
+            video_meta.song_info.language = Some(Language::from(language));
+            video_meta.song_info.year = Some(year);
+            video_meta.last_played = last_played;
+            video_meta
+        })
+        .collect());
+    let ids = |pairs: &[(&str, &str)]| -> Vec<usize> {
+        let query = ListingQuery::from_pairs(pairs.iter().cloned()).unwrap();
+        query.apply(&library).entries.iter().map(|e| e.id).collect()
+    };
+    assert_eq!(ids(&[]), vec![0, 1, 2]);
+    assert_eq!(ids(&[("q", "kyojin")]), vec![0, 2]);
+    assert_eq!(ids(&[("q", "KYOUJIN shingeki")]), vec![0]);
+    assert_eq!(ids(&[("language", "jp")]), vec![0, 2]);
+    assert_eq!(ids(&[("year_min", "1990"), ("year_max", "2000")]), vec![1]);
+    assert_eq!(ids(&[("sort", "last_played")]), vec![1, 2, 0]);
+    assert_eq!(ids(&[("sort", "-last_played")]), vec![0, 2, 1]);
+    assert_eq!(ids(&[("offset", "1"), ("limit", "1")]), vec![1]);
+    let query = ListingQuery::from_pairs(vec![("limit", "1")]).unwrap();
+    assert_eq!(query.apply(&library).total, 3);
+    assert!(ListingQuery::from_pairs(vec![("year_min", "soon")]).is_err());
+    assert!(ListingQuery::from_pairs(vec![("langage", "jp")]).is_err());
+}
@@ -8,15 +8,29 @@ use staticfile::Static;
 use std::sync::{Weak, RwLock, Arc, Mutex};
 use super::state::State as ToyundaState;
 use super::command::*;
+use super::error::Error as PlayerError;
 use super::video_meta::*;
+use super::playlist::*;
 use super::toyunda_history::*;
+use super::playing_state::PlayingState;
+use super::state_events::*;
+use super::manager_auth::*;
+use super::listing::ListingQuery;
+use super::library::*;
+use super::library_cache::LibraryCache;
+use super::playlist_file::*;
+use ::subtitles::Error as SubtitlesError;
 use iron::mime::Mime;
+use iron::response::WriteBody;
 
 
 use ::utils::for_each_in_dir;
 use std::path::{Path, PathBuf};
 use std::ops::Deref;
 use std::sync::mpsc::{channel, Sender, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use std::thread;
 use serde_json;
 use bodyparser;
 
@@ -30,6 +44,14 @@ enum WebCommandType {
     AddMultipleToQueue,
     #[serde(rename = "delete_from_queue")]
     DeleteFromQueue,
+    #[serde(rename = "move_in_queue")]
+    MoveInQueue,
+    #[serde(rename = "swap_in_queue")]
+    SwapInQueue,
+    #[serde(rename = "move_to_top")]
+    MoveToTop,
+    #[serde(rename = "reorder_queue")]
+    ReorderQueue,
     #[serde(rename = "clear_queue")]
     ClearQueue,
     #[serde(rename = "pause")]
@@ -46,6 +68,26 @@ enum WebCommandType {
     ToggleSubtitles,
     #[serde(rename = "announcement")]
     Announcement,
+    #[serde(rename = "rescan_library")]
+    RescanLibrary,
+    /// replaces the queue with a saved playlist
+    #[serde(rename = "load_playlist")]
+    LoadPlaylist,
+    /// adds a saved playlist at the end of the queue
+    #[serde(rename = "append_playlist")]
+    AppendPlaylist,
+}
+
+impl WebCommandType {
+    /// guests can only add songs at the end of the queue
+    fn allowed_for(&self, role: Role, pos: Option<u32>) -> bool {
+        match (role, self) {
+            (Role::Operator, _) => true,
+            (Role::Guest, &WebCommandType::AddToQueue) => pos.is_none(),
+            (Role::Guest, &WebCommandType::AddMultipleToQueue) => true,
+            (Role::Guest, _) => false,
+        }
+    }
 }
 
 #[derive(Debug,Deserialize)]
@@ -55,30 +97,89 @@ struct WebCommand {
     list: Option<Vec<u32>>,
     text: Option<String>,
     pos: Option<u32>,
+    /// the entry of the queue the command is about
+    queue_id: Option<QueueId>,
+    /// the other entry for swap_in_queue
+    other_queue_id: Option<QueueId>,
+    /// the whole queue for reorder_queue
+    queue_ids: Option<Vec<QueueId>>,
+    /// destination of move_in_queue
+    to: Option<u32>,
+    /// who asks for the songs; the address of the client is used if absent
+    nickname: Option<String>,
+    /// name of the saved playlist for load_playlist and append_playlist
+    playlist: Option<String>,
+}
+
+/// what saving a playlist did
+#[derive(Debug,Serialize)]
+struct SavedPlaylist {
+    songs: usize,
+    /// the entries of an uploaded playlist which couldn't be used
+    errors: Vec<String>,
 }
 
+/// answer of the commands adding songs
+#[derive(Debug,Serialize)]
+struct AddedSongs {
+    /// the client needs the ids to act on the new entries
+    ids: Vec<QueueId>,
+    /// the songs added by an operator even though they were played recently
+    warnings: Vec<RecentlyPlayed>,
+}
+
+/// how often the yaml directories are checked when they are watched
+const WATCH_INTERVAL_SECS: u64 = 5;
+
+/// how long a command waits for the player, which answers once per frame
+const COMMAND_TIMEOUT_SECS: u64 = 5;
+
+/// a yaml file, with what tells it changed
+type YamlFileState = (PathBuf, Option<SystemTime>, u64);
+
 pub struct Manager {
     listening: Listening,
-    pub receiver: Receiver<Command>,
-    _yaml_files: Arc<Vec<VideoMeta>>,
+    pub receiver: Receiver<ManagerRequest>,
+    library: SharedLibrary,
+    yaml_directories: Arc<Vec<PathBuf>>,
+    /// where the parsed yaml files are kept between two runs
+    library_cache: Arc<Option<PathBuf>>,
+    events: EventStreams,
+    /// tells the watcher of the yaml directories to stop
+    stop_watching: Arc<AtomicBool>,
 }
 
 impl Manager {
     fn add_yaml_file<P: AsRef<Path>>(yaml_files: &mut Vec<VideoMeta>,
-                                     file: P)
+                                     file: P,
+                                     cache: &mut LibraryCache)
                                      -> Result<(), String> {
         let file = file.as_ref();
-
-        match VideoMeta::from_yaml(file) {
+        let parse = |file: &Path| {
+            VideoMeta::from_yaml(file).map(|mut video_meta| {
+                video_meta.yaml_path = Some(file.to_path_buf());
+                video_meta.fix_paths(file)
+            })
+        };
+        match cache.get_or_parse(file, parse) {
             Ok(video_meta) => {
-                yaml_files.push(video_meta.fix_paths(file));
+                yaml_files.push(video_meta);
                 Ok(())
             }
             Err(e) => Err(format!("Error when loading yaml file '{}' : {}", file.display(), e)),
         }
     }
 
-    fn parse_yaml_directory<P: AsRef<Path>>(directory: P) -> Result<Vec<VideoMeta>, String> {
+    /// returns the yaml files which could be loaded, and why the other ones
+    /// couldn't
+    pub fn load_yaml_directory<P: AsRef<Path>>(directory: P) -> (Vec<VideoMeta>, Vec<String>) {
+        Self::load_yaml_directory_cached(directory, &mut LibraryCache::new())
+    }
+
+    /// only the files changed since they were cached are parsed
+    fn load_yaml_directory_cached<P: AsRef<Path>>(directory: P,
+                                                  cache: &mut LibraryCache)
+                                                  -> (Vec<VideoMeta>, Vec<String>) {
         let mut yaml_files: Vec<VideoMeta> = Vec::new();
         let (paths, errs) = for_each_in_dir(directory,
                                             3,
@@ -88,20 +189,102 @@ impl Manager {
                                                     _ => false,
                                                 }
                                             });
-        for err in errs {
-            error!("IoError '{}' when parsing yaml dir", err);
-        }
+        let mut errors: Vec<String> = errs.iter()
+            .map(|err| format!("IoError '{}' when parsing yaml dir", err))
+            .collect();
         for path in paths {
-            match Self::add_yaml_file(&mut yaml_files, &path) {
-                Ok(()) => {}
-                Err(err_string) => {
-                    error!("{}", err_string);
-                }
+            if let Err(err_string) = Self::add_yaml_file(&mut yaml_files, &path, cache) {
+                errors.push(err_string);
             }
         }
+        (yaml_files, errors)
+    }
+
+    fn parse_yaml_directory<P: AsRef<Path>>(directory: P,
+                                            cache: &mut LibraryCache)
+                                            -> Result<Vec<VideoMeta>, String> {
+        let (yaml_files, errors) = Self::load_yaml_directory_cached(directory, cache);
+        for err_string in errors {
+            error!("{}", err_string);
+        }
         Ok(yaml_files)
     }
 
+    /// goes through the library cache if there is one, and updates it
+    fn load_songs(yaml_directories: &[PathBuf], library_cache: Option<&Path>) -> Vec<VideoMeta> {
+        let mut cache = match library_cache {
+            Some(path) => LibraryCache::open(path),
+            None => LibraryCache::new(),
+        };
+        let mut yaml_files: Vec<VideoMeta> = Vec::new();
+        for dir in yaml_directories {
+            yaml_files.extend(Self::parse_yaml_directory(dir, &mut cache).unwrap());
+        }
+        if let Some(path) = library_cache {
+            if let Err(e) = cache.save(path) {
+                error!("Couldn't save the library cache '{}' : {}", path.display(), e);
+            }
+        }
+        yaml_files
+    }
+
+    /// the songs already queued are copies, a rescan doesn't change them
+    fn rescan_library(library: &SharedLibrary,
+                      yaml_directories: &[PathBuf],
+                      library_cache: Option<&Path>,
+                      events: &EventStreams) {
+        match library.rescan(|| Self::load_songs(yaml_directories, library_cache)) {
+            Some(changes) => {
+                info!("Library rescanned : {} songs added, {} removed",
+                      changes.added,
+                      changes.removed);
+                events.broadcast(StateEvent::LibraryUpdated {
+                    added: changes.added,
+                    removed: changes.removed,
+                });
+            }
+            None => warn!("The library is already being rescanned"),
+        }
+    }
+
+    fn yaml_files_state(yaml_directories: &[PathBuf]) -> Vec<YamlFileState> {
+        let mut files_state: Vec<YamlFileState> = vec![];
+        for dir in yaml_directories {
+            let is_yaml = |path: &Path| path.extension().map_or(false, |e| e == "yaml");
+            let (paths, _) = for_each_in_dir(dir, 3, &is_yaml);
+            for path in paths {
+                if let Ok(metadata) = path.metadata() {
+                    files_state.push((path, metadata.modified().ok(), metadata.len()));
+                }
+            }
+        }
+        files_state.sort();
+        files_state
+    }
+
+    /// checks the yaml files every few seconds and rescans when one of
+    /// them is added, changed or removed
+    fn watch_yaml_directories(library: SharedLibrary,
+                              yaml_directories: Arc<Vec<PathBuf>>,
+                              library_cache: Arc<Option<PathBuf>>,
+                              events: EventStreams,
+                              stop: Arc<AtomicBool>) {
+        thread::spawn(move || {
+            let mut files_state = Self::yaml_files_state(&yaml_directories);
+            while !stop.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_secs(WATCH_INTERVAL_SECS));
+                let new_files_state = Self::yaml_files_state(&yaml_directories);
+                if new_files_state != files_state {
+                    files_state = new_files_state;
+                    Self::rescan_library(&library,
+                                         &yaml_directories,
+                                         library_cache.as_ref().as_ref().map(|p| p.as_path()),
+                                         &events);
+                }
+            }
+        });
+    }
+
     fn state_request(toyunda_state: Weak<RwLock<ToyundaState>>) -> IronResult<Response> {
         match toyunda_state.upgrade() {
             Some(arc_t) => {
@@ -114,24 +297,239 @@ impl Manager {
         }
     }
 
-    fn list_request(list: Weak<Vec<VideoMeta>>) -> IronResult<Response> {
+    fn events_request(events: &EventStreams) -> IronResult<Response> {
+        match events.subscribe() {
+            Some(event_stream) => {
+                let event_stream_mime: Mime = "text/event-stream".parse().unwrap();
+                let body: Box<WriteBody> = Box::new(event_stream);
+                Ok(Response::with((status::Ok, event_stream_mime, body)))
+            }
+            None => Ok(Response::with(status::ServiceUnavailable)),
+        }
+    }
+
+    /// the whole listing without parameters, a page of the songs matching
+    /// the query otherwise
+    fn list_request(request: &Request, library: Arc<Library>) -> IronResult<Response> {
+        let json_mime: Mime = "application/json".parse().unwrap();
+        let url = request.url.clone().into_generic_url();
+        let pairs = url.query_pairs().filter(|&(ref key, _)| key != "token").collect::<Vec<_>>();
+        if pairs.is_empty() {
+            // the position is the id : a song removed by a rescan is a null
+            let json_answer = serde_json::to_string_pretty(&library.songs).unwrap();
+            return Ok(Response::with((status::Ok, json_answer, json_mime)));
+        }
+        match ListingQuery::from_pairs(pairs) {
+            Ok(query) => {
+                let json_answer = serde_json::to_string(&query.apply(&library)).unwrap();
+                Ok(Response::with((status::Ok, json_answer, json_mime)))
+            }
+            Err(e) => Ok(Response::with((status::BadRequest, e))),
+        }
+    }
+
+    fn lyrics_request(request: &Request,
+                      toyunda_state: Weak<RwLock<ToyundaState>>)
+                      -> IronResult<Response> {
+        let format = request.extensions
+            .get::<Router>()
+            .and_then(|params| params.find("format"))
+            .unwrap_or("");
+        let mime: Mime = match format {
+            "lrc" => "text/plain; charset=utf-8".parse().unwrap(),
+            "vtt" => "text/vtt; charset=utf-8".parse().unwrap(),
+            _ => return Ok(Response::with(status::BadRequest)),
+        };
+        let video_meta = match toyunda_state.upgrade() {
+            Some(arc_t) => {
+                let toy_state = arc_t.deref().read().unwrap();
+                let video_meta = match toy_state.playing_state {
+                    PlayingState::Playing(ref video_meta) => video_meta.clone(),
+                    PlayingState::Idle => return Ok(Response::with(status::NotFound)),
+                };
+                video_meta
+            }
+            None => return Ok(Response::with(status::ServiceUnavailable)),
+        };
+        // the state is not locked anymore while the subtitles are read
+        match video_meta.load_subtitles() {
+            Ok(subtitles) => {
+                let lyrics = match format {
+                    "lrc" => subtitles.to_enhanced_lrc(),
+                    _ => subtitles.to_webvtt(),
+                };
+                Ok(Response::with((status::Ok, lyrics, mime)))
+            }
+            Err(e) => {
+                warn!("Couldn't serve the lyrics : {}", e);
+                // the message tells what is wrong with the subtitles
+                let status = match e {
+                    SubtitlesError::Io { .. } => status::NotFound,
+                    _ => status::InternalServerError,
+                };
+                Ok(Response::with((status, e.to_string())))
+            }
+        }
+    }
+
+    /// the name of the playlist in the url of the request
+    fn playlist_name(request: &Request) -> String {
+        let name = request.extensions
+            .get::<Router>()
+            .and_then(|params| params.find("name"))
+            .unwrap_or("");
+        percent_decode(name)
+    }
+
+    /// m3u8 unless `?format=xspf` is given
+    fn export_response(request: &Request, songs: &[VideoMeta]) -> IronResult<Response> {
+        let url = request.url.clone().into_generic_url();
+        let format = url.query_pairs()
+            .find(|&(ref key, _)| key == "format")
+            .map(|(_, value)| PlaylistFormat::from_name(&value))
+            .unwrap_or(Ok(PlaylistFormat::M3u8));
+        match format {
+            Ok(format) => {
+                let mime: Mime = format.mime().parse().unwrap();
+                Ok(Response::with((status::Ok, export(songs, format), mime)))
+            }
+            Err(e) => Ok(Response::with((status::BadRequest, e))),
+        }
+    }
+
+    fn playlists_request(playlists: &PlaylistStore) -> IronResult<Response> {
         let json_mime: Mime = "application/json".parse().unwrap();
-        match list.upgrade() {
-            Some(list) => {
-                let json_answer = serde_json::to_string_pretty(list.as_ref()).unwrap();
+        let json_answer = serde_json::to_string(&playlists.names()).unwrap();
+        Ok(Response::with((status::Ok, json_answer, json_mime)))
+    }
+
+    fn export_playlist_request(request: &Request,
+                               playlists: &PlaylistStore)
+                               -> IronResult<Response> {
+        match playlists.load(&Self::playlist_name(request)) {
+            Ok((songs, _)) => Self::export_response(request, &songs),
+            Err(e) => Ok(Response::with((status::NotFound, e))),
+        }
+    }
+
+    fn export_queue_request(request: &Request,
+                            toyunda_state: Weak<RwLock<ToyundaState>>)
+                            -> IronResult<Response> {
+        match toyunda_state.upgrade() {
+            Some(arc_t) => {
+                let songs: Vec<VideoMeta> =
+                    arc_t.read().unwrap().playlist.iter().map(|e| e.video_meta.clone()).collect();
+                Self::export_response(request, &songs)
+            }
+            None => Ok(Response::with(status::ServiceUnavailable)),
+        }
+    }
+
+    /// Saves the current queue, or the m3u8 or xspf playlist sent in the
+    /// body; its relative paths start from the playlists directory then
+    fn save_playlist_request(request: &mut Request,
+                             playlists: &PlaylistStore,
+                             toyunda_state: Weak<RwLock<ToyundaState>>)
+                             -> IronResult<Response> {
+        let name = Self::playlist_name(request);
+        let body = match request.get::<bodyparser::Raw>() {
+            Ok(body) => body,
+            Err(err) => return Err(IronError::new(err, status::BadRequest)),
+        };
+        let (songs, errors) = match body {
+            Some(ref content) if !content.trim().is_empty() => {
+                import(content, PlaylistFormat::detect(content), playlists.directory())
+            }
+            _ => {
+                match toyunda_state.upgrade() {
+                    Some(arc_t) => {
+                        let playlist = &arc_t.read().unwrap().playlist;
+                        (playlist.iter().map(|e| e.video_meta.clone()).collect(), vec![])
+                    }
+                    None => return Ok(Response::with(status::ServiceUnavailable)),
+                }
+            }
+        };
+        match playlists.save(&name, &songs) {
+            Ok(()) => {
+                info!("Playlist '{}' saved with {} songs", name, songs.len());
+                let json_mime: Mime = "application/json".parse().unwrap();
+                let json_answer = serde_json::to_string(&SavedPlaylist {
+                        songs: songs.len(),
+                        errors: errors,
+                    })
+                    .unwrap();
                 Ok(Response::with((status::Ok, json_answer, json_mime)))
             }
-            None => Ok(Response::with((status::Gone, "{}", json_mime))),
+            Err(e) => Ok(Response::with((status::BadRequest, e))),
         }
     }
 
+    fn delete_playlist_request(request: &Request,
+                               playlists: &PlaylistStore)
+                               -> IronResult<Response> {
+        match playlists.delete(&Self::playlist_name(request)) {
+            Ok(()) => Ok(Response::with(status::NoContent)),
+            Err(e) => Ok(Response::with((status::NotFound, e))),
+        }
+    }
+
+    /// the songs of a saved playlist added to the queue, after it's cleared
+    /// if `replace` is true
+    fn playlist_commands(playlists: &PlaylistStore,
+                         name: &Option<String>,
+                         replace: bool,
+                         added_by: &Option<String>)
+                         -> Result<Vec<Command>, String> {
+        let name = match *name {
+            Some(ref name) => name,
+            None => return Err(String::from("'playlist' field is needed")),
+        };
+        let (songs, errors) = try!(playlists.load(name));
+        for error in errors {
+            warn!("Playlist '{}' : {}", name, error);
+        }
+        let mut commands = if replace {
+            vec![Command::ClearQueue]
+        } else {
+            vec![]
+        };
+        commands.extend(songs.into_iter().map(|video_meta| {
+            Command::AddToQueue(QueueEntry::new(video_meta, added_by.clone()))
+        }));
+        Ok(commands)
+    }
+
+    fn role_request(role: Role) -> IronResult<Response> {
+        let json_mime: Mime = "application/json".parse().unwrap();
+        let json_answer = serde_json::to_string(&role).unwrap();
+        Ok(Response::with((status::Ok, json_answer, json_mime)))
+    }
+
     fn command(request: &mut Request,
-               tx: Sender<Command>,
-               list: Weak<Vec<VideoMeta>>)
+               role: Role,
+               quotas: &Mutex<RequestQuotas>,
+               replay_cooldown: Option<u32>,
+               tx: Sender<ManagerRequest>,
+               library: Arc<Library>,
+               playlists: &PlaylistStore,
+               toyunda_state: Weak<RwLock<ToyundaState>>)
                -> IronResult<Response> {
+        let remote_ip = request.remote_addr.ip();
         let web_command = request.get_ref::<bodyparser::Struct<WebCommand>>();
         match web_command {
             Ok(&Some(ref web_command)) => {
+                let added_by = match web_command.nickname {
+                    Some(ref nickname) if !nickname.trim().is_empty() => {
+                        Some(nickname.trim().to_string())
+                    }
+                    // the address stays in `requested_from`, which isn't
+                    // shown to the other clients
+                    _ => None,
+                };
+                if !web_command.command.allowed_for(role, web_command.pos) {
+                    return Ok(Role::forbidden_response(Some(role)));
+                }
                 let commands: Result<Vec<Command>, String> = match web_command.command {
                     WebCommandType::PlayNext => Ok(vec![Command::PlayNext]),
                     WebCommandType::ClearQueue => Ok(vec![Command::ClearQueue]),
@@ -139,20 +537,19 @@ impl Manager {
                     WebCommandType::Pause => Ok(vec![Command::TogglePause]),
                     WebCommandType::AddToQueue => {
                         if let Some(id) = web_command.id {
-                            if let Some(list) = list.upgrade() {
-                                if let Some(video_meta) = list.get(id as usize) {
-                                    match web_command.pos {
-                                        None => Ok(vec![Command::AddToQueue(video_meta.clone())]),
-                                        Some(pos) => {
-                                            Ok(vec![Command::AddToQueueWithPos(video_meta.clone(),
-                                                                               pos as usize)])
-                                        }
+                            // None as well if the song was removed by a rescan
+                            if let Some(video_meta) = library.get(id as usize) {
+                                let queue_entry = QueueEntry::new(video_meta.clone(),
+                                                                  added_by.clone());
+                                match web_command.pos {
+                                    None => Ok(vec![Command::AddToQueue(queue_entry)]),
+                                    Some(pos) => {
+                                        Ok(vec![Command::AddToQueueWithPos(queue_entry,
+                                                                           pos as usize)])
                                     }
-                                } else {
-                                    Err(format!("Bad Index {}", id))
                                 }
                             } else {
-                                Err(String::from("Gone"))
+                                Err(format!("Bad Index {}", id))
                             }
                         } else {
                             Err(String::from("'id' field is needed"))
@@ -160,34 +557,72 @@ impl Manager {
                     }
                     WebCommandType::AddMultipleToQueue => {
                         if let Some(ref ids) = web_command.list {
-                            if let Some(list) = list.upgrade() {
-                                let commands = ids.iter()
-                                    .filter_map(|id| {
-                                        list.get(*id as usize)
-                                            .map(|video_meta| {
-                                                Command::AddToQueue(video_meta.clone())
-                                            })
-                                    })
-                                    .collect::<Vec<_>>();
-                                Ok(commands)
-                            } else {
-                                Err(String::from("Gone"))
-                            }
+                            let commands = ids.iter()
+                                .filter_map(|id| {
+                                    library.get(*id as usize)
+                                        .map(|video_meta| {
+                                            let queue_entry = QueueEntry::new(video_meta.clone(),
+                                                                              added_by.clone());
+                                            Command::AddToQueue(queue_entry)
+                                        })
+                                })
+                                .collect::<Vec<_>>();
+                            Ok(commands)
                         } else {
                             Err(String::from("'list' field is needed"))
                         }
                     }
                     WebCommandType::DeleteFromQueue => {
-                        if let Some(pos) = web_command.pos {
-                            Ok(vec![Command::DeleteFromQueue(pos as usize)])
+                        if let Some(queue_id) = web_command.queue_id {
+                            Ok(vec![Command::DeleteFromQueue(queue_id)])
                         } else {
-                            Err(format!("'pos' field is needed"))
+                            Err(String::from("'queue_id' field is needed"))
+                        }
+                    }
+                    WebCommandType::MoveInQueue => {
+                        match (web_command.queue_id, web_command.to) {
+                            (Some(queue_id), Some(to)) => {
+                                Ok(vec![Command::MoveInQueue(queue_id, to as usize)])
+                            }
+                            _ => Err(String::from("'queue_id' and 'to' fields are needed")),
+                        }
+                    }
+                    WebCommandType::SwapInQueue => {
+                        match (web_command.queue_id, web_command.other_queue_id) {
+                            (Some(queue_id), Some(other_queue_id)) => {
+                                Ok(vec![Command::SwapInQueue(queue_id, other_queue_id)])
+                            }
+                            _ => {
+                                Err(String::from("'queue_id' and 'other_queue_id' fields are \
+                                                  needed"))
+                            }
+                        }
+                    }
+                    WebCommandType::MoveToTop => {
+                        if let Some(queue_id) = web_command.queue_id {
+                            Ok(vec![Command::MoveToTop(queue_id)])
+                        } else {
+                            Err(String::from("'queue_id' field is needed"))
+                        }
+                    }
+                    WebCommandType::ReorderQueue => {
+                        if let Some(ref queue_ids) = web_command.queue_ids {
+                            Ok(vec![Command::ReorderQueue(queue_ids.clone())])
+                        } else {
+                            Err(String::from("'queue_ids' field is needed"))
                         }
                     }
                     WebCommandType::PauseBeforeNext => Ok(vec![Command::PauseBeforeNext]),
                     WebCommandType::QuitOnFinish => Ok(vec![Command::ToggleQuitOnFinish]),
                     WebCommandType::Quit => Ok(vec![Command::Quit]),
                     WebCommandType::ToggleSubtitles => Ok(vec![Command::ToggleDisplaySubtitles]),
+                    WebCommandType::RescanLibrary => Ok(vec![Command::RescanLibrary]),
+                    WebCommandType::LoadPlaylist => {
+                        Self::playlist_commands(playlists, &web_command.playlist, true, &added_by)
+                    }
+                    WebCommandType::AppendPlaylist => {
+                        Self::playlist_commands(playlists, &web_command.playlist, false, &added_by)
+                    }
                     WebCommandType::Announcement => {
                         if let Some(ref s) = web_command.text {
                             use chrono::Local;
@@ -201,18 +636,128 @@ impl Manager {
                         }
                     }
                 };
-                if let Ok(commands) = commands {
-                    for command in commands {
-                        if let Err(e) = tx.send(command) {
-                            error!("An error happened when trying\
-                            to send a command to the other thread : {}",
-                                   e);
-                            return Ok(Response::with(status::InternalServerError));
+                let mut commands = match commands {
+                    Ok(commands) => commands,
+                    Err(e) => return Ok(Response::with((status::BadRequest, e))),
+                };
+                for command in &mut commands {
+                    match *command {
+                        Command::AddToQueue(ref mut queue_entry) |
+                        Command::AddToQueueWithPos(ref mut queue_entry, _) => {
+                            queue_entry.requested_from = Some(remote_ip);
+                        }
+                        _ => {}
+                    }
+                }
+                let added_ids: Vec<QueueId> = commands.iter()
+                    .filter_map(|command| match *command {
+                        Command::AddToQueue(ref queue_entry) |
+                        Command::AddToQueueWithPos(ref queue_entry, _) => Some(queue_entry.id),
+                        _ => None,
+                    })
+                    .collect();
+                let mut warnings: Vec<RecentlyPlayed> = vec![];
+                if let Some(arc_t) = toyunda_state.upgrade() {
+                    if let Some(minutes) = replay_cooldown {
+                        use chrono::Local;
+                        let state = arc_t.read().unwrap();
+                        // a song added twice by the same command is refused too
+                        let mut queue = state.playlist.clone();
+                        let now = Local::now().timestamp();
+                        let time_left = state.time_left(now);
+                        for command in &commands {
+                            let queue_entry = match *command {
+                                Command::AddToQueue(ref queue_entry) |
+                                Command::AddToQueueWithPos(ref queue_entry, _) => queue_entry,
+                                _ => continue,
+                            };
+                            let video_meta = &queue_entry.video_meta;
+                            let last_played = state.played_at
+                                .get(&video_meta.video_path)
+                                .cloned()
+                                .or(video_meta.last_played);
+                            if let Err(refusal) =
+                                check_cooldown(video_meta,
+                                               last_played,
+                                               &queue,
+                                               time_left,
+                                               minutes,
+                                               now) {
+                                if role == Role::Guest {
+                                    let json_mime: Mime = "application/json".parse().unwrap();
+                                    let json_answer = serde_json::to_string(&refusal).unwrap();
+                                    return Ok(Response::with((status::Conflict,
+                                                              json_answer,
+                                                              json_mime)));
+                                }
+                                warn!("{}", refusal.reason);
+                                warnings.push(refusal);
+                            }
+                            queue.push_back(queue_entry.clone());
                         }
                     }
+                    if role == Role::Guest && !added_ids.is_empty() {
+                        use chrono::Local;
+                        let playlist = &arc_t.read().unwrap().playlist;
+                        let now = Local::now().timestamp();
+                        let quota_result = quotas.lock()
+                            .unwrap()
+                            .check(playlist, remote_ip, added_ids.len(), now);
+                        if let Err(e) = quota_result {
+                            return Ok(Response::with((status::TooManyRequests, e)));
+                        }
+                    }
+                }
+                // the player checks the ids against its queue and answers once
+                // the commands are done, it's the only one to know the queue
+                // is still the one the client saw
+                let (reply_tx, reply_rx) = channel();
+                let manager_request = ManagerRequest {
+                    commands: commands,
+                    reply: reply_tx,
+                };
+                if let Err(e) = tx.send(manager_request) {
+                    error!("An error happened when trying to send a command to the other \
+                            thread : {}",
+                           e);
+                    return Ok(Response::with(status::InternalServerError));
+                }
+                match reply_rx.recv_timeout(Duration::from_secs(COMMAND_TIMEOUT_SECS)) {
+                    Ok(Ok(())) => {
+                        // the songs refused by the player don't count
+                        if role == Role::Guest && !added_ids.is_empty() {
+                            use chrono::Local;
+                            quotas.lock()
+                                .unwrap()
+                                .record(remote_ip, added_ids.len(), Local::now().timestamp());
+                        }
+                    }
+                    Ok(Err(PlayerError::QueueError(e))) => {
+                        // someone else changed the queue in the meantime
+                        let status = match e {
+                            QueueError::UnknownId(_) => status::Conflict,
+                            _ => status::BadRequest,
+                        };
+                        return Ok(Response::with((status, e.to_string())));
+                    }
+                    Ok(Err(e)) => {
+                        return Ok(Response::with((status::InternalServerError, e.to_string())))
+                    }
+                    Err(e) => {
+                        error!("The player didn't answer a command : {}", e);
+                        return Ok(Response::with(status::ServiceUnavailable));
+                    }
+                }
+                if added_ids.is_empty() {
                     Ok(Response::with(status::NoContent))
                 } else {
-                    Ok(Response::with(status::BadRequest))
+                    let json_mime: Mime = "application/json".parse().unwrap();
+                    let json_answer = serde_json::to_string(&AddedSongs {
+                            ids: added_ids,
+                            warnings: warnings,
+                        })
+                        .unwrap();
+                    Ok(Response::with((status::Ok, json_answer, json_mime)))
                 }
             }
             Ok(&None) => Ok(Response::with(status::BadRequest)),
@@ -243,58 +788,203 @@ impl Manager {
     pub fn new<A: ToSocketAddrs>(address: A,
                                  toyunda_state: Weak<RwLock<ToyundaState>>,
                                  yaml_directories: Vec<PathBuf>,
-                                 songs_history: Option<&SongsHistory>)
+                                 songs_history: Option<&SongsHistory>,
+                                 auth: ManagerAuth,
+                                 quotas: RequestQuotas,
+                                 watch_directories: bool,
+                                 library_cache: Option<PathBuf>,
+                                 playlists: PlaylistStore,
+                                 replay_cooldown: Option<u32>)
                                  -> IronResult<Manager> {
-        let mut yaml_files: Vec<VideoMeta> = Vec::new();
-        for dir in yaml_directories {
-            if yaml_files.is_empty() {
-                yaml_files = Self::parse_yaml_directory(&dir).unwrap();
-            } else {
-                yaml_files.extend(Self::parse_yaml_directory(&dir).unwrap());
-            }
-        }
+        let mut yaml_files = Self::load_songs(&yaml_directories,
+                                              library_cache.as_ref().map(|p| p.as_path()));
         if let Some(ref songs_history) = songs_history {
             for video_meta in &mut yaml_files {
                 video_meta.set_last_played(songs_history);
             }
         }
-        let yaml_files = Arc::new(yaml_files);
+        // the search index is built here, and again after every rescan
+        let library = SharedLibrary::new(Library::new(yaml_files));
         let (tx, rx) = channel();
+        let auth = Arc::new(auth);
+        // answers right away if the role of the client is too low
+        macro_rules! check_role {
+            ($auth:expr, $request:expr, $role:expr) => {
+                match $auth.check_request($request, $role) {
+                    Ok(role) => role,
+                    Err(response) => return Ok(response),
+                }
+            }
+        }
         let toyunda_state_cloned = toyunda_state.clone();
+        let auth_cloned = auth.clone();
         let mut api_handler = Router::new();
         api_handler.get("state",
-                        move |_r: &mut Request| {
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
                             Self::state_request(toyunda_state_cloned.clone())
                         },
                         "get_state");
+        let toyunda_state_cloned = toyunda_state.clone();
+        let auth_cloned = auth.clone();
+        api_handler.get("lyrics/:format",
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
+                            Self::lyrics_request(request, toyunda_state_cloned.clone())
+                        },
+                        "get_lyrics");
         let tx_command = Mutex::new(tx);
-        let weak_list = Arc::downgrade(&yaml_files);
-        let weak_list2 = weak_list.clone();
+        let library_cloned = library.clone();
+        let playlists_cloned = playlists.clone();
+        let toyunda_state_cloned = toyunda_state.clone();
+        let auth_cloned = auth.clone();
+        // only guests are limited
+        let quotas = Mutex::new(quotas);
         api_handler.post("command",
                          move |request: &mut Request| {
+                             let role = check_role!(auth_cloned, request, Role::Guest);
                              let tx_command = tx_command.lock().unwrap().clone();
-                             Self::command(request, tx_command, weak_list2.clone())
+                             Self::command(request,
+                                           role,
+                                           &quotas,
+                                           replay_cooldown,
+                                           tx_command,
+                                           library_cloned.current(),
+                                           &playlists_cloned,
+                                           toyunda_state_cloned.clone())
                          },
                          "do_command");
+        let playlists_cloned = playlists.clone();
+        let auth_cloned = auth.clone();
+        api_handler.get("playlists",
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
+                            Self::playlists_request(&playlists_cloned)
+                        },
+                        "get_playlists");
+        let playlists_cloned = playlists.clone();
+        let auth_cloned = auth.clone();
+        api_handler.get("playlists/:name",
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
+                            Self::export_playlist_request(request, &playlists_cloned)
+                        },
+                        "get_playlist");
+        let playlists_cloned = playlists.clone();
+        let toyunda_state_cloned = toyunda_state.clone();
+        let auth_cloned = auth.clone();
+        api_handler.post("playlists/:name",
+                         move |request: &mut Request| {
+                             check_role!(auth_cloned, request, Role::Operator);
+                             Self::save_playlist_request(request,
+                                                         &playlists_cloned,
+                                                         toyunda_state_cloned.clone())
+                         },
+                         "save_playlist");
+        let auth_cloned = auth.clone();
+        api_handler.delete("playlists/:name",
+                           move |request: &mut Request| {
+                               check_role!(auth_cloned, request, Role::Operator);
+                               Self::delete_playlist_request(request, &playlists)
+                           },
+                           "delete_playlist");
+        let toyunda_state_cloned = toyunda_state.clone();
+        let auth_cloned = auth.clone();
+        api_handler.get("queue/export",
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
+                            Self::export_queue_request(request, toyunda_state_cloned.clone())
+                        },
+                        "export_queue");
+        let library_cloned = library.clone();
+        let auth_cloned = auth.clone();
         api_handler.get("listing",
-                        move |_r: &mut Request| Self::list_request(weak_list.clone()),
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
+                            Self::list_request(request, library_cloned.current())
+                        },
                         "get_listing");
-        api_handler.get("logs", move |_r: &mut Request| Self::logs(), "get_logs");
+        let auth_cloned = auth.clone();
+        api_handler.get("logs",
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Operator);
+                            Self::logs()
+                        },
+                        "get_logs");
+        let auth_cloned = auth.clone();
+        api_handler.get("role",
+                        move |request: &mut Request| {
+                            let role = check_role!(auth_cloned, request, Role::Guest);
+                            Self::role_request(role)
+                        },
+                        "get_role");
+        let events = EventStreams::new();
+        let events_cloned = events.clone();
+        let auth_cloned = auth.clone();
+        api_handler.get("events",
+                        move |request: &mut Request| {
+                            check_role!(auth_cloned, request, Role::Guest);
+                            Self::events_request(&events_cloned)
+                        },
+                        "get_events");
         let mut mount = Mount::new();
         let web_directory = ::std::env::current_exe().unwrap().parent().unwrap().join("web/");
         mount.mount("/", Static::new(&web_directory));
         mount.mount("/api", api_handler);
-        let listening = Iron::new(mount).http(address).unwrap();
+        let mut iron = Iron::new(mount);
+        // every event stream keeps a thread busy
+        iron.threads = MAX_EVENT_STREAMS + 16;
+        let listening = iron.http(address).unwrap();
+        let yaml_directories = Arc::new(yaml_directories);
+        let library_cache = Arc::new(library_cache);
+        let stop_watching = Arc::new(AtomicBool::new(false));
+        if watch_directories {
+            Self::watch_yaml_directories(library.clone(),
+                                         yaml_directories.clone(),
+                                         library_cache.clone(),
+                                         events.clone(),
+                                         stop_watching.clone());
+        }
         Ok(Manager {
             listening: listening,
-            _yaml_files: yaml_files,
+            library: library,
+            yaml_directories: yaml_directories,
+            library_cache: library_cache,
             receiver: rx,
+            events: events,
+            stop_watching: stop_watching,
         })
     }
+
+    /// reads the yaml directories again in another thread, the listing
+    /// is replaced once it's done
+    pub fn rescan(&self) {
+        let library = self.library.clone();
+        let yaml_directories = self.yaml_directories.clone();
+        let library_cache = self.library_cache.clone();
+        let events = self.events.clone();
+        thread::spawn(move || {
+            Self::rescan_library(&library,
+                                 &yaml_directories,
+                                 library_cache.as_ref().as_ref().map(|p| p.as_path()),
+                                 &events)
+        });
+    }
+
+    /// the songs of the yaml directories, as of the last scan
+    pub fn library(&self) -> Arc<Library> {
+        self.library.current()
+    }
+
+    /// sends the event to every web client listening
+    pub fn notify(&self, event: StateEvent) {
+        self.events.broadcast(event);
+    }
 }
 
 impl Drop for Manager {
     fn drop(&mut self) {
+        self.stop_watching.store(true, Ordering::SeqCst);
         self.listening.close().unwrap();
     }
 }
@@ -0,0 +1,101 @@
+use iron::prelude::*;
+use iron::status;
+use iron::headers::{Authorization, Bearer};
+
+/// ordered : an operator can do everything a guest can
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Serialize)]
+pub enum Role {
+    /// can add songs and look at the state of the player, nothing else
+    #[serde(rename = "guest")]
+    Guest,
+    #[serde(rename = "operator")]
+    Operator,
+}
+
+impl Role {
+    /// the status to answer with when this role is too low
+    pub fn forbidden_response(role: Option<Role>) -> Response {
+        match role {
+            None => Response::with((status::Unauthorized, "A valid password is needed")),
+            Some(_) => Response::with((status::Forbidden, "Only operators can do that")),
+        }
+    }
+}
+
+/// Passwords of the web manager, set at startup.
+///
+/// Without an operator password, everyone is an operator, like before roles
+/// existed. Without a guest password, anyone can be a guest.
+#[derive(Debug,Clone,Default)]
+pub struct ManagerAuth {
+    pub operator_password: Option<String>,
+    pub guest_password: Option<String>,
+}
+
+/// takes as long whether the strings differ at the start or at the end
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl ManagerAuth {
+    /// None if the password is missing or wrong
+    pub fn role_of(&self, password: Option<&str>) -> Option<Role> {
+        let matches = |expected: &Option<String>| match (expected, password) {
+            (&Some(ref expected), Some(password)) => constant_time_eq(expected, password),
+            _ => false,
+        };
+        if self.operator_password.is_none() || matches(&self.operator_password) {
+            Some(Role::Operator)
+        } else if self.guest_password.is_none() || matches(&self.guest_password) {
+            Some(Role::Guest)
+        } else {
+            None
+        }
+    }
+
+    /// The password is read from the `Authorization: Bearer` header, or
+    /// from the `token` query parameter since EventSource can't send headers.
+    pub fn request_role(&self, request: &Request) -> Option<Role> {
+        let header_token = request.headers
+            .get::<Authorization<Bearer>>()
+            .map(|header| header.0.token.clone());
+        let token = header_token.or_else(|| {
+            request.url
+                .clone()
+                .into_generic_url()
+                .query_pairs()
+                .find(|&(ref key, _)| key == "token")
+                .map(|(_, value)| value.into_owned())
+        });
+        self.role_of(token.as_ref().map(|t| t.as_str()))
+    }
+
+    /// Err contains the response to send right away if the role is too low
+    pub fn check_request(&self, request: &Request, needed: Role) -> Result<Role, Response> {
+        match self.request_role(request) {
+            Some(role) if role >= needed => Ok(role),
+            role => Err(Role::forbidden_response(role)),
+        }
+    }
+}
+
+#[test]
+fn test_manager_roles() {
+    let open = ManagerAuth::default();
+    assert_eq!(open.role_of(None), Some(Role::Operator));
+    let auth = ManagerAuth {
+        operator_password: Some(String::from("op")),
+        guest_password: None,
+    };
+    assert_eq!(auth.role_of(Some("op")), Some(Role::Operator));
+    assert_eq!(auth.role_of(Some("o")), Some(Role::Guest));
+    assert_eq!(auth.role_of(None), Some(Role::Guest));
+    let auth = ManagerAuth {
+        operator_password: Some(String::from("op")),
+        guest_password: Some(String::from("guest")),
+    };
+    assert_eq!(auth.role_of(Some("op")), Some(Role::Operator));
+    assert_eq!(auth.role_of(Some("guest")), Some(Role::Guest));
+    assert_eq!(auth.role_of(Some("nope")), None);
+    assert_eq!(auth.role_of(None), None);
+}
@@ -0,0 +1,251 @@
+use ::toyunda_player::error::Result;
+#[cfg(test)]
+use std::cell::{RefCell, RefMut};
+#[cfg(test)]
+use std::collections::VecDeque;
+#[cfg(test)]
+use std::rc::Rc;
+
+/// what happened to the media since the last call to `poll_event`
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum MediaEvent {
+    /// the file given to `load_file` is ready, its duration and fps are known
+    FileLoaded,
+    /// the file reached its end
+    EndOfFile,
+    /// the file given to `load_file` couldn't be played
+    LoadingFailed,
+    Shutdown,
+}
+
+/// Everything ToyundaPlayer needs from the thing actually playing the videos.
+///
+/// Times are in seconds, like mpv does; properties are cached in `update` so
+/// reading them stays cheap during the rendering of a frame.
+pub trait MediaBackend {
+    fn load_file(&mut self, path: &str) -> Result<()>;
+    fn stop(&mut self) -> Result<()>;
+    /// relative seek
+    fn seek(&mut self, delta: f64) -> Result<()>;
+    /// absolute seek, between 0 and 100
+    fn set_percent_pos(&mut self, percent_pos: f64) -> Result<()>;
+    /// one frame forward if step is positive, one frame backwards otherwise
+    fn framestep(&mut self, step: i32) -> Result<()>;
+    fn is_paused(&self) -> Result<bool>;
+    fn set_pause(&mut self, pause: bool) -> Result<()>;
+    fn set_speed(&mut self, speed: f64) -> Result<()>;
+    /// in percents, like mpv's `volume`
+    fn volume(&self) -> Result<f64>;
+    fn max_volume(&self) -> Result<f64>;
+    fn set_volume(&mut self, volume: f64) -> Result<()>;
+    /// plays the current file forever, used by the edit mode
+    fn set_loop_file(&mut self, loop_file: bool) -> Result<()>;
+    /// draws the current frame of the video on the current (GL) window
+    fn draw(&mut self, width: u32, height: u32) -> Result<()>;
+    /// refreshes the cached properties, called once per frame
+    fn update(&mut self);
+    fn time_pos(&self) -> Option<f64>;
+    fn percent_pos(&self) -> Option<f64>;
+    /// width and height of the video
+    fn dimensions(&self) -> Option<(u32, u32)>;
+    fn duration(&self) -> Option<f64>;
+    fn fps(&self) -> Option<f64>;
+    fn poll_event(&mut self) -> Option<MediaEvent>;
+}
+
+/// A MediaBackend which plays nothing : its clock only moves when `advance`
+/// is called, so tests can script exactly what happens and when. Its clones
+/// share the same media, a test keeps one to drive the one given to the player.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct FakeBackend(Rc<RefCell<FakeMedia>>);
+
+#[cfg(test)]
+pub struct FakeMedia {
+    /// every file loaded so far, in order
+    pub loaded_files: Vec<String>,
+    /// files whose loading will fail
+    pub failing_files: Vec<String>,
+    /// duration given to every loaded file
+    pub file_duration: f64,
+    pub paused: bool,
+    pub speed: f64,
+    pub volume: f64,
+    pub loop_file: bool,
+    current_file: Option<String>,
+    time_pos: f64,
+    events: VecDeque<MediaEvent>,
+}
+
+#[cfg(test)]
+impl FakeBackend {
+    pub fn new(file_duration: f64) -> FakeBackend {
+        FakeBackend(Rc::new(RefCell::new(FakeMedia {
+            loaded_files: vec![],
+            failing_files: vec![],
+            file_duration: file_duration,
+            paused: false,
+            speed: 1.0,
+            volume: 100.0,
+            loop_file: false,
+            current_file: None,
+            time_pos: 0.0,
+            events: VecDeque::new(),
+        })))
+    }
+
+    pub fn media(&self) -> RefMut<FakeMedia> {
+        self.0.borrow_mut()
+    }
+
+    pub fn current_file(&self) -> Option<String> {
+        self.0.borrow().current_file.clone()
+    }
+
+    pub fn push_event(&self, event: MediaEvent) {
+        self.media().events.push_back(event);
+    }
+
+    /// moves the clock forward by `delta` seconds of real time, sends
+    /// EndOfFile if the end of the file is reached
+    pub fn advance(&self, delta: f64) {
+        let mut media = self.media();
+        if media.current_file.is_none() || media.paused {
+            return;
+        }
+        media.time_pos += delta * media.speed;
+        if media.time_pos >= media.file_duration {
+            if media.loop_file {
+                media.time_pos = media.time_pos % media.file_duration;
+            } else {
+                media.time_pos = 0.0;
+                media.current_file = None;
+                media.events.push_back(MediaEvent::EndOfFile);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl MediaBackend for FakeBackend {
+    fn load_file(&mut self, path: &str) -> Result<()> {
+        let mut media = self.media();
+        media.loaded_files.push(String::from(path));
+        media.time_pos = 0.0;
+        if media.failing_files.iter().any(|f| f == path) {
+            media.current_file = None;
+            media.events.push_back(MediaEvent::LoadingFailed);
+        } else {
+            media.current_file = Some(String::from(path));
+            media.events.push_back(MediaEvent::FileLoaded);
+        }
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        let mut media = self.media();
+        media.current_file = None;
+        media.time_pos = 0.0;
+        Ok(())
+    }
+
+    fn seek(&mut self, delta: f64) -> Result<()> {
+        let mut media = self.media();
+        media.time_pos = (media.time_pos + delta).max(0.0).min(media.file_duration);
+        Ok(())
+    }
+
+    fn set_percent_pos(&mut self, percent_pos: f64) -> Result<()> {
+        let mut media = self.media();
+        media.time_pos = media.file_duration * percent_pos / 100.0;
+        Ok(())
+    }
+
+    fn framestep(&mut self, _step: i32) -> Result<()> {
+        self.media().paused = true;
+        Ok(())
+    }
+
+    fn is_paused(&self) -> Result<bool> {
+        Ok(self.0.borrow().paused)
+    }
+
+    fn set_pause(&mut self, pause: bool) -> Result<()> {
+        self.media().paused = pause;
+        Ok(())
+    }
+
+    fn set_speed(&mut self, speed: f64) -> Result<()> {
+        self.media().speed = speed;
+        Ok(())
+    }
+
+    fn volume(&self) -> Result<f64> {
+        Ok(self.0.borrow().volume)
+    }
+
+    fn max_volume(&self) -> Result<f64> {
+        Ok(250.0)
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<()> {
+        self.media().volume = volume;
+        Ok(())
+    }
+
+    fn set_loop_file(&mut self, loop_file: bool) -> Result<()> {
+        self.media().loop_file = loop_file;
+        Ok(())
+    }
+
+    fn draw(&mut self, _width: u32, _height: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn update(&mut self) {}
+
+    fn time_pos(&self) -> Option<f64> {
+        let media = self.0.borrow();
+        media.current_file.as_ref().map(|_| media.time_pos)
+    }
+
+    fn percent_pos(&self) -> Option<f64> {
+        let media = self.0.borrow();
+        media.current_file.as_ref().map(|_| 100.0 * media.time_pos / media.file_duration)
+    }
+
+    fn dimensions(&self) -> Option<(u32, u32)> {
+        self.0.borrow().current_file.as_ref().map(|_| (1280, 720))
+    }
+
+    fn duration(&self) -> Option<f64> {
+        let media = self.0.borrow();
+        media.current_file.as_ref().map(|_| media.file_duration)
+    }
+
+    fn fps(&self) -> Option<f64> {
+        self.0.borrow().current_file.as_ref().map(|_| 25.0)
+    }
+
+    fn poll_event(&mut self) -> Option<MediaEvent> {
+        self.media().events.pop_front()
+    }
+}
+
+#[test]
+fn test_fake_backend_clock() {
+    let mut backend = FakeBackend::new(10.0);
+    backend.load_file("a.mp4").unwrap();
+    assert_eq!(backend.poll_event(), Some(MediaEvent::FileLoaded));
+    assert_eq!(backend.current_file(), Some(String::from("a.mp4")));
+    backend.advance(4.0);
+    backend.set_pause(true).unwrap();
+    backend.advance(4.0);
+    assert_eq!(backend.time_pos(), Some(4.0));
+    backend.set_pause(false).unwrap();
+    backend.set_speed(2.0).unwrap();
+    backend.advance(3.0);
+    assert_eq!(backend.poll_event(), Some(MediaEvent::EndOfFile));
+    assert_eq!(backend.time_pos(), None);
+    assert_eq!(backend.poll_event(), None);
+}
@@ -1,16 +1,27 @@
 mod toyunda_mode;
 mod toyunda;
-mod error;
+pub mod error;
 mod command;
 mod playing_state;
 mod manager;
+mod manager_auth;
+mod listing;
+mod search_index;
+mod library;
+mod library_cache;
 mod playlist;
+mod playlist_file;
+mod auto_dj;
+mod intermission;
+mod queue_journal;
 mod state;
+mod state_events;
 mod video_meta;
 mod editor;
 mod subtitle_loader;
 mod toyunda_history;
 mod startup_options;
+mod media_backend;
 pub mod log_messages;
 pub mod graphic_message;
 pub mod time_info;
@@ -20,3 +31,8 @@ pub use self::toyunda::*;
 pub use self::video_meta::*;
 pub use self::playlist::*;
 pub use self::startup_options::*;
+pub use self::media_backend::*;
+pub use self::state_events::StateEvent;
+pub use self::subtitle_loader::{AssFile, UltraStarFile};
+pub use self::manager::Manager;
+pub use self::manager_auth::ManagerAuth;
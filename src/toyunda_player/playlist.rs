@@ -1,4 +1,477 @@
 use ::toyunda_player::video_meta::VideoMeta;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::fmt;
+use std::cmp::min;
+use std::net::IpAddr;
+use chrono::{Local, TimeZone};
 
-pub type Playlist = VecDeque<VideoMeta>;
+pub type QueueId = usize;
+
+static NEXT_QUEUE_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// a song waiting in the queue : unlike its position, its id never changes
+#[derive(Debug,Clone,Serialize)]
+pub struct QueueEntry {
+    pub id: QueueId,
+    /// who asked for the song, None if it was added from the player itself
+    pub added_by: Option<String>,
+    /// where the request came from, what the quotas count : the nickname of
+    /// `added_by` is chosen by the guest
+    #[serde(skip_serializing)]
+    pub requested_from: Option<IpAddr>,
+    /// UNIX TIMESTAMP
+    pub added_at: i64,
+    pub video_meta: VideoMeta,
+    /// picked by the auto-DJ, the songs asked for go before it
+    pub auto_dj: bool,
+}
+
+impl QueueEntry {
+    pub fn new(video_meta: VideoMeta, added_by: Option<String>) -> QueueEntry {
+        QueueEntry {
+            id: NEXT_QUEUE_ID.fetch_add(1, Ordering::SeqCst),
+            added_by: added_by,
+            requested_from: None,
+            added_at: Local::now().timestamp(),
+            video_meta: video_meta,
+            auto_dj: false,
+        }
+    }
+
+    pub fn auto_dj(video_meta: VideoMeta) -> QueueEntry {
+        QueueEntry { auto_dj: true, ..QueueEntry::new(video_meta, None) }
+    }
+
+    /// who takes turns with the others in the queue
+    fn requester(&self) -> Requester {
+        match self.requested_from {
+            Some(address) => Requester::Address(address),
+            None => Requester::Name(&self.added_by),
+        }
+    }
+}
+
+/// the address a song was requested from, a guest can't take another turn by
+/// changing their nickname; the entries added from the player or a playlist
+/// don't have one
+#[derive(Debug,PartialEq)]
+enum Requester<'a> {
+    Address(IpAddr),
+    Name(&'a Option<String>),
+}
+
+pub type Playlist = VecDeque<QueueEntry>;
+
+pub trait HasQueueId {
+    fn queue_id(&self) -> QueueId;
+}
+
+impl HasQueueId for QueueEntry {
+    fn queue_id(&self) -> QueueId {
+        self.id
+    }
+}
+
+/// the ids alone are enough to check a command before sending it
+impl HasQueueId for QueueId {
+    fn queue_id(&self) -> QueueId {
+        *self
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum QueueError {
+    /// the entry was removed or played in the meantime
+    UnknownId(QueueId),
+    OutOfBounds { pos: usize, len: usize },
+    /// the new order doesn't contain every entry exactly once
+    BadOrder,
+    /// what the command does to the queue can't be told beforehand, it can't
+    /// be sent along other commands
+    NotAlone,
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QueueError::UnknownId(id) => write!(f, "No entry with the id {} in the queue", id),
+            QueueError::OutOfBounds { pos, len } => {
+                write!(f, "Position {} is out of bounds, queue is of size {}", pos, len)
+            }
+            QueueError::BadOrder => {
+                write!(f, "The new order must contain every entry of the queue exactly once")
+            }
+            QueueError::NotAlone => write!(f, "This command must be sent alone"),
+        }
+    }
+}
+
+pub fn position_of<T: HasQueueId>(queue: &VecDeque<T>, id: QueueId) -> Result<usize, QueueError> {
+    queue.iter().position(|e| e.queue_id() == id).ok_or(QueueError::UnknownId(id))
+}
+
+pub fn insert_in_queue<T>(queue: &mut VecDeque<T>, pos: usize, entry: T) -> Result<(), QueueError> {
+    if pos <= queue.len() {
+        queue.insert(pos, entry);
+        Ok(())
+    } else {
+        Err(QueueError::OutOfBounds {
+            pos: pos,
+            len: queue.len(),
+        })
+    }
+}
+
+pub fn remove_from_queue<T: HasQueueId>(queue: &mut VecDeque<T>,
+                                        id: QueueId)
+                                        -> Result<T, QueueError> {
+    let pos = try!(position_of(queue, id));
+    Ok(queue.remove(pos).unwrap())
+}
+
+/// the entry ends up at the position `to`, the entries in between are shifted
+pub fn move_in_queue<T: HasQueueId>(queue: &mut VecDeque<T>,
+                                    id: QueueId,
+                                    to: usize)
+                                    -> Result<(), QueueError> {
+    let from = try!(position_of(queue, id));
+    if to >= queue.len() {
+        return Err(QueueError::OutOfBounds {
+            pos: to,
+            len: queue.len(),
+        });
+    }
+    let entry = queue.remove(from).unwrap();
+    queue.insert(to, entry);
+    Ok(())
+}
+
+pub fn swap_in_queue<T: HasQueueId>(queue: &mut VecDeque<T>,
+                                    id: QueueId,
+                                    other_id: QueueId)
+                                    -> Result<(), QueueError> {
+    let pos = try!(position_of(queue, id));
+    let other_pos = try!(position_of(queue, other_id));
+    queue.swap(pos, other_pos);
+    Ok(())
+}
+
+/// `order` must contain the id of every entry of the queue exactly once
+pub fn reorder_queue<T: HasQueueId>(queue: &mut VecDeque<T>,
+                                    order: &[QueueId])
+                                    -> Result<(), QueueError> {
+    let mut positions: Vec<usize> = Vec::with_capacity(order.len());
+    for &id in order {
+        let pos = try!(position_of(queue, id));
+        if positions.contains(&pos) {
+            return Err(QueueError::BadOrder);
+        }
+        positions.push(pos);
+    }
+    if positions.len() != queue.len() {
+        return Err(QueueError::BadOrder);
+    }
+    let mut entries: Vec<Option<T>> = queue.drain(..).map(Some).collect();
+    for pos in positions {
+        queue.push_back(entries[pos].take().unwrap());
+    }
+    Ok(())
+}
+
+/// the position where `queue_entry` goes when requesters take turns : after
+/// every entry of the same round, a round being the n-th song of each
+/// requester. Entries added from the player count as one requester.
+pub fn fair_position(queue: &Playlist, queue_entry: &QueueEntry) -> usize {
+    let requester = queue_entry.requester();
+    let round = queue.iter().filter(|e| e.requester() == requester).count();
+    let mut rounds: Vec<(Requester, usize)> = vec![];
+    for (pos, entry) in queue.iter().enumerate() {
+        let entry_requester = entry.requester();
+        let entry_round = match rounds.iter_mut().find(|r| r.0 == entry_requester) {
+            Some(r) => {
+                r.1 += 1;
+                r.1
+            }
+            None => {
+                rounds.push((entry_requester, 0));
+                0
+            }
+        };
+        if entry_round > round {
+            return pos;
+        }
+    }
+    queue.len()
+}
+
+/// `pos`, unless an entry picked by the auto-DJ comes before
+pub fn before_auto_dj(queue: &Playlist, pos: usize) -> usize {
+    queue.iter().position(|e| e.auto_dj).map_or(pos, |auto_pos| min(pos, auto_pos))
+}
+
+/// where `queue_entry` goes when it's added without a position : at the end,
+/// or after its round with `fair_queue`; the songs asked for go before the
+/// ones of the auto-DJ
+pub fn add_position(queue: &Playlist, queue_entry: &QueueEntry, fair_queue: bool) -> usize {
+    let pos = if fair_queue {
+        fair_position(queue, queue_entry)
+    } else {
+        queue.len()
+    };
+    if queue_entry.auto_dj {
+        pos
+    } else {
+        before_auto_dj(queue, pos)
+    }
+}
+
+/// Limits on what a single guest can add, None means no limit.
+///
+/// Remembers who added what during the last hour, even when the songs
+/// were played since.
+#[derive(Debug,Clone,Default)]
+pub struct RequestQuotas {
+    pub max_in_queue: Option<usize>,
+    pub max_per_hour: Option<usize>,
+    /// where each song was requested from and when, oldest first
+    history: VecDeque<(IpAddr, i64)>,
+}
+
+impl RequestQuotas {
+    pub fn new(max_in_queue: Option<usize>, max_per_hour: Option<usize>) -> RequestQuotas {
+        RequestQuotas {
+            max_in_queue: max_in_queue,
+            max_per_hour: max_per_hour,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// `count` songs are about to be added by `requester` at `now`, they are
+    /// counted by `record` once the player added them
+    pub fn check(&mut self,
+                 queue: &Playlist,
+                 requester: IpAddr,
+                 count: usize,
+                 now: i64)
+                 -> Result<(), String> {
+        while self.history.front().map_or(false, |&(_, time)| time <= now - 3600) {
+            self.history.pop_front();
+        }
+        if let Some(max_in_queue) = self.max_in_queue {
+            let in_queue = queue.iter()
+                .filter(|e| e.requested_from == Some(requester))
+                .count();
+            if in_queue + count > max_in_queue {
+                return Err(format!("You can't have more than {} songs in the queue, you already \
+                                    have {}",
+                                   max_in_queue,
+                                   in_queue));
+            }
+        }
+        if let Some(max_per_hour) = self.max_per_hour {
+            let last_hour = self.history.iter().filter(|r| r.0 == requester).count();
+            if last_hour + count > max_per_hour {
+                // the oldest request of this requester is the next one to expire
+                let available_in = self.history
+                    .iter()
+                    .find(|r| r.0 == requester)
+                    .map_or(0, |&(_, time)| time + 3600 - now);
+                return Err(format!("You can't add more than {} songs per hour, try again in {} \
+                                    minutes",
+                                   max_per_hour,
+                                   (available_in + 59) / 60));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record(&mut self, requester: IpAddr, count: usize, now: i64) {
+        for _ in 0..count {
+            self.history.push_back((requester, now));
+        }
+    }
+}
+
+/// why a song can't be added again yet
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct RecentlyPlayed {
+    pub reason: String,
+    /// UNIX TIMESTAMP, None if the duration of a song before it isn't known
+    pub available_at: Option<i64>,
+}
+
+/// A song can't be added again less than `minutes` after it was played,
+/// `last_played` and `now` being UNIX TIMESTAMPS. A song already in the queue
+/// is available again `minutes` after it should end, given `time_left` on the
+/// song being played and the durations of the songs before it; a duration of
+/// 0 isn't known.
+pub fn check_cooldown(video_meta: &VideoMeta,
+                      last_played: Option<i64>,
+                      queue: &Playlist,
+                      time_left: Option<i64>,
+                      minutes: u32,
+                      now: i64)
+                      -> Result<(), RecentlyPlayed> {
+    let cooldown = minutes as i64 * 60;
+    let refusal = |reason: String, available_at: Option<i64>| {
+        let reason = match available_at {
+            Some(available_at) => {
+                format!("{}, it can be added again at {}",
+                        reason,
+                        Local.timestamp(available_at, 0).format("%H:%M"))
+            }
+            None => {
+                format!("{}, it can be added again {} minutes after it's played",
+                        reason,
+                        minutes)
+            }
+        };
+        RecentlyPlayed {
+            reason: reason,
+            available_at: available_at,
+        }
+    };
+    if let Some(pos) = queue.iter().position(|e| e.video_meta.video_path == video_meta.video_path) {
+        let ends_in = queue.iter()
+            .take(pos + 1)
+            .fold(time_left, |ends_in, e| match e.video_meta.video_duration {
+                0 => None,
+                duration => ends_in.map(|ends_in| ends_in + duration as i64 / 1000),
+            });
+        return Err(refusal(format!("'{}' is already in the queue", video_meta),
+                           ends_in.map(|ends_in| now + ends_in + cooldown)));
+    }
+    match last_played {
+        Some(last_played) if now - last_played < cooldown => {
+            Err(refusal(format!("'{}' was played {} minutes ago",
+                                video_meta,
+                                (now - last_played) / 60),
+                        Some(last_played + cooldown)))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+fn entry_by(requester: &str) -> QueueEntry {
+    // no .yaml next to it, so nothing is read
+    let video_meta = VideoMeta::new("song.mp4").unwrap();
+    QueueEntry::new(video_meta, Some(requester.to_string()))
+}
+
+#[test]
+fn test_fair_position() {
+    let mut queue = Playlist::new();
+    for requester in &["a", "a", "a", "b"] {
+        let entry = entry_by(requester);
+        let pos = fair_position(&queue, &entry);
+        queue.insert(pos, entry);
+    }
+    // b doesn't wait for every song of a
+    let order: Vec<_> = queue.iter().map(|e| e.added_by.clone().unwrap()).collect();
+    assert_eq!(order, vec!["a", "b", "a", "a"]);
+    assert_eq!(fair_position(&queue, &entry_by("c")), 2);
+    assert_eq!(fair_position(&queue, &entry_by("b")), 3);
+    assert_eq!(fair_position(&queue, &entry_by("a")), 4);
+    let mut from_player = entry_by("c");
+    from_player.added_by = None;
+    assert_eq!(fair_position(&queue, &from_player), 2);
+    // the turns of the guests go by address, whatever their nickname
+    let guest: IpAddr = "192.168.1.10".parse().unwrap();
+    let mut queue = Playlist::new();
+    for requester in &["x", "y", "z"] {
+        let entry = QueueEntry { requested_from: Some(guest), ..entry_by(requester) };
+        let pos = fair_position(&queue, &entry);
+        queue.insert(pos, entry);
+    }
+    assert_eq!(fair_position(&queue, &entry_by("w")), 1);
+}
+
+#[test]
+fn test_before_auto_dj() {
+    let mut queue = Playlist::new();
+    queue.push_back(entry_by("a"));
+    assert_eq!(before_auto_dj(&queue, 1), 1);
+    queue.push_back(QueueEntry::auto_dj(VideoMeta::new("auto.mp4").unwrap()));
+    assert_eq!(before_auto_dj(&queue, 2), 1);
+    assert_eq!(before_auto_dj(&queue, 0), 0);
+}
+
+#[test]
+fn test_check_cooldown() {
+    let song = VideoMeta::new("song.mp4").unwrap();
+    let queue = Playlist::new();
+    assert!(check_cooldown(&song, None, &queue, Some(0), 60, 10000).is_ok());
+    assert!(check_cooldown(&song, Some(10000 - 3600), &queue, Some(0), 60, 10000).is_ok());
+    let refusal = check_cooldown(&song, Some(10000 - 600), &queue, None, 60, 10000).unwrap_err();
+    assert_eq!(refusal.available_at, Some(10000 + 3000));
+    assert!(refusal.reason.starts_with("'song' was played 10 minutes ago"));
+    let mut queue = Playlist::new();
+    let mut other = entry_by("a");
+    other.video_meta = VideoMeta::new("other.mp4").unwrap();
+    other.video_meta.video_duration = 200000;
+    queue.push_back(other);
+    queue.push_back(entry_by("b"));
+    queue[1].video_meta.video_duration = 100000;
+    let refusal = check_cooldown(&song, None, &queue, Some(0), 60, 10000).unwrap_err();
+    assert_eq!(refusal.available_at, Some(10000 + 300 + 3600));
+    // the song being played ends in 2 minutes
+    let refusal = check_cooldown(&song, None, &queue, Some(120), 60, 10000).unwrap_err();
+    assert_eq!(refusal.available_at, Some(10000 + 120 + 300 + 3600));
+    let refusal = check_cooldown(&song, None, &queue, None, 60, 10000).unwrap_err();
+    assert_eq!(refusal.available_at, None);
+    // the duration of the first song isn't known
+    queue[0].video_meta.video_duration = 0;
+    let refusal = check_cooldown(&song, None, &queue, Some(0), 60, 10000).unwrap_err();
+    assert_eq!(refusal.available_at, None);
+    assert!(refusal.reason.ends_with("it can be added again 60 minutes after it's played"));
+}
+
+#[test]
+fn test_request_quotas() {
+    let a: IpAddr = "192.168.1.10".parse().unwrap();
+    let b: IpAddr = "192.168.1.11".parse().unwrap();
+    let mut queue = Playlist::new();
+    // a new nickname doesn't give more songs
+    queue.push_back(QueueEntry { requested_from: Some(a), ..entry_by("a") });
+    queue.push_back(QueueEntry { requested_from: Some(a), ..entry_by("not a") });
+    let mut quotas = RequestQuotas::new(Some(3), None);
+    assert!(quotas.check(&queue, a, 1, 0).is_ok());
+    assert!(quotas.check(&queue, a, 2, 0).is_err());
+    assert!(quotas.check(&queue, b, 3, 0).is_ok());
+    let mut quotas = RequestQuotas::new(None, Some(2));
+    assert!(quotas.check(&queue, a, 2, 0).is_ok());
+    // refused by the player : nothing is counted
+    assert!(quotas.check(&queue, a, 2, 0).is_ok());
+    quotas.record(a, 2, 0);
+    assert!(quotas.check(&queue, a, 1, 1800).is_err());
+    assert!(quotas.check(&queue, b, 1, 1800).is_ok());
+    // an hour later, the first songs don't count anymore
+    assert!(quotas.check(&queue, a, 2, 3600).is_ok());
+}
+
+#[test]
+fn test_queue_moves() {
+    fn as_vec(queue: &VecDeque<QueueId>) -> Vec<QueueId> {
+        queue.iter().cloned().collect()
+    }
+    // ids and positions are mixed up on purpose
+    let mut queue: VecDeque<QueueId> = vec![10, 11, 12, 13, 14].into_iter().collect();
+    move_in_queue(&mut queue, 13, 0).unwrap();
+    assert_eq!(as_vec(&queue), vec![13, 10, 11, 12, 14]);
+    move_in_queue(&mut queue, 13, 4).unwrap();
+    assert_eq!(as_vec(&queue), vec![10, 11, 12, 14, 13]);
+    swap_in_queue(&mut queue, 14, 13).unwrap();
+    assert_eq!(as_vec(&queue), vec![10, 11, 12, 13, 14]);
+    reorder_queue(&mut queue, &[14, 12, 10, 11, 13]).unwrap();
+    assert_eq!(as_vec(&queue), vec![14, 12, 10, 11, 13]);
+    assert_eq!(remove_from_queue(&mut queue, 12), Ok(12));
+    assert_eq!(move_in_queue(&mut queue, 12, 0), Err(QueueError::UnknownId(12)));
+    assert_eq!(move_in_queue(&mut queue, 10, 4),
+               Err(QueueError::OutOfBounds { pos: 4, len: 4 }));
+    assert_eq!(swap_in_queue(&mut queue, 10, 12), Err(QueueError::UnknownId(12)));
+    assert_eq!(reorder_queue(&mut queue, &[14, 10, 11]), Err(QueueError::BadOrder));
+    assert_eq!(reorder_queue(&mut queue, &[14, 10, 11, 11]), Err(QueueError::BadOrder));
+    assert_eq!(as_vec(&queue), vec![14, 10, 11, 13]);
+}
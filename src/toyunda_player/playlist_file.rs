@@ -0,0 +1,333 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// None if the file isn't a playlist
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<PlaylistFormat> {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("m3u8") | Some("m3u") => Some(PlaylistFormat::M3u8),
+            Some("xspf") => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<PlaylistFormat, String> {
+        match name {
+            "m3u8" | "m3u" => Ok(PlaylistFormat::M3u8),
+            "xspf" => Ok(PlaylistFormat::Xspf),
+            _ => Err(format!("Unknown playlist format '{}'", name)),
+        }
+    }
+
+    /// for content sent without a file name
+    pub fn detect(content: &str) -> PlaylistFormat {
+        if content.trim_left_matches('\u{feff}').trim_left().starts_with('<') {
+            PlaylistFormat::Xspf
+        } else {
+            PlaylistFormat::M3u8
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match *self {
+            PlaylistFormat::M3u8 => "audio/x-mpegurl; charset=utf-8",
+            PlaylistFormat::Xspf => "application/xspf+xml; charset=utf-8",
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            ::std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// an absolute path becomes a file:// URL, a relative one a relative URL
+fn path_to_location(path: &Path) -> String {
+    let path = percent_encode(&path.to_string_lossy());
+    if path.starts_with('/') {
+        format!("file://{}", path)
+    } else {
+        path
+    }
+}
+
+/// relative paths are relative to the playlist; the locations of xspf are
+/// always URLs, the ones of m3u8 only when they start with file://
+fn location_to_path(location: &str, is_url: bool, base_directory: &Path) -> PathBuf {
+    let path = if location.starts_with("file://") {
+        PathBuf::from(percent_decode(&location["file://".len()..]))
+    } else if is_url {
+        PathBuf::from(percent_decode(location))
+    } else {
+        PathBuf::from(location)
+    };
+    base_directory.join(path)
+}
+
+fn export_m3u8(songs: &[VideoMeta]) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    for video_meta in songs {
+        let duration: i64 = match video_meta.video_duration {
+            0 => -1,
+            duration => (duration / 1000) as i64,
+        };
+        playlist.push_str(&format!("#EXTINF:{},{}\n{}\n",
+                                   duration,
+                                   video_meta,
+                                   video_meta.video_path.display()));
+    }
+    playlist
+}
+
+fn export_xspf(songs: &[VideoMeta]) -> String {
+    let mut playlist = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                                     <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n\
+                                     \t<trackList>\n");
+    for video_meta in songs {
+        playlist.push_str("\t\t<track>\n");
+        playlist.push_str(&format!("\t\t\t<location>{}</location>\n",
+                                   escape_xml(&path_to_location(&video_meta.video_path))));
+        playlist.push_str(&format!("\t\t\t<title>{}</title>\n",
+                                   escape_xml(&video_meta.to_string())));
+        if let Some(ref artist) = video_meta.song_info.artist {
+            playlist.push_str(&format!("\t\t\t<creator>{}</creator>\n", escape_xml(artist)));
+        }
+        if video_meta.video_duration > 0 {
+            playlist.push_str(&format!("\t\t\t<duration>{}</duration>\n",
+                                       video_meta.video_duration));
+        }
+        playlist.push_str("\t\t</track>\n");
+    }
+    playlist.push_str("\t</trackList>\n</playlist>\n");
+    playlist
+}
+
+pub fn export(songs: &[VideoMeta], format: PlaylistFormat) -> String {
+    match format {
+        PlaylistFormat::M3u8 => export_m3u8(songs),
+        PlaylistFormat::Xspf => export_xspf(songs),
+    }
+}
+
+fn m3u8_locations(content: &str) -> Vec<String> {
+    content.lines()
+        .map(|line| line.trim_matches('\u{feff}').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn xspf_locations(content: &str) -> Vec<String> {
+    let mut locations = vec![];
+    let mut rest = content;
+    while let Some(start) = rest.find("<location>") {
+        rest = &rest[start + "<location>".len()..];
+        match rest.find("</location>") {
+            Some(end) => {
+                locations.push(unescape_xml(rest[..end].trim()));
+                rest = &rest[end..];
+            }
+            None => break,
+        }
+    }
+    locations
+}
+
+/// The songs of the playlist, found like the video files given on the
+/// command line are, and why the other entries couldn't be used
+pub fn import(content: &str,
+              format: PlaylistFormat,
+              base_directory: &Path)
+              -> (Vec<VideoMeta>, Vec<String>) {
+    let locations = match format {
+        PlaylistFormat::M3u8 => m3u8_locations(content),
+        PlaylistFormat::Xspf => xspf_locations(content),
+    };
+    let mut songs: Vec<VideoMeta> = vec![];
+    let mut errors: Vec<String> = vec![];
+    for location in locations {
+        let path = location_to_path(&location, format == PlaylistFormat::Xspf, base_directory);
+        match VideoMeta::new(path) {
+            Ok(video_meta) => songs.push(video_meta),
+            Err(e) => errors.push(format!("Skipping '{}' : {}", location, e)),
+        }
+    }
+    (songs, errors)
+}
+
+pub fn import_file<P: AsRef<Path>>(path: P) -> Result<(Vec<VideoMeta>, Vec<String>), String> {
+    let path = path.as_ref();
+    let format = try!(PlaylistFormat::from_path(path)
+        .ok_or(format!("'{}' is not a m3u8 or xspf playlist", path.display())));
+    let mut content = String::new();
+    try!(File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| format!("Can't read playlist '{}' : {}", path.display(), e)));
+    let base_directory = path.parent().unwrap_or(Path::new(""));
+    Ok(import(&content, format, base_directory))
+}
+
+/// The named playlists of the manager, saved as m3u8 files in `directory`
+#[derive(Debug,Clone)]
+pub struct PlaylistStore {
+    directory: PathBuf,
+}
+
+impl PlaylistStore {
+    pub fn new<P: Into<PathBuf>>(directory: P) -> PlaylistStore {
+        PlaylistStore { directory: directory.into() }
+    }
+
+    /// where relative paths of the imported playlists start from
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// the name is part of a file name, so not everything goes
+    fn path_of(&self, name: &str) -> Result<PathBuf, String> {
+        let valid = !name.trim().is_empty() && name.chars().count() <= 64 &&
+                    name.chars().all(|c| c.is_alphanumeric() || " -_()',!&".contains(c));
+        if valid {
+            Ok(self.directory.join(format!("{}.m3u8", name)))
+        } else {
+            Err(format!("Invalid playlist name '{}'", name))
+        }
+    }
+
+    /// sorted, empty if nothing was saved yet
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = match fs::read_dir(&self.directory) {
+            Ok(entries) => {
+                entries.filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().map_or(false, |e| e == "m3u8"))
+                    .filter_map(|path| {
+                        path.file_stem().and_then(|stem| stem.to_str()).map(String::from)
+                    })
+                    .collect()
+            }
+            Err(_) => vec![],
+        };
+        names.sort();
+        names
+    }
+
+    pub fn save(&self, name: &str, songs: &[VideoMeta]) -> Result<(), String> {
+        let path = try!(self.path_of(name));
+        try!(fs::create_dir_all(&self.directory).map_err(|e| format!("{}", e)));
+        File::create(&path)
+            .and_then(|mut file| file.write_all(export(songs, PlaylistFormat::M3u8).as_bytes()))
+            .map_err(|e| format!("Can't write playlist '{}' : {}", path.display(), e))
+    }
+
+    pub fn load(&self, name: &str) -> Result<(Vec<VideoMeta>, Vec<String>), String> {
+        let path = try!(self.path_of(name));
+        if !path.is_file() {
+            return Err(format!("There is no playlist '{}'", name));
+        }
+        import_file(path)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let path = try!(self.path_of(name));
+        fs::remove_file(&path).map_err(|_| format!("There is no playlist '{}'", name))
+    }
+}
+
+#[test]
+fn test_playlist_formats() {
+    let temp_dir = ::utils::TempDir::new("playlist_test");
+    let dir = temp_dir.path();
+    // the yaml next to the video is found, like on the command line
+    File::create(dir.join("with yaml.mp4")).unwrap();
+    File::create(dir.join("with yaml.yaml"))
+        .unwrap()
+        .write_all(b"video_path: with yaml.mp4\nsong_info:\n  artist: Someone & co\n")
+        .unwrap();
+    let songs = vec![VideoMeta::new(dir.join("with yaml.mp4")).unwrap(),
+                     VideoMeta::new(dir.join("Opening #1 (100%).webm")).unwrap()];
+    assert_eq!(songs[0].song_info.artist, Some(String::from("Someone & co")));
+    for format in vec![PlaylistFormat::M3u8, PlaylistFormat::Xspf] {
+        let content = export(&songs, format);
+        assert_eq!(PlaylistFormat::detect(&content), format);
+        let (imported, errors) = import(&content, format, Path::new("/elsewhere"));
+        assert!(errors.is_empty());
+        let paths: Vec<&PathBuf> = imported.iter().map(|v| &v.video_path).collect();
+        assert_eq!(paths, vec![&songs[0].video_path, &songs[1].video_path]);
+        assert_eq!(imported[0].song_info.artist, songs[0].song_info.artist);
+    }
+    // relative to the playlist, and unknown files are reported
+    let (imported, errors) = import("#EXTM3U\nsong.mp4\nnotes.txt\n",
+                                    PlaylistFormat::M3u8,
+                                    Path::new("/karaoke"));
+    assert_eq!(imported[0].video_path, PathBuf::from("/karaoke/song.mp4"));
+    assert_eq!(errors.len(), 1);
+
+    let store = PlaylistStore::new(dir.join("playlists"));
+    assert!(store.names().is_empty());
+    store.save("90s openings", &songs).unwrap();
+    assert!(store.save("../escape", &songs).is_err());
+    assert_eq!(store.names(), vec![String::from("90s openings")]);
+    let (loaded, _) = store.load("90s openings").unwrap();
+    assert_eq!(loaded.len(), 2);
+    store.delete("90s openings").unwrap();
+    assert!(store.load("90s openings").is_err());
+}
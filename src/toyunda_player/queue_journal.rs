@@ -0,0 +1,158 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use ::toyunda_player::playlist::{Playlist, QueueEntry};
+use ::toyunda_player::playing_state::PlayingState;
+use ::subtitles::Subtitles;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use serde_json;
+
+/// how long before the sentence the song resumes, so it's on screen before
+/// it has to be sung
+const RESUME_LEAD_MS: u32 = 1000;
+
+/// a song as written in the journal
+#[derive(Debug,Serialize,Deserialize)]
+pub struct JournalEntry {
+    pub added_by: Option<String>,
+    /// UNIX TIMESTAMP
+    pub added_at: i64,
+    /// not part of a serialized VideoMeta
+    pub yaml_path: Option<PathBuf>,
+    pub video_meta: VideoMeta,
+    #[serde(default)]
+    pub auto_dj: bool,
+}
+
+/// What was playing and what was queued, written every time the queue
+/// changes so a crash or an unexpected quit doesn't lose it
+#[derive(Debug,Serialize,Deserialize)]
+pub struct QueueJournal {
+    /// the song being played, and where it was in ms
+    pub playing: Option<(JournalEntry, u32)>,
+    pub queue: Vec<JournalEntry>,
+}
+
+impl JournalEntry {
+    fn new(video_meta: &VideoMeta, added_by: Option<String>, added_at: i64) -> JournalEntry {
+        JournalEntry {
+            added_by: added_by,
+            added_at: added_at,
+            yaml_path: video_meta.yaml_path.clone(),
+            video_meta: video_meta.clone(),
+            auto_dj: false,
+        }
+    }
+
+    /// the queue ids are given again, the clients know about none of them
+    fn into_queue_entry(self) -> QueueEntry {
+        let mut video_meta = self.video_meta;
+        video_meta.yaml_path = self.yaml_path;
+        let mut queue_entry = QueueEntry::new(video_meta, self.added_by);
+        queue_entry.added_at = self.added_at;
+        queue_entry.auto_dj = self.auto_dj;
+        queue_entry
+    }
+}
+
+impl QueueJournal {
+    /// `position` is where the song being played is, in ms
+    pub fn new(playing_state: &PlayingState, position: u32, playlist: &Playlist) -> QueueJournal {
+        QueueJournal {
+            playing: match *playing_state {
+                PlayingState::Playing(ref video_meta) => {
+                    Some((JournalEntry::new(video_meta, None, 0), position))
+                }
+                PlayingState::Idle => None,
+            },
+            queue: playlist.iter()
+                .map(|e| JournalEntry {
+                    auto_dj: e.auto_dj,
+                    ..JournalEntry::new(&e.video_meta, e.added_by.clone(), e.added_at)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<QueueJournal, String> {
+        let path = path.as_ref();
+        let file = try!(File::open(path)
+            .map_err(|e| format!("Can't open queue journal '{}' : {}", path.display(), e)));
+        serde_json::from_reader(file)
+            .map_err(|e| format!("Invalid queue journal '{}' : {}", path.display(), e))
+    }
+
+    /// written somewhere else first, so a crash never leaves half a journal
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        let file = try!(File::create(&tmp_path).map_err(|e| format!("{}", e)));
+        try!(serde_json::to_writer(file, self).map_err(|e| format!("{}", e)));
+        fs::rename(&tmp_path, path).map_err(|e| format!("{}", e))
+    }
+
+    /// the queue to play again, the interrupted song first, and where to
+    /// resume it
+    pub fn into_queue(self) -> (Playlist, Option<u32>) {
+        let mut playlist = Playlist::new();
+        let mut position = None;
+        if let Some((entry, entry_position)) = self.playing {
+            playlist.push_back(entry.into_queue_entry());
+            position = Some(entry_position);
+        }
+        playlist.extend(self.queue.into_iter().map(JournalEntry::into_queue_entry));
+        (playlist, position)
+    }
+}
+
+/// a little before the beginning of the sentence sung at `position`, so
+/// nobody has to catch up in the middle of it
+pub fn sentence_start(subtitles: &Subtitles, position: u32) -> u32 {
+    subtitles.sentences
+        .iter()
+        .filter_map(|sentence| sentence.syllables.first().map(|syllable| syllable.begin))
+        .filter(|begin| *begin <= position)
+        .max()
+        .map(|begin| begin.saturating_sub(RESUME_LEAD_MS))
+        .unwrap_or(position)
+}
+
+#[test]
+fn test_queue_journal() {
+    use ::subtitles::{Sentence, Syllable};
+    use ::subtitles::pos::RowPosition;
+    let mut playing = VideoMeta::new("playing.mp4").unwrap();
+    playing.yaml_path = Some(PathBuf::from("playing.yaml"));
+    let mut playlist = Playlist::new();
+    playlist.push_back(QueueEntry::new(VideoMeta::new("next.mp4").unwrap(),
+                                       Some(String::from("guest"))));
+    let journal = QueueJournal::new(&PlayingState::Playing(playing), 42000, &playlist);
+    let dir = ::utils::TempDir::new("queue_journal_test");
+    let path = dir.path().join("queue.json");
+    journal.save(&path).unwrap();
+    let (restored, position) = QueueJournal::open(&path).unwrap().into_queue();
+    assert_eq!(position, Some(42000));
+    let paths: Vec<PathBuf> = restored.iter().map(|e| e.video_meta.video_path.clone()).collect();
+    assert_eq!(paths, vec![PathBuf::from("playing.mp4"), PathBuf::from("next.mp4")]);
+    assert_eq!(restored[0].video_meta.yaml_path, Some(PathBuf::from("playing.yaml")));
+    assert_eq!(restored[1].added_by, Some(String::from("guest")));
+    assert_eq!(restored[1].added_at, playlist[0].added_at);
+
+    let mut subtitles = Subtitles::default();
+    for &(begin, end) in &[(10000, 14000), (15000, 19000)] {
+        subtitles.sentences.push(Sentence {
+            syllables: vec![Syllable {
+                                text: String::from("la"),
+                                begin: begin,
+                                end: Some(end),
+                                syllable_options: None,
+                                pitch: None,
+                            }],
+            position: RowPosition::default(),
+            sentence_options: None,
+        });
+    }
+    assert_eq!(sentence_start(&subtitles, 17500), 14000);
+    assert_eq!(sentence_start(&subtitles, 14500), 9000);
+    // still in the intro
+    assert_eq!(sentence_start(&subtitles, 5000), 5000);
+}
@@ -0,0 +1,188 @@
+use ::toyunda_player::video_meta::VideoMeta;
+use std::cmp::min;
+
+/// lowercase, without accents
+fn fold_char(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ä' | 'ã' | 'å' | 'ā' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'ö' | 'õ' | 'ø' | 'ō' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' => 'u',
+        'ý' | 'ÿ' => 'y',
+        c => c,
+    }
+}
+
+/// "Kyōjin", "Kyoujin", "Kyoojin" and "kyojin" all become "kyojin" : long
+/// vowels are written in too many ways in romanized japanese, so they are
+/// all written as short ones
+fn fold_long_vowels(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    let mut previous: Option<char> = None;
+    for c in word.chars() {
+        let long_vowel = match (previous, c) {
+            (Some('o'), 'u') | (Some('o'), 'o') | (Some('u'), 'u') | (Some('a'), 'a') |
+            (Some('e'), 'e') | (Some('i'), 'i') => true,
+            _ => false,
+        };
+        if !long_vowel {
+            folded.push(c);
+        }
+        previous = Some(c);
+    }
+    folded
+}
+
+/// the words of a text, as they are compared
+pub fn normalize(text: &str) -> Vec<String> {
+    let folded: String = text.chars()
+        .flat_map(|c| c.to_lowercase())
+        .map(|c| if c.is_alphanumeric() { fold_char(c) } else { ' ' })
+        .collect();
+    folded.split_whitespace().map(fold_long_vowels).collect()
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_row[j] + if a_char == *b_char { 0 } else { 1 };
+            row.push(min(substitution, min(previous_row[j + 1], row[j]) + 1));
+        }
+        previous_row = row;
+    }
+    previous_row[b.len()]
+}
+
+/// how many typos are forgiven : none for short words, they would match
+/// about everything
+fn max_typos(word: &str) -> usize {
+    match word.chars().count() {
+        0...3 => 0,
+        4...6 => 1,
+        _ => 2,
+    }
+}
+
+/// lower is better, None if the word of the query doesn't match this word
+fn word_score(query_word: &str, word: &str) -> Option<usize> {
+    if word == query_word {
+        Some(0)
+    } else if word.starts_with(query_word) {
+        // the user is still typing
+        Some(1)
+    } else {
+        let distance = edit_distance(query_word, word);
+        if distance <= max_typos(query_word) {
+            Some(1 + distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// Words of the titles, alt titles and artists of every song, built once
+/// when the yaml files are loaded
+#[derive(Debug,Default)]
+pub struct SearchIndex {
+    songs_words: Vec<Vec<String>>,
+}
+
+impl SearchIndex {
+    /// None for the songs removed from the library, they never match
+    pub fn new<'a, I>(songs: I) -> SearchIndex
+        where I: IntoIterator<Item = Option<&'a VideoMeta>>
+    {
+        let songs_words = songs.into_iter()
+            .map(|video_meta| {
+                let video_meta = match video_meta {
+                    Some(video_meta) => video_meta,
+                    None => return vec![],
+                };
+                let song_info = &video_meta.song_info;
+                let mut words: Vec<String> = vec![];
+                for text in [&song_info.artist, &song_info.media_title, &song_info.song_name]
+                    .iter() {
+                    if let Some(ref text) = **text {
+                        words.extend(normalize(text));
+                    }
+                }
+                if let Some(ref alt_titles) = song_info.media_alt_titles {
+                    for alt_title in alt_titles {
+                        words.extend(normalize(alt_title));
+                    }
+                }
+                words.sort();
+                words.dedup();
+                words
+            })
+            .collect();
+        SearchIndex { songs_words: songs_words }
+    }
+
+    /// the index of every song matching every word of the query, best
+    /// matches first
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let query_words = normalize(query);
+        let mut results: Vec<(usize, usize)> = self.songs_words
+            .iter()
+            .enumerate()
+            .filter_map(|(id, words)| {
+                let mut score = 0;
+                for query_word in &query_words {
+                    match words.iter().filter_map(|w| word_score(query_word, w)).min() {
+                        Some(word_score) => score += word_score,
+                        None => return None,
+                    }
+                }
+                Some((score, id))
+            })
+            .collect();
+        results.sort();
+        results.into_iter().map(|(_, id)| id).collect()
+    }
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(normalize("Shingeki no Kyōjin"), vec!["shingeki", "no", "kyojin"]);
+    assert_eq!(normalize("KYOUJIN"), vec!["kyojin"]);
+    assert_eq!(normalize("Pokémon: Évolutions"), vec!["pokemon", "evolutions"]);
+    assert_eq!(normalize("Yūki Aoi"), normalize("yuuki aoi"));
+    assert_eq!(edit_distance("kyojin", "kyoujin"), 1);
+    assert_eq!(edit_distance("bebop", "bebop"), 0);
+    assert_eq!(edit_distance("", "abc"), 3);
+}
+
+#[test]
+fn test_search_index() {
+    let songs: Vec<VideoMeta> = vec![("Shingeki no Kyojin", "Linked Horizon"),
+                                     ("Cowboy Bebop", "Seatbelts"),
+                                     ("Kyōjin no Hoshi", "Isao Sasaki"),
+                                     ("Horizont", "Someone")]
+        .into_iter()
+        .map(|(title, artist)| {
+            let mut video_meta = VideoMeta::test_song(title);
+            video_meta.song_info.artist = Some(artist.to_string());
+            video_meta
+        })
+        .collect();
+    let index = SearchIndex::new(songs.iter().map(Some));
+    assert_eq!(index.search("kyoujin"), vec![0, 2]);
+    assert_eq!(index.search("shingeki kyoujin"), vec![0]);
+    // a typo
+    assert_eq!(index.search("cowbpy"), vec![1]);
+    // the end of the word is still being typed
+    assert_eq!(index.search("seat"), vec![1]);
+    // the exact match comes first
+    assert_eq!(index.search("horizon"), vec![0, 3]);
+    assert_eq!(index.search("horizont"), vec![3, 0]);
+    assert_eq!(index.search("sasaki isao"), vec![2]);
+    assert!(index.search("zzz").is_empty());
+}
@@ -1,38 +1,265 @@
 use clap::ArgMatches;
+use log::LogLevelFilter;
+use serde::{Serialize, Serializer};
+use serde_yaml;
+use std::fs::File;
+use std::io::Read;
+use std::collections::BTreeMap;
 use std::net::{IpAddr, Ipv4Addr};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::env;
 use super::ToyundaMode;
 
-#[derive(Debug)]
+/// What the command line and the config files can set. Everything is
+/// optional, so the layers can be merged before the defaults are applied.
+///
+/// The keys of the config files are the names of the fields, or the long
+/// name of the flag when they differ.
+#[derive(Debug,Default,Deserialize)]
+#[serde(default,deny_unknown_fields)]
 pub struct StartupOptions {
+    #[serde(rename = "listen_address")]
     pub manager_listen_address: Option<IpAddr>,
+    #[serde(rename = "port")]
     pub manager_listen_port: Option<u16>,
     pub volume: Option<f64>,
     pub quit: Option<bool>,
+    /// only given on the command line
+    #[serde(skip_deserializing)]
     pub video_files: Vec<PathBuf>,
     pub songs_history: Option<PathBuf>,
+    #[serde(rename = "directories")]
     pub lookup_directories: Vec<PathBuf>,
     pub fullscreen: bool,
     pub mode: Option<ToyundaMode>,
     pub no_manager: bool,
+    pub operator_password: Option<String>,
+    pub guest_password: Option<String>,
+    pub max_songs_in_queue: Option<usize>,
+    pub max_songs_per_hour: Option<usize>,
+    pub fair_queue: bool,
+    /// in minutes, how long before a song can be added again after it was
+    /// played
+    pub replay_cooldown: Option<u32>,
+    #[serde(rename = "watch")]
+    pub watch_directories: bool,
+    pub library_cache: Option<PathBuf>,
+    pub queue_journal: Option<PathBuf>,
+    pub restore_queue: bool,
+    /// where the named playlists of the manager are saved
+    pub playlists_directory: Option<PathBuf>,
+    /// fills the queue with random songs when it runs dry, in karaoke mode
+    pub auto_dj: bool,
+    /// in seconds, how long the next song is announced before it starts in
+    /// karaoke mode
+    pub intermission_duration: Option<u32>,
+    /// shown between the songs so guests know where to add theirs, guessed
+    /// from the listen address by default
+    pub manager_url: Option<String>,
+    // the ones below can only be set by a config file
+    /// mpv's `aid`; 2 is the normalized audio track when there is one
+    pub audio_track: Option<i64>,
+    /// mpv's `softvol-max`, in percents
+    pub max_volume: Option<f64>,
+    /// in seconds
+    pub announcement_duration: Option<u32>,
+    /// used when the subtitles don't set them, in ms
+    pub fade_time_before: Option<u16>,
+    pub fade_time_after: Option<u16>,
+    /// what is printed on stdout, "warn" by default
+    pub log_level: Option<String>,
+    /// what is written in toyunda.log, "info" by default
+    pub log_file_level: Option<String>,
+    /// the songs played since aren't picked by the auto-DJ
+    pub auto_dj_recent_minutes: Option<u32>,
+    /// weights of the languages and music types for the auto-DJ, spelled as
+    /// in the yaml files; 1.0 for the ones not listed, 0 to never pick them
+    pub auto_dj_languages: Option<BTreeMap<String, f64>>,
+    pub auto_dj_music_types: Option<BTreeMap<String, f64>>,
+    pub auto_dj_only_valid_subtitles: bool,
 }
 
-#[derive(Debug)]
+/// The options once the defaults are applied, what is printed by
+/// `--print-config`
+#[derive(Debug,Serialize)]
 pub struct StartupParameters {
+    #[serde(rename = "listen_address")]
     pub manager_listen_address: IpAddr,
+    #[serde(rename = "port")]
     pub manager_listen_port: u16,
     pub volume: Option<f64>,
     pub quit: Option<bool>,
+    #[serde(skip_serializing)]
     pub video_files: Vec<PathBuf>,
     pub songs_history: Option<PathBuf>,
+    #[serde(rename = "directories")]
     pub lookup_directories: Vec<PathBuf>,
     pub fullscreen: bool,
     pub mode: ToyundaMode,
     pub no_manager: bool,
+    #[serde(serialize_with = "serialize_password")]
+    pub operator_password: Option<String>,
+    #[serde(serialize_with = "serialize_password")]
+    pub guest_password: Option<String>,
+    pub max_songs_in_queue: Option<usize>,
+    pub max_songs_per_hour: Option<usize>,
+    pub fair_queue: bool,
+    pub replay_cooldown: Option<u32>,
+    #[serde(rename = "watch")]
+    pub watch_directories: bool,
+    pub library_cache: Option<PathBuf>,
+    pub queue_journal: PathBuf,
+    pub restore_queue: bool,
+    pub playlists_directory: PathBuf,
+    pub auto_dj: bool,
+    pub intermission_duration: u32,
+    pub manager_url: Option<String>,
+    pub audio_track: i64,
+    pub max_volume: f64,
+    pub announcement_duration: u32,
+    pub fade_time_before: u16,
+    pub fade_time_after: u16,
+    pub log_level: String,
+    pub log_file_level: String,
+    pub auto_dj_recent_minutes: u32,
+    pub auto_dj_languages: BTreeMap<String, f64>,
+    pub auto_dj_music_types: BTreeMap<String, f64>,
+    pub auto_dj_only_valid_subtitles: bool,
+}
+
+/// the passwords aren't shown when the config is printed
+fn serialize_password<S: Serializer>(password: &Option<String>,
+                                     serializer: S)
+                                     -> Result<S::Ok, S::Error> {
+    password.as_ref().map(|_| "********").serialize(serializer)
+}
+
+fn check_log_level(level: &Option<String>) -> Result<(), String> {
+    match *level {
+        Some(ref level) => {
+            level.parse::<LogLevelFilter>()
+                .map(|_| ())
+                .map_err(|_| format!("Unknown log level '{}'", level))
+        }
+        None => Ok(()),
+    }
+}
+
+fn check_weights(weights: &Option<BTreeMap<String, f64>>) -> Result<(), String> {
+    match weights.as_ref().and_then(|w| w.iter().find(|&(_, weight)| !(*weight >= 0.0))) {
+        Some((name, weight)) => Err(format!("The weight of '{}' can't be {}", name, weight)),
+        None => Ok(()),
+    }
+}
+
+/// `toyunda.yaml` next to the executable, then the config of the user, which
+/// wins over it
+fn default_config_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = vec![];
+    if let Ok(exe) = env::current_exe() {
+        files.push(exe.with_file_name("toyunda.yaml"));
+    }
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    };
+    if let Some(config_home) = config_home {
+        files.push(config_home.join("toyunda-player").join("config.yaml"));
+    }
+    files
 }
 
 impl StartupOptions {
-    pub fn from_args<'a>(arg_matches: ArgMatches<'a>) -> Result<StartupOptions, String> {
+    pub fn from_config_str(config: &str) -> Result<StartupOptions, String> {
+        // an empty yaml document isn't an empty map for serde_yaml
+        if config.trim().is_empty() {
+            return Ok(StartupOptions::default());
+        }
+        let options: StartupOptions = try!(serde_yaml::from_str(config)
+            .map_err(|e| format!("{}", e)));
+        try!(check_log_level(&options.log_level));
+        try!(check_log_level(&options.log_file_level));
+        try!(check_weights(&options.auto_dj_languages));
+        try!(check_weights(&options.auto_dj_music_types));
+        Ok(options)
+    }
+
+    pub fn from_config_file<P: AsRef<Path>>(path: P) -> Result<StartupOptions, String> {
+        let path = path.as_ref();
+        let mut config = String::new();
+        try!(File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut config))
+            .map_err(|e| format!("Can't read config file '{}' : {}", path.display(), e)));
+        Self::from_config_str(&config)
+            .map_err(|e| format!("Invalid config file '{}' : {}", path.display(), e))
+    }
+
+    /// what isn't set by `self` is taken from `other`
+    pub fn or(self, other: StartupOptions) -> StartupOptions {
+        StartupOptions {
+            manager_listen_address: self.manager_listen_address.or(other.manager_listen_address),
+            manager_listen_port: self.manager_listen_port.or(other.manager_listen_port),
+            volume: self.volume.or(other.volume),
+            quit: self.quit.or(other.quit),
+            video_files: if self.video_files.is_empty() {
+                other.video_files
+            } else {
+                self.video_files
+            },
+            songs_history: self.songs_history.or(other.songs_history),
+            lookup_directories: if self.lookup_directories.is_empty() {
+                other.lookup_directories
+            } else {
+                self.lookup_directories
+            },
+            fullscreen: self.fullscreen || other.fullscreen,
+            mode: self.mode.or(other.mode),
+            no_manager: self.no_manager || other.no_manager,
+            operator_password: self.operator_password.or(other.operator_password),
+            guest_password: self.guest_password.or(other.guest_password),
+            max_songs_in_queue: self.max_songs_in_queue.or(other.max_songs_in_queue),
+            max_songs_per_hour: self.max_songs_per_hour.or(other.max_songs_per_hour),
+            fair_queue: self.fair_queue || other.fair_queue,
+            replay_cooldown: self.replay_cooldown.or(other.replay_cooldown),
+            watch_directories: self.watch_directories || other.watch_directories,
+            library_cache: self.library_cache.or(other.library_cache),
+            queue_journal: self.queue_journal.or(other.queue_journal),
+            restore_queue: self.restore_queue || other.restore_queue,
+            playlists_directory: self.playlists_directory.or(other.playlists_directory),
+            auto_dj: self.auto_dj || other.auto_dj,
+            intermission_duration: self.intermission_duration.or(other.intermission_duration),
+            manager_url: self.manager_url.or(other.manager_url),
+            audio_track: self.audio_track.or(other.audio_track),
+            max_volume: self.max_volume.or(other.max_volume),
+            announcement_duration: self.announcement_duration.or(other.announcement_duration),
+            fade_time_before: self.fade_time_before.or(other.fade_time_before),
+            fade_time_after: self.fade_time_after.or(other.fade_time_after),
+            log_level: self.log_level.or(other.log_level),
+            log_file_level: self.log_file_level.or(other.log_file_level),
+            auto_dj_recent_minutes: self.auto_dj_recent_minutes.or(other.auto_dj_recent_minutes),
+            auto_dj_languages: self.auto_dj_languages.or(other.auto_dj_languages),
+            auto_dj_music_types: self.auto_dj_music_types.or(other.auto_dj_music_types),
+            auto_dj_only_valid_subtitles: self.auto_dj_only_valid_subtitles ||
+                                          other.auto_dj_only_valid_subtitles,
+        }
+    }
+
+    /// Fills what the command line didn't set with the config files. Only
+    /// `config_file` is read if given, and it must exist then; the default
+    /// ones are skipped when absent.
+    pub fn with_config_files(self, config_file: Option<&Path>) -> Result<StartupOptions, String> {
+        let files: Vec<PathBuf> = match config_file {
+            Some(config_file) => vec![config_file.to_path_buf()],
+            None => default_config_files().into_iter().filter(|f| f.is_file()).collect(),
+        };
+        let mut options = self;
+        for file in files.iter().rev() {
+            options = options.or(try!(Self::from_config_file(file)));
+        }
+        Ok(options)
+    }
+
+    pub fn from_args<'a>(arg_matches: &ArgMatches<'a>) -> Result<StartupOptions, String> {
         Ok(StartupOptions {
             manager_listen_address: match arg_matches.value_of("manager_listen_address") {
                 Some(listen_address_str) => {
@@ -76,10 +303,52 @@ impl StartupOptions {
                 _ => None,
             },
             no_manager: arg_matches.is_present("no_manager"),
+            // passwords given on the command line can be seen with ps
+            operator_password: arg_matches.value_of("operator_password")
+                .map(|s| s.to_string())
+                .or_else(|| env::var("TOYUNDA_OPERATOR_PASSWORD").ok()),
+            guest_password: arg_matches.value_of("guest_password")
+                .map(|s| s.to_string())
+                .or_else(|| env::var("TOYUNDA_GUEST_PASSWORD").ok()),
+            max_songs_in_queue: match arg_matches.value_of("max_songs_in_queue") {
+                Some(max_str) => Some(try!(max_str.parse::<usize>().map_err(|e| format!("{}", e)))),
+                None => None,
+            },
+            max_songs_per_hour: match arg_matches.value_of("max_songs_per_hour") {
+                Some(max_str) => Some(try!(max_str.parse::<usize>().map_err(|e| format!("{}", e)))),
+                None => None,
+            },
+            fair_queue: arg_matches.is_present("fair_queue"),
+            replay_cooldown: match arg_matches.value_of("replay_cooldown") {
+                Some(minutes_str) => {
+                    Some(try!(minutes_str.parse::<u32>().map_err(|e| format!("{}", e))))
+                }
+                None => None,
+            },
+            watch_directories: arg_matches.is_present("watch_directories"),
+            library_cache: arg_matches.value_of("library_cache").map(|s| PathBuf::from(s)),
+            queue_journal: arg_matches.value_of("queue_journal").map(|s| PathBuf::from(s)),
+            restore_queue: arg_matches.is_present("restore_queue"),
+            playlists_directory: arg_matches.value_of("playlists_directory")
+                .map(|s| PathBuf::from(s)),
+            auto_dj: arg_matches.is_present("auto_dj"),
+            intermission_duration: match arg_matches.value_of("intermission_duration") {
+                Some(secs_str) => Some(try!(secs_str.parse::<u32>().map_err(|e| format!("{}", e)))),
+                None => None,
+            },
+            manager_url: arg_matches.value_of("manager_url").map(|s| s.to_string()),
+            ..StartupOptions::default()
         })
     }
 
     pub fn to_params(self) -> StartupParameters {
+        // kept next to the songs history by default
+        let library_cache = match (self.library_cache, self.songs_history.as_ref()) {
+            (Some(library_cache), _) => Some(library_cache),
+            (None, songs_history) => {
+                songs_history.map(|path| path.with_file_name("library_cache.json"))
+            }
+        };
         StartupParameters {
             manager_listen_address: self.manager_listen_address
                 .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
@@ -92,6 +361,80 @@ impl StartupOptions {
             fullscreen: self.fullscreen,
             mode: self.mode.unwrap_or(ToyundaMode::NormalMode),
             no_manager: self.no_manager,
+            operator_password: self.operator_password,
+            guest_password: self.guest_password,
+            max_songs_in_queue: self.max_songs_in_queue,
+            max_songs_per_hour: self.max_songs_per_hour,
+            fair_queue: self.fair_queue,
+            replay_cooldown: self.replay_cooldown,
+            watch_directories: self.watch_directories,
+            library_cache: library_cache,
+            queue_journal: self.queue_journal.unwrap_or_else(|| {
+                env::current_exe()
+                    .map(|exe| exe.with_file_name("queue_journal.json"))
+                    .unwrap_or(PathBuf::from("queue_journal.json"))
+            }),
+            restore_queue: self.restore_queue,
+            playlists_directory: self.playlists_directory.unwrap_or_else(|| {
+                env::current_exe()
+                    .map(|exe| exe.with_file_name("playlists"))
+                    .unwrap_or(PathBuf::from("playlists"))
+            }),
+            auto_dj: self.auto_dj,
+            intermission_duration: self.intermission_duration.unwrap_or(0),
+            manager_url: self.manager_url,
+            audio_track: self.audio_track.unwrap_or(2),
+            max_volume: self.max_volume.unwrap_or(250.0),
+            announcement_duration: self.announcement_duration.unwrap_or(8),
+            fade_time_before: self.fade_time_before.unwrap_or(200),
+            fade_time_after: self.fade_time_after.unwrap_or(200),
+            log_level: self.log_level.unwrap_or(String::from("warn")),
+            log_file_level: self.log_file_level.unwrap_or(String::from("info")),
+            auto_dj_recent_minutes: self.auto_dj_recent_minutes.unwrap_or(120),
+            auto_dj_languages: self.auto_dj_languages.unwrap_or_default(),
+            auto_dj_music_types: self.auto_dj_music_types.unwrap_or_default(),
+            auto_dj_only_valid_subtitles: self.auto_dj_only_valid_subtitles,
         }
     }
 }
+
+impl StartupParameters {
+    pub fn to_yaml(&self) -> Result<String, String> {
+        serde_yaml::to_string(self).map_err(|e| format!("{}", e))
+    }
+}
+
+#[test]
+fn test_config_layers() {
+    let user_config = StartupOptions::from_config_str("port: 8000\n\
+                                                       directories: [/songs]\n\
+                                                       mode: karaoke\n\
+                                                       fade_time_after: 300\n\
+                                                       log_level: debug\n")
+        .unwrap();
+    let exe_config = StartupOptions::from_config_str("port: 9000\nvolume: 80.0\nwatch: true\n")
+        .unwrap();
+    let mut cli = StartupOptions::default();
+    cli.manager_listen_port = Some(8888);
+    let params = cli.or(user_config).or(exe_config).to_params();
+    assert_eq!(params.manager_listen_port, 8888);
+    assert_eq!(params.volume, Some(80.0));
+    assert_eq!(params.lookup_directories, vec![PathBuf::from("/songs")]);
+    assert_eq!(params.mode, ToyundaMode::KaraokeMode);
+    assert!(params.watch_directories);
+    assert_eq!((params.fade_time_before, params.fade_time_after), (200, 300));
+    assert_eq!(params.log_level, "debug");
+    assert_eq!(params.audio_track, 2);
+    assert!(StartupOptions::from_config_str("").is_ok());
+    assert!(StartupOptions::from_config_str("prot: 8000").is_err());
+    assert!(StartupOptions::from_config_str("log_level: loud").is_err());
+    assert!(StartupOptions::from_config_str("video_files: [a.mkv]").is_err());
+    let auto_dj = StartupOptions::from_config_str("auto_dj: true\n\
+                                                   auto_dj_languages: {JAP: 2.0, FR: 0.5}\n")
+        .unwrap()
+        .to_params();
+    assert!(auto_dj.auto_dj);
+    assert_eq!(auto_dj.auto_dj_languages.get("FR"), Some(&0.5));
+    assert_eq!(auto_dj.auto_dj_recent_minutes, 120);
+    assert!(StartupOptions::from_config_str("auto_dj_music_types: {OP: -1.0}").is_err());
+}
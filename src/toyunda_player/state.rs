@@ -1,5 +1,7 @@
 use ::toyunda_player::playlist::Playlist;
 use ::toyunda_player::playing_state::PlayingState;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 
 #[derive(Debug,Serialize)]
@@ -10,4 +12,27 @@ pub struct State {
     #[serde(skip_serializing_if="Option::is_none")]
     pub quit_when_finished: Option<bool>,
     pub pause_before_next: bool,
+    /// when the songs played since the start were last played, by video
+    /// file; UNIX TIMESTAMP
+    #[serde(skip_serializing)]
+    pub played_at: HashMap<PathBuf, i64>,
+}
+
+impl State {
+    /// seconds before the song being played ends, counted from when it
+    /// started so the pauses aren't taken into account; None if its duration
+    /// isn't known
+    pub fn time_left(&self, now: i64) -> Option<i64> {
+        match self.playing_state {
+            PlayingState::Idle => Some(0),
+            PlayingState::Playing(ref video_meta) => {
+                match (video_meta.video_duration, self.played_at.get(&video_meta.video_path)) {
+                    (0, _) | (_, None) => None,
+                    (duration, Some(started_at)) => {
+                        Some(::std::cmp::max(0, started_at + duration as i64 / 1000 - now))
+                    }
+                }
+            }
+        }
+    }
 }
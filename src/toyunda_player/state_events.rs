@@ -0,0 +1,111 @@
+use iron::response::WriteBody;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError};
+use std::time::Duration;
+use serde_json;
+use ::toyunda_player::video_meta::VideoMeta;
+use ::toyunda_player::playlist::{QueueEntry, QueueId};
+
+/// more than that and the manager wouldn't have any thread left to answer
+/// the other requests : every stream keeps a thread busy
+pub const MAX_EVENT_STREAMS: usize = 64;
+
+/// a comment is sent when nothing happened for this long, so closed
+/// connections are noticed and proxies don't cut the stream
+const KEEP_ALIVE_SECS: u64 = 15;
+
+/// Sent to the web clients every time the State of the player changes,
+/// so they don't have to poll `/api/state`
+#[derive(Debug,Clone,Serialize)]
+#[serde(tag = "type")]
+pub enum StateEvent {
+    #[serde(rename = "queue_add")]
+    QueueAdd { pos: usize, entry: QueueEntry },
+    #[serde(rename = "queue_delete")]
+    QueueDelete { id: QueueId },
+    /// the entry is now at the position `to`
+    #[serde(rename = "queue_move")]
+    QueueMove { id: QueueId, to: usize },
+    #[serde(rename = "queue_swap")]
+    QueueSwap { id: QueueId, other_id: QueueId },
+    /// the ids of every entry of the queue, in the new order
+    #[serde(rename = "queue_reorder")]
+    QueueReorder { order: Vec<QueueId> },
+    #[serde(rename = "queue_clear")]
+    QueueClear,
+    #[serde(rename = "playing")]
+    Playing { video_meta: VideoMeta },
+    #[serde(rename = "idle")]
+    Idle,
+    #[serde(rename = "options")]
+    Options {
+        display_subtitles: bool,
+        quit_when_finished: Option<bool>,
+        pause_before_next: bool,
+    },
+    #[serde(rename = "announcement")]
+    Announcement { text: String },
+    /// the yaml directories were scanned again, the listing changed
+    #[serde(rename = "library_updated")]
+    LibraryUpdated { added: usize, removed: usize },
+    /// a new message with the level error
+    #[serde(rename = "error_log")]
+    ErrorLog { message: String },
+}
+
+/// every client listening to `/api/events`
+#[derive(Clone)]
+pub struct EventStreams {
+    senders: Arc<Mutex<Vec<Sender<StateEvent>>>>,
+}
+
+impl EventStreams {
+    pub fn new() -> EventStreams {
+        EventStreams { senders: Arc::new(Mutex::new(vec![])) }
+    }
+
+    /// None if there are already too many clients
+    pub fn subscribe(&self) -> Option<EventStream> {
+        let mut senders = self.senders.lock().unwrap();
+        if senders.len() >= MAX_EVENT_STREAMS {
+            None
+        } else {
+            let (tx, rx) = channel();
+            senders.push(tx);
+            Some(EventStream { receiver: rx })
+        }
+    }
+
+    /// the clients which are gone are forgotten here
+    pub fn broadcast(&self, event: StateEvent) {
+        let mut senders = self.senders.lock().unwrap();
+        senders.retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+/// body of a `text/event-stream` response, written until the client leaves
+pub struct EventStream {
+    receiver: Receiver<StateEvent>,
+}
+
+impl WriteBody for EventStream {
+    fn write_body(&mut self, res: &mut Write) -> io::Result<()> {
+        // tells the client how long to wait before reconnecting
+        try!(res.write_all(b"retry: 2000\n\n"));
+        try!(res.flush());
+        loop {
+            match self.receiver.recv_timeout(Duration::from_secs(KEEP_ALIVE_SECS)) {
+                Ok(event) => {
+                    let json = try!(serde_json::to_string(&event)
+                        .map_err(|e| io::Error::new(io::ErrorKind::Other, e)));
+                    try!(write!(res, "data: {}\n\n", json));
+                }
+                Err(RecvTimeoutError::Timeout) => try!(res.write_all(b": keep-alive\n\n")),
+                // the manager is gone
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            };
+            try!(res.flush());
+        }
+    }
+}
@@ -1,30 +1,39 @@
 use std::path::Path;
 use ::subtitles::*;
 use ::std::fs::File;
-use ::std::io::{BufReader, BufRead};
-use ::std::error::Error;
+use ::std::io::{BufReader, BufRead, Read};
 use ::subtitles::pos::RowPosition;
 
+fn io_error(path: &Path, error: ::std::io::Error) -> Error {
+    Error::Io {
+        file: path.to_path_buf(),
+        error: error,
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    let mut content = String::new();
+    try!(File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|e| io_error(path, e)));
+    Ok(content)
+}
+
 
 /// frm / lyr / fps
 impl<'a> Load for (Option<&'a Path>, &'a Path, f64) {
-    fn into_subtitles(&self) -> Result<Subtitles, String> {
+    fn into_subtitles(&self) -> Result<Subtitles, Error> {
         // TODO : split this into with / without frm file, where one calls the other
         let frm: Option<&Path> = self.0;
         let lyr: &Path = self.1;
         let fps: f64 = self.2;
         let mut subtitles = Subtitles::default();
-        let lyr_file = try!(File::open(lyr).map_err(|e| e.description().to_string()));
+        let lyr_file = try!(File::open(lyr).map_err(|e| io_error(lyr, e)));
         let lyr_file = BufReader::new(&lyr_file);
         let mut subtitles_options: SubtitlesOptions = Default::default();
         let mut current_sentence_options: Option<SentenceOptions> = None;
         for (line_number, lyr_line) in lyr_file.lines().enumerate() {
-            let lyr_line = try!(lyr_line.map_err(|e| {
-                format!("IoError when reading {} at line {} : '{}'",
-                        lyr.display(),
-                        line_number,
-                        e.description())
-            }));
+            let lyr_line = try!(lyr_line.map_err(|e| io_error(lyr, e)));
             if (!lyr_line.starts_with("%") && !lyr_line.starts_with("#") && !lyr_line.is_empty()) {
                 let mut syllables: Vec<_> = lyr_line.split('&')
                     .map(|s| {
@@ -33,6 +42,7 @@ impl<'a> Load for (Option<&'a Path>, &'a Path, f64) {
                             begin: 0,
                             end: Some(0),
                             syllable_options: None,
+                            pitch: None,
                         }
                     })
                     .collect::<Vec<_>>();
@@ -84,40 +94,29 @@ impl<'a> Load for (Option<&'a Path>, &'a Path, f64) {
             error!("File has no frame per second, impossible to sync .lyr with .frm");
         } else {
             if let Some(frm) = frm {
-                let frm_file = try!(File::open(frm).map_err(|e| e.description().to_string()));
+                let frm_file = try!(File::open(frm).map_err(|e| io_error(frm, e)));
                 let frm_file = BufReader::new(&frm_file);
                 let mut frames: Vec<(u32, u32)> = vec![];
                 for (line_number, frm_line) in frm_file.lines().enumerate() {
-                    let frm_line = try!(frm_line.map_err(|e| {
-                        format!("IoError when reading {} at line {} : '{}'",
-                                frm.display(),
-                                line_number,
-                                e.description())
-                    }));
+                    let frm_line = try!(frm_line.map_err(|e| io_error(frm, e)));
                     if !frm_line.trim().is_empty() {
                         let line_frames: Result<Vec<_>, _> = frm_line.split(' ')
                             .map(|s| s.parse::<u32>())
                             .collect();
                         let begin_end =
-                            line_frames.map_err(|e| format!("{} at line {}", e, line_number))
+                            line_frames.map_err(|e| e.to_string())
                                 .and_then(|line_frames| {
                                     match (line_frames.get(0), line_frames.get(1), line_frames.get(2)) {
                                         (Some(&begin), Some(&end), None) => Ok((begin, end)),
                                         (None, _, _) | (_, None, _) => {
-                                            Err(format!("Error while parsing frm file '{}' at line \
-                                                         {}, not enough values",
-                                                        frm.display(),
-                                                        line_number))
-                                        }
-                                        (_, _, Some(_)) => {
-                                            Err(format!("Error while parsing frm file '{}' at line \
-                                                         {}, too many values",
-                                                        frm.display(),
-                                                        line_number))
+                                            Err(String::from("not enough values"))
                                         }
+                                        (_, _, Some(_)) => Err(String::from("too many values")),
                                     }
                                 });
-                        let begin_end = try!(begin_end);
+                        let begin_end = try!(begin_end.map_err(|e| {
+                            Error::parse(Some(line_number + 1), e).in_file(frm)
+                        }));
                         frames.push(begin_end);
                     } else {
                         warn!("empty line {} in frm file '{}'", line_number, frm.display());
@@ -144,3 +143,122 @@ impl<'a> Load for (Option<&'a Path>, &'a Path, f64) {
         Ok(subtitles)
     }
 }
+
+/// .ass / .ssa file
+pub struct AssFile<'a>(pub &'a Path);
+
+impl<'a> Load for AssFile<'a> {
+    fn into_subtitles(&self) -> Result<Subtitles, Error> {
+        let path: &Path = self.0;
+        let content = try!(read_file(path));
+        let subtitles = try!(Subtitles::from_ass(&content).map_err(|e| e.in_file(path)));
+        try!(subtitles.check());
+        Ok(subtitles)
+    }
+}
+
+pub struct UltraStarFile<'a>(pub &'a Path);
+
+impl<'a> Load for UltraStarFile<'a> {
+    fn into_subtitles(&self) -> Result<Subtitles, Error> {
+        let path: &Path = self.0;
+        let content = try!(read_file(path));
+        let subtitles = try!(Subtitles::from_ultrastar(&content).map_err(|e| e.in_file(path)));
+        try!(subtitles.check());
+        Ok(subtitles)
+    }
+}
+
+#[cfg(test)]
+fn lyr_frm_round_trip(subtitles: &Subtitles, name: &str) -> Subtitles {
+    use std::io::Write;
+    let (lyr, frm) = subtitles.to_lyr_frm(25.0);
+    let dir = ::utils::TempDir::new("lyr_frm_test");
+    let lyr_path = dir.path().join(format!("{}.lyr", name));
+    let frm_path = lyr_path.with_extension("frm");
+    File::create(&lyr_path).unwrap().write_all(lyr.as_bytes()).unwrap();
+    File::create(&frm_path).unwrap().write_all(frm.as_bytes()).unwrap();
+    (Some(&*frm_path), &*lyr_path, 25.0).into_subtitles().unwrap()
+}
+
+#[cfg(test)]
+fn test_syllable(text: &str, begin: u32, end: u32) -> Syllable {
+    Syllable {
+        text: String::from(text),
+        begin: begin,
+        end: Some(end),
+        syllable_options: None,
+        pitch: None,
+    }
+}
+
+#[test]
+fn test_lyr_frm_round_trip_timings() {
+    let mut subtitles = Subtitles::default();
+    subtitles.sentences.push(Sentence {
+        syllables: vec![test_syllable("Ka", 1000, 1200), test_syllable("ra ", 1200, 1520)],
+        position: RowPosition::default(),
+        sentence_options: None,
+    });
+    subtitles.sentences.push(Sentence {
+        syllables: vec![test_syllable("%o", 2000, 2480)],
+        position: RowPosition::default(),
+        sentence_options: None,
+    });
+    let imported = lyr_frm_round_trip(&subtitles, "toyunda_round_trip_timings");
+    assert_eq!(imported.sentences.len(), 2);
+    for (sentence, imported_sentence) in subtitles.sentences.iter().zip(imported.sentences.iter()) {
+        assert_eq!(sentence.syllables.len(), imported_sentence.syllables.len());
+        for (syllable, imported_syllable) in sentence.syllables
+            .iter()
+            .zip(imported_sentence.syllables.iter()) {
+            assert_eq!(syllable.text, imported_syllable.text);
+            assert_eq!(syllable.begin, imported_syllable.begin);
+            assert_eq!(syllable.end, imported_syllable.end);
+        }
+    }
+}
+
+#[test]
+fn test_lyr_frm_round_trip_colors() {
+    let blue = Color { red: 0, green: 0, blue: 255 };
+    let green = Color { red: 0, green: 255, blue: 0 };
+    let mut global_options = SentenceOptions::default();
+    global_options.syllable_options = Some(SyllableOptions {
+        alive_color: Some(blue),
+        transition_color: Some(green),
+        dead_color: Some(blue),
+        outline: None,
+    });
+    let mut sentence_options = SentenceOptions::default();
+    sentence_options.syllable_options = Some(SyllableOptions {
+        alive_color: Some(green),
+        ..SyllableOptions::default()
+    });
+    let mut subtitles = Subtitles::default();
+    subtitles.subtitles_options.sentence_options = Some(global_options);
+    for options in vec![None, Some(sentence_options), None] {
+        subtitles.sentences.push(Sentence {
+            syllables: vec![test_syllable("a", 0, 40)],
+            position: RowPosition::default(),
+            sentence_options: options,
+        });
+    }
+    let imported = lyr_frm_round_trip(&subtitles, "toyunda_round_trip_colors");
+    let global = imported.subtitles_options
+        .sentence_options
+        .and_then(|o| o.syllable_options)
+        .unwrap();
+    assert_eq!((global.alive_color, global.dead_color), (Some(blue), Some(blue)));
+    let alive_colors: Vec<_> = imported.sentences
+        .iter()
+        .map(|s| {
+            s.sentence_options
+                .as_ref()
+                .and_then(|o| o.syllable_options)
+                .or_syllable_options(global.as_syllable_options())
+                .and_then(|o| o.alive_color)
+        })
+        .collect();
+    assert_eq!(alive_colors, vec![Some(blue), Some(green), Some(blue)]);
+}
@@ -1,13 +1,12 @@
 extern crate serde_json;
 
 use super::*;
-use mpv::{MpvHandlerWithGl, Event as MpvEvent};
-use ::subtitles::{Subtitles, Load, AsSentenceOptions};
+use ::subtitles::{Subtitles, SentenceOptions, Load, AsSentenceOptions};
 use ::overlay::pos::*;
-use ::overlay::{Display, OverlayFrame, TextUnit, TextSubUnit, Outline, Color, AlphaColor};
-use ::sdl_displayer::{SDLDisplayer, SDLDisplayParameters as DisplayParams};
+use ::overlay::{Screen, OverlayFrame, TextUnit, TextSubUnit, Outline, Color, AlphaColor,
+                Rect};
+use ::sdl_displayer::SDLDisplayParameters as DisplayParams;
 use sdl2::event::Event;
-use sdl2::pixels::Color as SdlColor;
 use sdl2::Sdl;
 use sdl2::keyboard::{KeyboardState, Scancode, Keycode};
 use std::sync::{RwLock, Arc};
@@ -20,25 +19,52 @@ use ::toyunda_player::editor::*;
 use ::toyunda_player::toyunda_history::*;
 use ::toyunda_player::StartupParameters;
 use ::utils::RGB;
-use ::mpv_plug::MpvCache;
-use mpv::EndFileReason::MPV_END_FILE_REASON_EOF;
-use mpv::Error::{MPV_ERROR_PROPERTY_UNAVAILABLE, MPV_ERROR_LOADING_FAILED};
+use ::toyunda_player::queue_journal::{QueueJournal, sentence_start};
+use ::toyunda_player::playlist_file::{PlaylistFormat, PlaylistStore, import_file};
+use ::toyunda_player::auto_dj::AutoDj;
+use ::toyunda_player::intermission::{Intermission, default_manager_url};
+use rand::thread_rng;
 use chrono::{DateTime, Local};
+use std::path::PathBuf;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
-pub struct ToyundaPlayer<'r,'ttf> {
+pub struct ToyundaPlayer<'a> {
     pub subtitles: Option<Subtitles>,
-    pub mpv: Box<MpvHandlerWithGl>,
-    pub displayer: SDLDisplayer<'r,'ttf>,
+    pub media: Box<MediaBackend>,
+    pub displayer: Box<Screen<Parameters = DisplayParams> + 'a>,
     pub mode: ToyundaMode,
     pub state: Arc<RwLock<State>>,
     pub manager: Option<Manager>,
     pub editor_state: Option<EditorState>,
     pub announcements: Vec<(String, DateTime<Local>)>,
     pub songs_history: Option<SongsHistory>,
-    mpv_cache: MpvCache,
     unsaved_changes: bool,
+    /// log messages already sent to the web clients
+    log_messages_sent: usize,
+    /// requesters take turns in the queue instead of first come, first served
+    pub fair_queue: bool,
+    /// in seconds
+    pub announcement_duration: u32,
+    /// used for what the subtitles don't set themselves
+    pub sentence_defaults: SentenceOptions,
+    /// where the queue is written, None in edit mode
+    queue_journal: Option<PathBuf>,
+    /// the song restored from the queue journal, and where it was interrupted
+    resume_at: Option<(PathBuf, u32)>,
+    /// fills the queue when it runs dry, only in karaoke mode
+    auto_dj: Option<AutoDj>,
+    /// in seconds, 0 to start the next song right away
+    pub intermission_duration: u32,
+    /// when the next song starts, during an intermission
+    pub intermission_until: Option<Instant>,
+    /// shown during the intermissions, None without the manager
+    manager_url: Option<String>,
 }
 
+/// how often the position of the song is written to the queue journal
+const JOURNAL_INTERVAL_SECS: u64 = 5;
+
 /// returns 3 boolean : (AltPressed,CtrlPressed,ShiftPressed)
 #[inline]
 fn get_alt_keys(keyboard_state: KeyboardState) -> (bool, bool, bool) {
@@ -50,17 +76,19 @@ fn get_alt_keys(keyboard_state: KeyboardState) -> (bool, bool, bool) {
      keyboard_state.is_scancode_pressed(Scancode::LShift))
 }
 
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq)]
 pub enum ToyundaAction {
     Nothing,
     Terminate,
 }
 
-impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
-    pub fn new(mpv_box: Box<MpvHandlerWithGl>, displayer: SDLDisplayer<'r,'ttf>) -> ToyundaPlayer<'r,'ttf> {
+impl<'a> ToyundaPlayer<'a> {
+    pub fn new(media: Box<MediaBackend>,
+               displayer: Box<Screen<Parameters = DisplayParams> + 'a>)
+               -> ToyundaPlayer<'a> {
         ToyundaPlayer {
             subtitles: None,
-            mpv: mpv_box,
+            media: media,
             displayer: displayer,
             mode: ToyundaMode::NormalMode,
             state: Arc::new(RwLock::new(State {
@@ -69,13 +97,23 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 display_subtitles: true,
                 quit_when_finished: None,
                 pause_before_next: false,
+                played_at: HashMap::new(),
             })),
             manager: None,
             editor_state: None,
             songs_history: None,
             announcements: vec![],
-            mpv_cache: MpvCache::new(),
             unsaved_changes: false,
+            log_messages_sent: 0,
+            fair_queue: false,
+            announcement_duration: 8,
+            sentence_defaults: SentenceOptions::default(),
+            queue_journal: None,
+            resume_at: None,
+            auto_dj: None,
+            intermission_duration: 0,
+            intermission_until: None,
+            manager_url: None,
         }
     }
 
@@ -114,12 +152,46 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
 
     pub fn start(&mut self, params: StartupParameters) -> Result<()> {
         let mut is_playlist_empty = true;
+        if params.restore_queue {
+            match QueueJournal::open(&params.queue_journal) {
+                Ok(journal) => {
+                    let (playlist, position) = journal.into_queue();
+                    if let (Some(position), Some(entry)) = (position, playlist.front()) {
+                        self.resume_at = Some((entry.video_meta.video_path.clone(), position));
+                    }
+                    info!("{} songs restored from the queue journal", playlist.len());
+                    is_playlist_empty = playlist.is_empty();
+                    self.state.write().unwrap().playlist = playlist;
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
+        if params.mode != ToyundaMode::EditMode {
+            self.queue_journal = Some(params.queue_journal.clone());
+        }
         if !params.video_files.is_empty() {
             let mut state = self.state.write().unwrap();
             for value in &params.video_files {
+                if PlaylistFormat::from_path(value).is_some() {
+                    match import_file(value) {
+                        Ok((songs, errors)) => {
+                            for e in errors {
+                                error!("Error when importing playlist '{}' : {}",
+                                       value.display(),
+                                       e);
+                            }
+                            for video_meta in songs {
+                                state.playlist.push_back(QueueEntry::new(video_meta, None));
+                                is_playlist_empty = false;
+                            }
+                        }
+                        Err(e) => error!("{}", e),
+                    }
+                    continue;
+                }
                 match VideoMeta::new(value) {
                     Ok(video_meta) => {
-                        state.playlist.push_back(video_meta);
+                        state.playlist.push_back(QueueEntry::new(video_meta, None));
                         is_playlist_empty = false;
                     }
                     Err(e) => {
@@ -133,11 +205,16 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
         };
         let mut enable_manager: bool;
         self.mode = params.mode;
+        self.fair_queue = params.fair_queue;
+        self.announcement_duration = params.announcement_duration;
+        self.intermission_duration = params.intermission_duration;
+        self.sentence_defaults.fade_time_before = Some(params.fade_time_before);
+        self.sentence_defaults.fade_time_after = Some(params.fade_time_after);
         match params.mode {
             ToyundaMode::EditMode => {
                 self.editor_state = None;
                 enable_manager = false;
-                if let Err(e) = self.mpv.set_option("loop-file", "inf") {
+                if let Err(e) = self.media.set_loop_file(true) {
                     error!("loop file option failed for edit mode : {}", e);
                 };
                 debug!("Enabling edit mode");
@@ -145,11 +222,20 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
             ToyundaMode::KaraokeMode => {
                 debug!("Enabling karaoke mode");
                 enable_manager = true;
+                if params.auto_dj {
+                    self.auto_dj = Some(AutoDj::new(params.auto_dj_recent_minutes,
+                                                    &params.auto_dj_languages,
+                                                    &params.auto_dj_music_types,
+                                                    params.auto_dj_only_valid_subtitles));
+                }
             }
             ToyundaMode::NormalMode => {
                 enable_manager = true;
             }
         };
+        if params.auto_dj && params.mode != ToyundaMode::KaraokeMode {
+            warn!("The auto-DJ is only used in karaoke mode");
+        }
         if let Some(songs_history) = params.songs_history {
             match SongsHistory::new(songs_history) {
                 Ok(songs_history) => {
@@ -169,10 +255,25 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                                                  params.manager_listen_port),
                                        Arc::downgrade(&self.state),
                                        params.lookup_directories,
-                                       self.songs_history.as_ref());
+                                       self.songs_history.as_ref(),
+                                       ManagerAuth {
+                                           operator_password: params.operator_password,
+                                           guest_password: params.guest_password,
+                                       },
+                                       RequestQuotas::new(params.max_songs_in_queue,
+                                                          params.max_songs_per_hour),
+                                       params.watch_directories,
+                                       params.library_cache,
+                                       PlaylistStore::new(params.playlists_directory),
+                                       params.replay_cooldown);
             match manager {
                 Ok(manager) => {
                     self.manager = Some(manager);
+                    let (listen_address, listen_port) = (params.manager_listen_address,
+                                                         params.manager_listen_port);
+                    self.manager_url = Some(params.manager_url.unwrap_or_else(|| {
+                        default_manager_url(listen_address, listen_port)
+                    }));
                 }
                 Err(e) => {
                     error!("Error when initializing manager : '{}'", e);
@@ -181,18 +282,20 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
         }
 
         if let Some(volume) = params.volume {
-            match self.mpv.set_property("volume", volume) {
+            match self.media.set_volume(volume) {
                 Ok(_) => {
                     debug!("Successfully overridden initial volume");
                 }
                 Err(e) => {
-                    error!("Could not change initial volume of mpv,\
+                    error!("Could not change initial volume,\
                            error '{}' ({:?})",
                            e,
                            e);
                 }
             };
         }
+        // shown as the next song, the operator still starts the first one
+        self.fill_auto_dj();
         if (is_playlist_empty == false) {
             if let Err(e) = self.execute_command(Command::PlayNext) {
                 error!("Error trying to play first file : '{}'", e);
@@ -206,6 +309,7 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
     ///
     pub fn on_load_media(&mut self) -> Result<ToyundaAction> {
         let res = self.import_cur_file_subtitles();
+        self.resume_interrupted_song();
         if let Err(e) = res {
             if self.mode == ToyundaMode::KaraokeMode {
                 if let &PlayingState::Playing(ref video_meta) = &self.state
@@ -228,6 +332,97 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
         }
     }
 
+    /// seeks where the song restored from the queue journal was interrupted;
+    /// in karaoke mode, the sentence being sung then starts again
+    fn resume_interrupted_song(&mut self) {
+        let (video_path, position) = match self.resume_at.take() {
+            Some(resume_at) => resume_at,
+            None => return,
+        };
+        let is_restored_song = match self.state.read().unwrap().playing_state {
+            PlayingState::Playing(ref video_meta) => video_meta.video_path == video_path,
+            PlayingState::Idle => false,
+        };
+        if !is_restored_song {
+            return;
+        }
+        let position = match (self.mode, self.subtitles.as_ref()) {
+            (ToyundaMode::KaraokeMode, Some(subtitles)) => sentence_start(subtitles, position),
+            _ => position,
+        };
+        let delta = position as f64 / 1000.0 - self.media.time_pos().unwrap_or(0.0);
+        if let Err(e) = self.media.seek(delta) {
+            error!("Could not resume the interrupted song : {}", e);
+        }
+    }
+
+    /// writes the queue and the song being played, so they can be restored
+    /// with --restore-queue
+    pub fn write_queue_journal(&self) {
+        if let Some(ref path) = self.queue_journal {
+            let journal = {
+                let state = self.state.read().unwrap();
+                QueueJournal::new(&state.playing_state,
+                                  self.get_media_current_time(),
+                                  &state.playlist)
+            };
+            if let Err(e) = journal.save(path) {
+                error!("Could not write the queue journal '{}' : {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Adds a song picked by the auto-DJ if the queue is empty, false if
+    /// nothing was added. Without the manager, there is no library to pick from.
+    pub fn fill_auto_dj(&mut self) -> bool {
+        let video_meta = {
+            let auto_dj = match self.auto_dj {
+                Some(ref auto_dj) => auto_dj,
+                None => return false,
+            };
+            let library = match self.manager {
+                Some(ref manager) => manager.library(),
+                None => return false,
+            };
+            let state = self.state.read().unwrap();
+            if !state.playlist.is_empty() {
+                return false;
+            }
+            let playing = match state.playing_state {
+                PlayingState::Playing(ref video_meta) => Some(&video_meta.video_path),
+                PlayingState::Idle => None,
+            };
+            // the history knows about the songs played since the library was loaded
+            let songs_history = self.songs_history.as_ref();
+            let last_played = |video_meta: &VideoMeta| {
+                songs_history.and_then(|history| history.get(&*format!("{}", video_meta)))
+                    .and_then(|dates| dates.last())
+                    .map(|date| date.timestamp())
+                    .or(video_meta.last_played)
+            };
+            let songs = library.songs
+                .iter()
+                .filter_map(|song| song.as_ref())
+                .filter(|song| Some(&song.video_path) != playing);
+            match auto_dj.pick(songs, last_played, Local::now().timestamp(), &mut thread_rng()) {
+                Some(video_meta) => video_meta.clone(),
+                None => {
+                    warn!("The auto-DJ found no song to pick, every song was played recently \
+                           or filtered out");
+                    return false;
+                }
+            }
+        };
+        info!("Auto-DJ picked '{}'", video_meta);
+        match self.execute_command(Command::AddToQueue(QueueEntry::auto_dj(video_meta))) {
+            Ok(_) => true,
+            Err(e) => {
+                error!("Could not add the song of the auto-DJ : {}", e);
+                false
+            }
+        }
+    }
+
     /// This method doesnt load subtitles ... we wait for the file to be loaded
     /// to load subtitles (that way Video-related parameters can be sent to subtitles,
     /// like total length, FPS...
@@ -240,19 +435,20 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 self.execute_command(Command::PlayNext)
             }
             Some(video_path) => {
-                match self.mpv.command(&["loadfile", video_path.as_str()]) {
+                match self.media.load_file(video_path.as_str()) {
                     Ok(_) => {
                         if let Some(ref mut songs_history) = self.songs_history {
                             songs_history.insert_song_history_entry(&*format!("{}", &video_meta));
                         };
                         if let Err(e) = self.displayer
-                            .canvas
-                            .window_mut()
                             .set_title(&*format!("Toyunda Player - {}", video_meta)) {
                             warn!("Unexpected error when setting title : {}", e);
                         };
-                        self.state.write().unwrap().playing_state =
-                            PlayingState::Playing(video_meta);
+                        self.notify(StateEvent::Playing { video_meta: video_meta.clone() });
+                        let mut state = self.state.write().unwrap();
+                        state.played_at
+                            .insert(video_meta.video_path.clone(), Local::now().timestamp());
+                        state.playing_state = PlayingState::Playing(video_meta);
                         info!("Now playing : '{}'", &video_path);
                         Ok(ToyundaAction::Nothing)
                     }
@@ -269,31 +465,35 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
     }
 
     pub fn get_file_fps(&self) -> f64 {
-        self.mpv.get_property::<f64>("fps").unwrap_or(0.0)
+        self.media.fps().unwrap_or(0.0)
     }
 
     /// if video_meta is None, reload the current subtitles
     /// otherwise load from video_meta
     pub fn import_cur_file_subtitles(&mut self) -> Result<()> {
-        let duration: u32 = (self.mpv.get_property::<f64>("duration").unwrap_or(0.0) *
-                             1000.0) as u32;
+        let duration: u32 = (self.media.duration().unwrap_or(0.0) * 1000.0) as u32;
         let fps: f64 = self.get_file_fps();
-        let (json_path, lyr_path, frm_path) = match &self.state.read().unwrap().playing_state {
+        let (json_path, ass_path, lyr_path, frm_path, ultrastar_path) = match &self.state
+            .read()
+            .unwrap()
+            .playing_state {
             &PlayingState::Idle => {
                 return Err(Error::Text(String::from("Error when reloading subtitles : no file \
                                                      is playing !")))
             }
             &PlayingState::Playing(ref video_meta) => {
-                (video_meta.json_path(), video_meta.lyr_path(), video_meta.frm_path())
+                (video_meta.json_path(),
+                 video_meta.ass_path(),
+                 video_meta.lyr_path(),
+                 video_meta.frm_path(),
+                 video_meta.ultrastar_path())
             }
         };
         // TODO move this chunk into a private method
         if (json_path.is_file()) {
             debug!("Loading file {}", json_path.display());
             let json_file = ::std::fs::File::open(json_path).expect("Failed to open JSON file");
-            let read_result: ::std::result::Result<Subtitles, _> =
-                serde_json::from_reader(json_file);
-            match read_result {
+            match Subtitles::from_json(json_file) {
                 Ok(mut subtitles) => {
                     subtitles.post_init(duration);
                     self.subtitles = Some(subtitles);
@@ -301,6 +501,16 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 }
                 Err(e) => Err(e.into()),
             }
+        } else if (ass_path.is_file()) {
+            debug!("Failed to load json file, loading {}", ass_path.display());
+            AssFile(&*ass_path)
+                .into_subtitles()
+                .map(|mut subtitles| {
+                    subtitles.post_init(duration);
+                    self.subtitles = Some(subtitles);
+                    ()
+                })
+                .map_err(Error::SubtitlesError)
         } else {
             debug!("Failed to load json file, trying lyr and frm files");
             if (lyr_path.is_file() && frm_path.is_file()) {
@@ -312,7 +522,7 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                         self.subtitles = Some(subtitles);
                         ()
                     })
-                    .map_err(|s| Error::Text(s))
+                    .map_err(Error::SubtitlesError)
             } else if lyr_path.is_file() {
                 warn!("Failed to load json subtitle file, loading lyr");
                 warn!("Failed to load .frm file; Subtitles won't have timings");
@@ -323,7 +533,18 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                         self.subtitles = Some(subtitles);
                         ()
                     })
-                    .map_err(|s| Error::Text(s))
+                    .map_err(Error::SubtitlesError)
+            } else if ultrastar_path.is_file() {
+                warn!("Failed to load json subtitle file, loading {}",
+                      ultrastar_path.display());
+                UltraStarFile(&*ultrastar_path)
+                    .into_subtitles()
+                    .map(|mut subtitles| {
+                        subtitles.post_init(duration);
+                        self.subtitles = Some(subtitles);
+                        ()
+                    })
+                    .map_err(Error::SubtitlesError)
             } else if frm_path.is_file() {
                 Err(Error::FileNotFound(frm_path.display().to_string()))
             } else {
@@ -430,7 +651,7 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
             .filter(|m| !is_karaoke_mode || m.category == Category::Announcement);
         let graphic_messages =
             graphic_messages.chain(self.announcements.iter().filter_map(|&(ref s, ref t)| {
-                if *t + Duration::seconds(8) > Local::now() {
+                if *t + Duration::seconds(self.announcement_duration as i64) > Local::now() {
                     Some(GraphicMessage {
                         category: Category::Announcement,
                         text: s.clone(),
@@ -466,12 +687,10 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
     }
 
     pub fn render_overlay(&mut self) -> Result<()> {
-        use sdl2::rect::Rect;
-        let (width, height) = self.displayer.sdl_canvas().window().size();
+        let (width, height) = self.displayer.size();
         let time_pos = self.get_media_current_time();
-        let display_params: DisplayParams = match (self.mpv_cache.cached_width(),
-                                                   self.mpv_cache.cached_height()) {
-            (Some(w), Some(h)) => {
+        let display_params: DisplayParams = match self.media.dimensions() {
+            Some((w, h)) => {
                 let (final_w, final_h);
                 let (offset_x, offset_y);
                 let mpv_aspect_ratio: f64 = (w as f64) / (h as f64);
@@ -499,12 +718,26 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 }
             }
         };
+        if self.mode != ToyundaMode::EditMode {
+            let state = self.state.read().unwrap();
+            if !state.playing_state.is_playing() {
+                let now = Instant::now();
+                let intermission = Intermission {
+                    playlist: &state.playlist,
+                    countdown: self.intermission_until
+                        .map(|until| if until > now { (until - now).as_secs() + 1 } else { 0 }),
+                    manager_url: self.manager_url.as_ref().map(|url| url.as_str()),
+                };
+                self.displayer.display(&intermission.to_overlay_frame(), &display_params);
+            }
+        }
         if let Some(ref subtitles) = self.subtitles {
             if self.state.read().unwrap().display_subtitles {
                 let overlay_frame = if let Some(ref editor_state) = self.editor_state {
                     editor_state.to_overlay_frame(time_pos, subtitles)
                 } else {
-                    subtitles.to_overlay_frame(time_pos)
+                    subtitles.to_overlay_frame_with_defaults(time_pos,
+                                                             Some(&self.sentence_defaults))
                 };
                 match overlay_frame {
                     Ok(overlay_frame) => {
@@ -517,19 +750,20 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
             }
         }
         if (self.mode == ToyundaMode::EditMode) {
-            let percent_pos: f64 = self.mpv_cache.cached_percent_pos().unwrap_or(0.0);
-            let (window_width, window_height) =
-                self.displayer.sdl_canvas().window().size();
+            let percent_pos: f64 = self.media.percent_pos().unwrap_or(0.0);
+            let (window_width, window_height) = self.displayer.size();
             let rect_width = window_width * 5 / 1000;
             let rect_height = rect_width * 2;
             let (rect_origin_x, rect_origin_y) =
                 ((((window_width - rect_width) as f64) * percent_pos / 100.0) as i32,
                  (window_height - rect_height) as i32);
-            self.displayer.sdl_canvas_mut().set_draw_color(SdlColor::RGB(0, 0, 255));
-            self.displayer
-                .sdl_canvas_mut()
-                .fill_rect(Rect::new(rect_origin_x, rect_origin_y, rect_width, rect_height))
-                .unwrap();
+            let rect = Rect {
+                x: rect_origin_x,
+                y: rect_origin_y,
+                width: rect_width,
+                height: rect_height,
+            };
+            self.displayer.fill_rect(rect, Color::new(0, 0, 255)).unwrap();
         };
         // display logs
         let credits_overlay_frame = self.credits_as_overlay_frame();
@@ -540,23 +774,78 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
         Ok(())
     }
 
-    fn get_manager_commands(&mut self) -> Vec<Command> {
-        let mut commands: Vec<Command> = Vec::new();
-        if let &Some(ref manager) = &self.manager {
-            for command in manager.receiver.try_recv() {
-                commands.push(command);
+    /// every request the manager sent since the last frame
+    fn get_manager_requests(&mut self) -> Vec<ManagerRequest> {
+        match self.manager {
+            Some(ref manager) => manager.receiver.try_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// sends the event to the web clients, if the manager is enabled
+    pub fn notify(&self, event: StateEvent) {
+        if let Some(ref manager) = self.manager {
+            manager.notify(event);
+        }
+    }
+
+    pub fn notify_options(&self) {
+        let event = {
+            let state = self.state.read().unwrap();
+            StateEvent::Options {
+                display_subtitles: state.display_subtitles,
+                quit_when_finished: state.quit_when_finished,
+                pause_before_next: state.pause_before_next,
             }
+        };
+        self.notify(event);
+    }
+
+    /// the log messages are written by the logger, which doesn't know about
+    /// the player : the new ones are looked for once per frame
+    fn notify_error_logs(&mut self) {
+        use ::toyunda_player::log_messages::LOG_MESSAGES;
+        use log::LogLevel;
+        if self.manager.is_none() {
+            return;
+        }
+        let messages: Vec<String> = match LOG_MESSAGES.read() {
+            Ok(log_messages) => {
+                let new_messages = log_messages.iter()
+                    .skip(self.log_messages_sent)
+                    .filter(|m| m.level == LogLevel::Error)
+                    .map(|m| m.msg.clone())
+                    .collect();
+                self.log_messages_sent = log_messages.len();
+                new_messages
+            }
+            Err(_) => return,
+        };
+        for message in messages {
+            self.notify(StateEvent::ErrorLog { message: message });
         }
-        commands
     }
 
     pub fn on_end_file(&mut self) -> Result<ToyundaAction> {
         self.state.write().unwrap().playing_state = PlayingState::Idle;
-        if let Err(e) = self.displayer.canvas.window_mut().set_title("Toyunda Player") {
+        self.notify(StateEvent::Idle);
+        if let Err(e) = self.displayer.set_title("Toyunda Player") {
             warn!("Unexpected error when setting title : {}", e);
         };
         self.clear_subtitles();
-        self.execute_command(Command::PlayNext)
+        let announce_next = {
+            let state = self.state.read().unwrap();
+            self.mode == ToyundaMode::KaraokeMode && self.intermission_duration > 0 &&
+            !state.playlist.is_empty() && !state.pause_before_next
+        };
+        if announce_next {
+            // the main loop plays it once the countdown is over
+            self.intermission_until =
+                Some(Instant::now() + Duration::from_secs(self.intermission_duration as u64));
+            Ok(ToyundaAction::Nothing)
+        } else {
+            self.execute_command(Command::PlayNext)
+        }
     }
 
     /// true : confirm terminate
@@ -613,61 +902,80 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
         }
     }
 
+    /// Terminate means "no matter what" here : the media backend is gone, or
+    /// there is nothing left to play
+    pub fn handle_media_event(&mut self, event: MediaEvent) -> Result<ToyundaAction> {
+        match event {
+            MediaEvent::Shutdown => Ok(ToyundaAction::Terminate),
+            MediaEvent::EndOfFile => self.on_end_file(),
+            MediaEvent::LoadingFailed => {
+                match &self.state.read().unwrap().playing_state {
+                    &PlayingState::Playing(ref video_meta) => {
+                        error!("Failed to load video stream `{}` current song will be skipped",
+                               video_meta.video_path.display());
+                    }
+                    _ => {}
+                }
+                self.on_end_file()
+            }
+            MediaEvent::FileLoaded => {
+                match self.on_load_media() {
+                    Err(ref e) if self.mode == ToyundaMode::KaraokeMode => {
+                        error!("{}", e);
+                        self.execute_command(Command::PlayNext)
+                    }
+                    r => r,
+                }
+            }
+        }
+    }
+
+    /// handles every pending media event, true if the player must quit right
+    /// away, without asking to save the subtitles
+    pub fn poll_media_events(&mut self,
+                             command_results: &mut Vec<Result<ToyundaAction>>)
+                             -> bool {
+        while let Some(event) = self.media.poll_event() {
+            match self.handle_media_event(event) {
+                Ok(ToyundaAction::Terminate) => return true,
+                r => command_results.push(r),
+            }
+        }
+        false
+    }
+
     pub fn main_loop(&mut self, sdl_context: &Sdl) -> Result<()> {
         let mut event_pump = sdl_context.event_pump().expect("Failed to create event_pump");
         // TODO : Add a single queue of `Command` so the result can
         // be processed in the place only.
         let mut command_results: Vec<Result<ToyundaAction>> = Vec::with_capacity(16);
+        let mut last_journal_write = Instant::now();
         'main: loop {
-            let (width, height) = self.displayer.sdl_canvas().window().size();
+            let (width, height) = self.displayer.size();
             let alt_keys = get_alt_keys(event_pump.keyboard_state());
-            self.mpv
-                .draw(0, width as i32, -(height as i32))
-                .expect("failed to draw video frame with mpv");
-            self.mpv_cache.update(&self.mpv);
+            self.media
+                .draw(width, height)
+                .expect("failed to draw video frame");
+            self.media.update();
             for event in event_pump.poll_iter() {
                 command_results.push(self.handle_event(event, alt_keys));
             }
-            for command in self.get_manager_commands() {
-                command_results.push(self.execute_command(command));
-            }
-            while let Some(event) = self.mpv.wait_event(0.0) {
-                match event {
-                    MpvEvent::Shutdown => break 'main,
-                    MpvEvent::EndFile(Ok(MPV_END_FILE_REASON_EOF)) => {
-                        match self.on_end_file() {
-                            Ok(ToyundaAction::Terminate) => break 'main,
-                            // TODO parse mpv error (shouldnt happen often, but still)
-                            _ => {}
-                        }
-                    }
-                    MpvEvent::EndFile(Err(MPV_ERROR_LOADING_FAILED)) => {
-                        match &self.state.read().unwrap().playing_state {
-                            &PlayingState::Playing(ref video_meta) => {
-                                error!("Failed to load video stream `{}` current song will be \
-                                        skipped",
-                                       video_meta.video_path.display());
-                            }
-                            _ => {}
-                        }
-                        match self.on_end_file() {
-                            Ok(ToyundaAction::Terminate) => break 'main,
-                            _ => {}
-                        }
-                    }
-                    MpvEvent::FileLoaded => {
-                        match self.on_load_media() {
-                            Ok(_) => {}
-                            Err(e) => {
-                                error!("{}", e);
-                                if self.mode == ToyundaMode::KaraokeMode {
-                                    command_results.push(self.execute_command(Command::PlayNext));
-                                }
-                            }
-                        }
-                    }
-                    _ => {}
-                };
+            for request in self.get_manager_requests() {
+                command_results.push(Ok(self.execute_manager_request(request)));
+            }
+            if self.poll_media_events(&mut command_results) {
+                break 'main;
+            }
+            self.notify_error_logs();
+            if self.intermission_until.map_or(false, |until| Instant::now() >= until) {
+                command_results.push(self.execute_command(Command::PlayNext));
+            }
+            // the position of the song changes without any command
+            if last_journal_write.elapsed() >= Duration::from_secs(JOURNAL_INTERVAL_SECS) {
+                if self.state.read().unwrap().playing_state.is_playing() {
+                    self.write_queue_journal();
+                }
+                last_journal_write = Instant::now();
             }
             for r in command_results.drain(0..) {
                 match r {
@@ -710,7 +1018,7 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
     }
 
     pub fn get_media_current_time(&self) -> u32 {
-        if let Some(t) = self.mpv_cache.cached_time_pos() {
+        if let Some(t) = self.media.time_pos() {
             (t.max(0.0) * 1000.0) as u32
         } else {
             0u32
@@ -951,24 +1259,21 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 };
                 self.execute_command(Command::Seek(-3.0))
             }
+            Event::KeyDown { keycode: Some(Keycode::R), repeat: false, .. }
+                if mode != EditMode && is_ctrl_pressed => {
+                self.execute_command(Command::RescanLibrary)
+            }
             Event::KeyDown { keycode: Some(Keycode::R), repeat: false, .. } if mode !=
                                                                                KaraokeMode => {
                 self.execute_command(Command::ReloadSubtitles)
             }
             Event::MouseButtonDown { x, y, mouse_btn, .. } if mode != KaraokeMode => {
-                let (win_width, win_height) =
-                    self.displayer.sdl_canvas().window().size();
+                let (win_width, win_height) = self.displayer.size();
                 if (y as u32 > win_height * 96 / 100) {
                     // bottom 4% : low enough to move
                     let percent_pos: f64 = (100.0 * x as f64 / win_width as f64);
-                    if let Err(e) = self.mpv.set_property("percent-pos", percent_pos) {
-                        match e {
-                            MPV_ERROR_PROPERTY_UNAVAILABLE => {}
-                            // happens when video is paused
-                            _ => {
-                                error!("Unexpected error: `{}` when trying to move", e);
-                            }
-                        };
+                    if let Err(e) = self.media.set_percent_pos(percent_pos) {
+                        error!("Unexpected error: `{}` when trying to move", e);
                     }
                 } else if mode == EditMode {
                     use sdl2::mouse::MouseButton::*;
@@ -984,7 +1289,7 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 Ok(ToyundaAction::Nothing)
             }
             Event::MouseButtonUp { y, mouse_btn, .. } if mode != KaraokeMode => {
-                let (_, win_height) = self.displayer.sdl_canvas().window().size();
+                let (_, win_height) = self.displayer.size();
                 if (y as u32 > win_height * 96 / 100) {
                     // do nothing
                 } else if mode == EditMode {
@@ -1013,14 +1318,15 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
                 };
                 match VideoMeta::new(filename) {
                     Ok(video_meta) => {
-                        let add_result = self.execute_command(Command::AddToQueue(video_meta));
+                        let queue_entry = QueueEntry::new(video_meta, None);
+                        let add_result = self.execute_command(Command::AddToQueue(queue_entry));
                         if play_next {
                             add_result.and(self.execute_command(Command::PlayNext))
                         } else {
                             add_result
                         }
                     }
-                    Err(e) => Err(Error::Text(e)),
+                    Err(e) => Err(e.into()),
                 }
             }
             Event::KeyDown { keycode: Some(Keycode::S), repeat: false, .. } if mode !=
@@ -1048,3 +1354,182 @@ impl<'r,'ttf> ToyundaPlayer<'r,'ttf> {
         self.subtitles = None;
     }
 }
+
+#[cfg(test)]
+struct FakeScreen;
+
+#[cfg(test)]
+impl ::overlay::Display for FakeScreen {
+    type Parameters = DisplayParams;
+    fn display(&mut self, _: &OverlayFrame, _: &DisplayParams) -> Vec<Rect> {
+        vec![]
+    }
+}
+
+#[cfg(test)]
+impl Screen for FakeScreen {
+    fn size(&self) -> (u32, u32) {
+        (960, 540)
+    }
+
+    fn set_title(&mut self, _: &str) -> ::std::result::Result<(), String> {
+        Ok(())
+    }
+
+    fn toggle_fullscreen(&mut self) -> ::std::result::Result<(), String> {
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, _: Rect, _: Color) -> ::std::result::Result<(), String> {
+        Ok(())
+    }
+
+    fn render(&mut self) {}
+}
+
+#[cfg(test)]
+fn test_player(mode: ToyundaMode, songs: &[PathBuf]) -> (ToyundaPlayer<'static>, FakeBackend) {
+    let backend = FakeBackend::new(10.0);
+    let mut player = ToyundaPlayer::new(Box::new(backend.clone()), Box::new(FakeScreen));
+    player.mode = mode;
+    for song in songs {
+        let video_meta = VideoMeta::new(song).unwrap();
+        player.state.write().unwrap().playlist.push_back(QueueEntry::new(video_meta, None));
+    }
+    (player, backend)
+}
+
+#[cfg(test)]
+fn playing(player: &ToyundaPlayer) -> Option<PathBuf> {
+    match player.state.read().unwrap().playing_state {
+        PlayingState::Playing(ref video_meta) => Some(video_meta.video_path.clone()),
+        PlayingState::Idle => None,
+    }
+}
+
+#[test]
+fn test_play_next_on_empty_queue() {
+    // the normal mode quits once the queue is over, the karaoke mode waits for more songs
+    let (mut player, backend) = test_player(ToyundaMode::NormalMode, &[]);
+    assert_eq!(player.execute_command(Command::PlayNext).unwrap(),
+               ToyundaAction::Terminate);
+    assert!(backend.media().loaded_files.is_empty());
+    assert_eq!(playing(&player), None);
+    let (mut player, _) = test_player(ToyundaMode::KaraokeMode, &[]);
+    assert_eq!(player.execute_command(Command::PlayNext).unwrap(),
+               ToyundaAction::Nothing);
+}
+
+#[test]
+fn test_quit_when_finished() {
+    let (mut player, _) = test_player(ToyundaMode::KaraokeMode, &[]);
+    player.execute_command(Command::ToggleQuitOnFinish).unwrap();
+    assert_eq!(player.state.read().unwrap().quit_when_finished, Some(true));
+    assert_eq!(player.execute_command(Command::PlayNext).unwrap(),
+               ToyundaAction::Terminate);
+    let (mut player, _) = test_player(ToyundaMode::NormalMode, &[]);
+    player.state.write().unwrap().quit_when_finished = Some(false);
+    assert_eq!(player.execute_command(Command::PlayNext).unwrap(),
+               ToyundaAction::Nothing);
+}
+
+#[test]
+fn test_karaoke_skips_songs() {
+    use ::utils::TempDir;
+    let dir = TempDir::new("player_test");
+    for name in &["a", "d"] {
+        let file = ::std::fs::File::create(dir.path().join(format!("{}.json", name))).unwrap();
+        serde_json::to_writer(file, &Subtitles::default()).unwrap();
+    }
+    // b can't be loaded, c has no subtitles
+    let songs: Vec<PathBuf> =
+        ["a.mp4", "b.mp4", "c.mp4", "d.mp4"].iter().map(|f| dir.path().join(f)).collect();
+    let (mut player, backend) = test_player(ToyundaMode::KaraokeMode, &songs);
+    backend.media().failing_files.push(songs[1].to_string_lossy().into_owned());
+    let mut results = vec![];
+    player.execute_command(Command::PlayNext).unwrap();
+    assert!(!player.poll_media_events(&mut results));
+    assert_eq!(playing(&player).as_ref(), Some(&songs[0]));
+    assert!(player.subtitles.is_some());
+    backend.advance(10.0);
+    assert!(!player.poll_media_events(&mut results));
+    assert_eq!(playing(&player).as_ref(), Some(&songs[3]));
+    assert_eq!(backend.media().loaded_files.len(), 4);
+    assert!(player.state.read().unwrap().playlist.is_empty());
+    // the karaoke goes on once the queue is over
+    backend.advance(10.0);
+    assert!(!player.poll_media_events(&mut results));
+    assert_eq!(playing(&player), None);
+    assert!(results.iter().all(|r| *r.as_ref().unwrap() == ToyundaAction::Nothing));
+}
+
+#[test]
+fn test_end_of_queue_and_shutdown() {
+    let (mut player, backend) = test_player(ToyundaMode::NormalMode,
+                                            &[PathBuf::from("/nowhere/a.mp4")]);
+    let mut results = vec![];
+    player.execute_command(Command::PlayNext).unwrap();
+    // no subtitles, but the normal mode plays it anyway
+    assert!(!player.poll_media_events(&mut results));
+    assert_eq!(playing(&player), Some(PathBuf::from("/nowhere/a.mp4")));
+    // quits right away at the end of the last file, like on Shutdown
+    backend.advance(10.0);
+    assert!(player.poll_media_events(&mut results));
+    assert_eq!(playing(&player), None);
+    let (mut player, backend) = test_player(ToyundaMode::KaraokeMode, &[]);
+    let mut results = vec![];
+    backend.push_event(MediaEvent::Shutdown);
+    assert!(player.poll_media_events(&mut results));
+    assert!(results.is_empty());
+}
+
+#[test]
+fn test_manager_request() {
+    use std::sync::mpsc::channel;
+    let (mut player, _) = test_player(ToyundaMode::NormalMode,
+                                      &[PathBuf::from("a.mp4"), PathBuf::from("b.mp4")]);
+    let state = player.state.clone();
+    let queue_ids = || -> Vec<QueueId> {
+        state.read().unwrap().playlist.iter().map(|e| e.id).collect()
+    };
+    let ids = queue_ids();
+    let (tx, rx) = channel();
+    // the first entry was deleted meanwhile, nothing of the request is done
+    let request = ManagerRequest {
+        commands: vec![Command::DeleteFromQueue(ids[0]),
+                       Command::DeleteFromQueue(ids[1]),
+                       Command::DeleteFromQueue(ids[0])],
+        reply: tx.clone(),
+    };
+    assert_eq!(player.execute_manager_request(request), ToyundaAction::Nothing);
+    match rx.try_recv().unwrap() {
+        Err(Error::QueueError(QueueError::UnknownId(id))) => assert_eq!(id, ids[0]),
+        other => panic!("unexpected reply {:?}", other),
+    }
+    assert_eq!(queue_ids(), ids);
+    let request = ManagerRequest {
+        commands: vec![Command::SwapInQueue(ids[0], ids[1]), Command::DeleteFromQueue(ids[0])],
+        reply: tx.clone(),
+    };
+    assert_eq!(player.execute_manager_request(request), ToyundaAction::Nothing);
+    assert!(rx.try_recv().unwrap().is_ok());
+    assert_eq!(queue_ids(), vec![ids[1]]);
+    // what PlayNext leaves in the queue isn't known before it's done
+    let request = ManagerRequest {
+        commands: vec![Command::PlayNext, Command::DeleteFromQueue(ids[1])],
+        reply: tx.clone(),
+    };
+    assert_eq!(player.execute_manager_request(request), ToyundaAction::Nothing);
+    match rx.try_recv().unwrap() {
+        Err(Error::QueueError(QueueError::NotAlone)) => {}
+        other => panic!("unexpected reply {:?}", other),
+    }
+    assert_eq!(queue_ids(), vec![ids[1]]);
+    assert_eq!(playing(&player), None);
+    let request = ManagerRequest {
+        commands: vec![Command::Quit],
+        reply: tx,
+    };
+    assert_eq!(player.execute_manager_request(request), ToyundaAction::Terminate);
+    assert!(rx.try_recv().unwrap().is_ok());
+}
@@ -1,9 +1,12 @@
-#[derive(Copy,Clone,Debug,Eq,PartialEq)]
+#[derive(Copy,Clone,Debug,Eq,PartialEq,Serialize,Deserialize)]
 pub enum ToyundaMode {
     /// most shortcuts are available
+    #[serde(rename = "normal")]
     NormalMode,
     /// shortcuts are different, allow modifying the subtitles directly in the player
+    #[serde(rename = "edit")]
     EditMode,
     /// almost no shortcuts are available
+    #[serde(rename = "karaoke")]
     KaraokeMode,
 }
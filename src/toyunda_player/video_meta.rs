@@ -1,6 +1,8 @@
 extern crate serde_yaml;
 
 use ::subtitles::song_info::SongInfo;
+use ::subtitles::{Subtitles, Load, Error as SubtitlesError};
+use super::subtitle_loader::{AssFile, UltraStarFile};
 use super::time_info::TimeInfo;
 use std::path::{Path, PathBuf};
 use std::fmt;
@@ -12,6 +14,10 @@ pub struct VideoMeta {
     pub json_path: Option<PathBuf>,
     pub lyr_path: Option<PathBuf>,
     pub frm_path: Option<PathBuf>,
+    #[serde(default,skip_serializing_if="Option::is_none")]
+    pub ass_path: Option<PathBuf>,
+    #[serde(default,skip_serializing_if="Option::is_none")]
+    pub ultrastar_path: Option<PathBuf>,
     #[serde(skip_deserializing)]
     pub yaml_path: Option<PathBuf>,
     #[serde(default)]
@@ -60,6 +66,8 @@ impl VideoMeta {
                         lyr_path: None,
                         frm_path: None,
                         json_path: None,
+                        ass_path: None,
+                        ultrastar_path: None,
                         yaml_path: None,
                         song_info: SongInfo::default(),
                         time_info: TimeInfo::default(),
@@ -105,6 +113,8 @@ impl VideoMeta {
         fix_option_path(&original, &mut self.json_path);
         fix_option_path(&original, &mut self.frm_path);
         fix_option_path(&original, &mut self.lyr_path);
+        fix_option_path(&original, &mut self.ass_path);
+        fix_option_path(&original, &mut self.ultrastar_path);
         self
     }
 
@@ -130,6 +140,47 @@ impl VideoMeta {
             Some(ref path) => path.clone(),
         }
     }
+
+    pub fn ass_path(&self) -> PathBuf {
+        match self.ass_path {
+            None => self.video_path.with_extension("ass"),
+            Some(ref path) => path.clone(),
+        }
+    }
+
+    pub fn ultrastar_path(&self) -> PathBuf {
+        match self.ultrastar_path {
+            None => self.video_path.with_extension("txt"),
+            Some(ref path) => path.clone(),
+        }
+    }
+
+    /// json files are preferred, then .ass and UltraStar files; .lyr files are
+    /// left out as they need the fps of the video
+    pub fn load_subtitles(&self) -> Result<Subtitles, SubtitlesError> {
+        use std::io;
+        let json_path = self.json_path();
+        let ass_path = self.ass_path();
+        let ultrastar_path = self.ultrastar_path();
+        if json_path.is_file() {
+            let json_file = try!(::std::fs::File::open(&json_path).map_err(|e| {
+                SubtitlesError::Io {
+                    file: json_path.clone(),
+                    error: e,
+                }
+            }));
+            Subtitles::from_json(json_file)
+        } else if ass_path.is_file() {
+            AssFile(&*ass_path).into_subtitles()
+        } else if ultrastar_path.is_file() {
+            UltraStarFile(&*ultrastar_path).into_subtitles()
+        } else {
+            Err(SubtitlesError::Io {
+                file: self.video_path.clone(),
+                error: io::Error::new(io::ErrorKind::NotFound, "no subtitles for this file"),
+            })
+        }
+    }
 }
 
 impl fmt::Display for VideoMeta {
@@ -147,3 +198,14 @@ impl fmt::Display for VideoMeta {
         }
     }
 }
+
+#[cfg(test)]
+impl VideoMeta {
+    /// a song of the tests, without any file next to it : the video is named
+    /// after the media title
+    pub fn test_song(media_title: &str) -> VideoMeta {
+        let mut video_meta = VideoMeta::new(format!("{}.mp4", media_title)).unwrap();
+        video_meta.song_info.media_title = Some(media_title.to_string());
+        video_meta
+    }
+}
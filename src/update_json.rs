@@ -20,7 +20,7 @@ pub fn update_json(args: &ArgMatches) -> bool {
             match (json_file, yaml_file) {
                 (Ok(json_file), Ok(yaml_file)) => {
                     let video_meta: Result<VideoMeta, _> = serde_yaml::from_reader(&yaml_file);
-                    let subs: Result<Subtitles, _> = serde_json::from_reader(&json_file);
+                    let subs: Result<Subtitles, _> = Subtitles::from_json(&json_file);
                     match (video_meta, subs) {
                         (Ok(video_meta), Ok(mut subs)) => {
                             subs.song_info = video_meta.song_info.clone();
@@ -40,7 +40,7 @@ pub fn update_json(args: &ArgMatches) -> bool {
                             false
                         }
                         (_, Err(err)) => {
-                            println!("error while parsing subtitles : {:?}", err);
+                            println!("error while parsing subtitles : {}", err);
                             false
                         }
                     }
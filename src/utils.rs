@@ -108,6 +108,41 @@ pub fn for_each_in_dir<P: AsRef<Path>, F: Fn(&Path) -> bool>(directory: P,
     (vec_path, vec_error)
 }
 
+/// A directory for the files written by a test, removed with them when
+/// dropped. Its name is unique, tests running at the same time don't share it.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+        use std::time::{SystemTime, UNIX_EPOCH};
+        static COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let path = ::std::env::temp_dir().join(format!("toyunda_{}_{}_{}",
+                                                       name,
+                                                       nanos,
+                                                       COUNT.fetch_add(1, Ordering::SeqCst)));
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
 #[test]
 fn test_bgr() {
     use ::overlay::Color;
@@ -93,6 +93,30 @@ function toyunda_command(command_type,id,response_fun,error_fun) {
 	},error_fun);
 }
 
+// commands adding songs, which the player may refuse because of the quotas
+function add_command(parameters,response_fun) {
+	parameters.nickname = vue.nickname;
+	AJAX.post("/api/command",parameters,function(status,answer) {
+		if (status == 401) {
+			return;
+		} else if (status == 409 && answer.charAt(0) == "{") {
+			// played too recently, the reason tells when it can be added again
+			swal("Refusé", JSON.parse(answer).reason, "error");
+		} else if (is_status_error(status)) {
+			swal("Refusé", answer, "error");
+		} else {
+			// nothing is answered when no song was added
+			var warnings = answer ? JSON.parse(answer).warnings : [];
+			if (warnings.length > 0) {
+				swal("Attention", warnings.map(function(w) { return w.reason; }).join("\n"), "warning");
+			}
+			if (response_fun) {
+				response_fun(status,answer);
+			}
+		}
+	});
+}
+
 function format_name(song_info,video_path) {
 	var candidate = format_info2name(song_info);
 	if (candidate == null) {
@@ -114,28 +138,30 @@ var vue = new Vue({
 		panel: 0,
 		search : "",
 		playlist : [],
+		// the page(s) of the listing matching the search
 		listing : [],
+		listing_total : 0,
+		language_filter : "",
+		listing_sort : "",
 		currently_playing : null,
 		draft_indexes : [],
 		announcement_message: "",
-		connected:true
+		connected:true,
+		// sent with the songs so the player knows who asked for them
+		nickname:localStorage.getItem("toyunda_nickname") || "",
+		// the names of the playlists saved by the player
+		playlists : [],
+		selected_playlist : "",
+		playlist_name : "",
+		// until the player tells otherwise
+		role:"guest"
 	},
 	computed :{
-		filtered_list: function() {
-			var search = this.search ;
-			var listing = this.listing;
-			if (search != "") {
-				var searches = search.match(/\S+/g);
-				if (searches != null) {
-					for (var i = 0; i < searches.length ; i++) {
-						var search_regexp = new RegExp(searches[i],'i');
-						listing = listing.filter(function(e) {
-							return search_regexp.test(e.formatted_name);
-						});
-					}
-				}
-			}
-			return listing ;
+		listing_has_more: function() {
+			return this.listing.length < this.listing_total;
+		},
+		is_operator: function() {
+			return this.role == "operator";
 		},
 		now_playing: function() {
 			return format_fullinfo(this.currently_playing);
@@ -160,9 +186,8 @@ var vue = new Vue({
 			return this.announcement_message.length <= 0;
 		},
 		draft : function() {
-			var listing = this.listing ;
 			return this.draft_indexes.map(function(e) {
-				return listing[e];
+				return known_songs[e];
 			});
 		},
 		draft_duration : function() {
@@ -189,6 +214,18 @@ var vue = new Vue({
 			}
 		}
 	},
+	watch : {
+		search : fetch_listing_later,
+		language_filter : function() {
+			fetch_listing(false);
+		},
+		listing_sort : function() {
+			fetch_listing(false);
+		},
+		nickname : function(nickname) {
+			localStorage.setItem("toyunda_nickname", nickname);
+		}
+	},
 	methods : {
 		format_name:format_name,
 		format_info2name:format_info2name,
@@ -207,7 +244,7 @@ var vue = new Vue({
 			}
 		},
 		draft_transfer_beginning:function(index) {
-			AJAX.post("/api/command",{
+			add_command({
 				command:"add_to_queue",
 				id:this.draft_indexes[index],
 				pos:0
@@ -216,7 +253,10 @@ var vue = new Vue({
 			}.bind(this));
 		},
 		draft_transfer_single:function(index) {
-			toyunda_command("add_to_queue",this.draft_indexes[index],function(){
+			add_command({
+				command:"add_to_queue",
+				id:this.draft_indexes[index]
+			},function(){
 				this.draft_indexes.splice(index,1);
 			}.bind(this))
 		},
@@ -224,7 +264,10 @@ var vue = new Vue({
 			this.draft_indexes.splice(index,1);
 		},
 		add_to_queue:function(entry) {
-			toyunda_command("add_to_queue",entry.index);
+			add_command({
+				command:"add_to_queue",
+				id:entry.index
+			});
 		},
 		add_to_draft:function(entry) {
 			this.draft_indexes.push(entry.index);
@@ -232,7 +275,20 @@ var vue = new Vue({
 		queue_delete_at:function(index) {
 			AJAX.post("/api/command",{
 				command:"delete_from_queue",
-				pos:index
+				queue_id:this.playlist[index].queue_id
+			});
+		},
+		queue_move:function(index,to) {
+			AJAX.post("/api/command",{
+				command:"move_in_queue",
+				queue_id:this.playlist[index].queue_id,
+				to:to
+			});
+		},
+		queue_move_to_top:function(index) {
+			AJAX.post("/api/command",{
+				command:"move_to_top",
+				queue_id:this.playlist[index].queue_id
 			});
 		},
 		play_next:function() {
@@ -247,6 +303,35 @@ var vue = new Vue({
 		toggle_subtitles:function() {
 			toyunda_command("toggle_subtitles");
 		},
+		rescan_library:function() {
+			toyunda_command("rescan_library");
+		},
+		load_playlist:function(append) {
+			add_command({
+				command: append ? "append_playlist" : "load_playlist",
+				playlist: this.selected_playlist
+			},function() {
+				update();
+			});
+		},
+		save_playlist:function() {
+			var name = this.playlist_name.trim();
+			// no body : the player saves its current queue
+			AJAX.post("/api/playlists/" + encodeURIComponent(name),undefined,function(status,answer) {
+				if (status == 401) {
+					return;
+				} else if (is_status_error(status)) {
+					swal("Erreur", answer, "error");
+				} else {
+					vue.playlist_name = "";
+					vue.selected_playlist = name;
+					fetch_playlists();
+				}
+			});
+		},
+		export_url:function(format) {
+			return AUTH.url_with_token("/api/queue/export?format=" + format);
+		},
 		quit:function() {
 			swal({
 				title: 'Quitter ?',
@@ -272,7 +357,7 @@ var vue = new Vue({
 			this.draft_indexes.pop(); // <^notify Vue of a change
 		},
 		draft_transfer:function(){
-			AJAX.post("/api/command",{
+			add_command({
 				command:"add_multiple_to_queue",
 				list:this.draft_indexes
 			},function(){
@@ -288,8 +373,17 @@ var vue = new Vue({
 			}.bind(this))
 		},
 		draft_add_random:function(){
-			var index = Math.floor(Math.random() * this.listing.length);
-			this.draft_indexes.push(index);
+			// among the songs matching the search
+			var offset = Math.floor(Math.random() * this.listing_total);
+			AJAX.get(listing_url(offset, 1),function(status,answer) {
+				if (!is_status_error(status) && answer.entries.length > 0) {
+					var e = answer.entries[0];
+					this.draft_indexes.push(prepare_listing_entry(e.video_meta, e.id).index);
+				}
+			}.bind(this));
+		},
+		listing_more:function(){
+			fetch_listing(true);
 		},
 		draft_remove_last:function(){
 			if (this.draft_indexes.length > 0) {
@@ -310,6 +404,35 @@ document.addEventListener("keypress",function(event){
 	}
 })
 
+// entries of the queue are identified by their id, not their position
+function format_queue_entry(entry,i) {
+	var e = entry.video_meta;
+	e.queue_id = entry.id;
+	e.added_by = entry.added_by;
+	e.auto_dj = entry.auto_dj;
+	e.formatted_name = format_name(e.song_info,e.video_path);
+	e.formatted_fullinfo = format_fullinfo(e);
+	e.human_duration = human_duration(e.video_duration);
+	e.human_last_played = human_since(e.last_played);
+	e.index = i;
+	return e;
+}
+
+function queue_position(queue_id) {
+	for (var i = 0 ; i < vue.playlist.length ; i++) {
+		if (vue.playlist[i].queue_id == queue_id) {
+			return i;
+		}
+	}
+	return -1;
+}
+
+function reindex_playlist() {
+	vue.playlist.forEach(function(e,i) {
+		e.index = i;
+	});
+}
+
 function update() {
 	AJAX.get("/api/state",function(status,answer) {
 		if (is_status_error(status)) {
@@ -321,16 +444,7 @@ function update() {
 			} else {
 				vue.currently_playing = null;
 			}
-			var playlist = answer.playlist ;
-			playlist = playlist.map(function(e,i) {
-				e.formatted_name = format_name(e.song_info,e.video_path);
-				e.formatted_fullinfo = format_fullinfo(e);
-				e.human_duration = human_duration(e.video_duration);
-				e.human_last_played = human_since(e.last_played);
-				e.index = i;
-				return e;
-			});
-			vue.playlist = playlist ;
+			vue.playlist = answer.playlist.map(format_queue_entry);
 		}
 		vue.connected = true;
 	},function(){
@@ -338,39 +452,177 @@ function update() {
 	});
 }
 
-setInterval(update, 2000);
+// the ids of an event which aren't in the queue shown : some event was missed,
+// the whole state is fetched again instead of applying this one
+function missing_queue_ids(queue_ids) {
+	var missing = queue_ids.some(function(queue_id) {
+		return queue_position(queue_id) == -1;
+	});
+	if (missing) {
+		update();
+	}
+	return missing;
+}
 
-// retrieve the listing once
-AJAX.get("/api/listing",function(status,answer) {
-	if (is_status_error(status)) {
-		console.error("Error "+status+" when retrieving listing : "+answer);
-	} else {
-		if (Array.isArray(answer)) {
-			var len = answer.length ;
-			for (var i = 0 ; i < len ; i++ ) {
-				var entry = answer[i];
-				entry.search_string = "";
-				entry.search_string += (entry.artist || "") + " ";
-				entry.search_string += (entry.year || "") + " ";
-				entry.search_string += (entry.language || "") + " ";
-				if (entry.alt_media_titles) {
-					entry.alt_media_titles.forEach(function(e) {
-						entry.search_string += e + " ";
-					});
-				}
-				entry.search_string += format_name(entry.song_info,entry.video_path);
-				entry.formatted_name = format_name(entry.song_info,entry.video_path);
-				entry.index = i;
-				entry.human_duration = human_duration(entry.video_duration);
-				entry.formatted_fullinfo = format_fullinfo(entry);
-				entry.human_last_played = human_since(entry.last_played);
+// events sent by the player every time its state changes
+function on_state_event(message) {
+	var event = JSON.parse(message.data);
+	switch (event.type) {
+		case "queue_add":
+			vue.playlist.splice(event.pos, 0, format_queue_entry(event.entry, event.pos));
+			reindex_playlist();
+			break;
+		case "queue_delete":
+			if (missing_queue_ids([event.id])) {
+				break;
+			}
+			vue.playlist.splice(queue_position(event.id), 1);
+			reindex_playlist();
+			break;
+		case "queue_move":
+			if (missing_queue_ids([event.id])) {
+				break;
 			}
-			vue.listing = answer
+			var moved = vue.playlist.splice(queue_position(event.id), 1);
+			vue.playlist.splice(event.to, 0, moved[0]);
+			reindex_playlist();
+			break;
+		case "queue_swap":
+			if (missing_queue_ids([event.id,event.other_id])) {
+				break;
+			}
+			var pos = queue_position(event.id);
+			var other_pos = queue_position(event.other_id);
+			var swapped = vue.playlist[pos];
+			Vue.set(vue.playlist, pos, vue.playlist[other_pos]);
+			Vue.set(vue.playlist, other_pos, swapped);
+			reindex_playlist();
+			break;
+		case "queue_reorder":
+			if (missing_queue_ids(event.order)) {
+				break;
+			}
+			var old_playlist = vue.playlist;
+			vue.playlist = event.order.map(function(queue_id) {
+				return old_playlist[queue_position(queue_id)];
+			});
+			reindex_playlist();
+			break;
+		case "queue_clear":
+			vue.playlist = [];
+			break;
+		case "playing":
+			vue.currently_playing = event.video_meta;
+			break;
+		case "idle":
+			vue.currently_playing = null;
+			break;
+		case "library_updated":
+			// the ids stay the same, only the songs shown may change
+			fetch_listing(false);
+			break;
+		case "error_log":
+			console.error("Player error : "+event.message);
+			break;
+		default:
+			break;
+	}
+}
+
+if (window.EventSource) {
+	var state_events = new EventSource(AUTH.url_with_token("/api/events"));
+	state_events.onopen = function() {
+		// whatever happened while disconnected is lost : get the whole state again
+		update();
+	};
+	state_events.onmessage = on_state_event;
+	state_events.onerror = function() {
+		if (state_events.readyState == EventSource.CLOSED) {
+			// the player refused the stream (too many clients) : poll instead
+			setInterval(update, 2000);
 		} else {
-			console.error("Error when updating listing ; answer is not an Array");
-			console.error(answer);
+			vue.connected = false;
+		}
+	};
+} else {
+	setInterval(update, 2000);
+}
+
+AJAX.get("/api/role",function(status,answer) {
+	if (!is_status_error(status)) {
+		vue.role = answer;
+		if (answer == "operator") {
+			fetch_playlists();
 		}
 	}
 });
 
+function fetch_playlists() {
+	AJAX.get("/api/playlists",function(status,answer) {
+		if (!is_status_error(status)) {
+			vue.playlists = answer;
+			if (vue.playlists.indexOf(vue.selected_playlist) == -1) {
+				vue.selected_playlist = vue.playlists.length > 0 ? vue.playlists[0] : "";
+			}
+		}
+	});
+}
+
+// the songs of the listing already retrieved, by id
+var known_songs = {};
+
+function prepare_listing_entry(entry, id) {
+	entry.formatted_name = format_name(entry.song_info,entry.video_path);
+	entry.index = id;
+	entry.human_duration = human_duration(entry.video_duration);
+	entry.formatted_fullinfo = format_fullinfo(entry);
+	entry.human_last_played = human_since(entry.last_played);
+	known_songs[id] = entry;
+	return entry;
+}
+
+var LISTING_PAGE_SIZE = 100;
+
+// the search and the filters are done by the player
+function listing_url(offset, limit) {
+	var parameters = ["offset=" + offset, "limit=" + limit];
+	if (vue.search != "") {
+		parameters.push("q=" + encodeURIComponent(vue.search));
+	}
+	if (vue.language_filter != "") {
+		parameters.push("language=" + encodeURIComponent(vue.language_filter));
+	}
+	if (vue.listing_sort != "") {
+		parameters.push("sort=" + encodeURIComponent(vue.listing_sort));
+	}
+	return "/api/listing?" + parameters.join("&");
+}
+
+// replaces the listing, or adds the next page to it if `more` is true
+function fetch_listing(more) {
+	var offset = more ? vue.listing.length : 0;
+	AJAX.get(listing_url(offset, LISTING_PAGE_SIZE),function(status,answer) {
+		if (is_status_error(status)) {
+			console.error("Error "+status+" when retrieving listing : "+answer);
+		} else {
+			var entries = answer.entries.map(function(e) {
+				return prepare_listing_entry(e.video_meta, e.id);
+			});
+			vue.listing = more ? vue.listing.concat(entries) : entries;
+			vue.listing_total = answer.total;
+		}
+	});
+}
+
+var fetch_listing_timeout = null;
+// waits for the user to stop typing
+function fetch_listing_later() {
+	clearTimeout(fetch_listing_timeout);
+	fetch_listing_timeout = setTimeout(function() {
+		fetch_listing(false);
+	}, 300);
+}
+
+fetch_listing(false);
+
 update();
@@ -12,11 +12,60 @@ function is_status_error(status) {
 	return status < 200 || status >= 400 ;
 }
 
+// the password of the manager, given once with "?token=..." in the url
+var AUTH = {
+	token : function() {
+		var match = /[?&]token=([^&]*)/.exec(window.location.search);
+		if (match) {
+			localStorage.setItem("toyunda_token", decodeURIComponent(match[1]));
+		}
+		return localStorage.getItem("toyunda_token");
+	},
+	// EventSource can't send headers
+	url_with_token : function(url) {
+		var token = AUTH.token();
+		if (!token) {
+			return url;
+		}
+		return url + (url.indexOf("?") == -1 ? "?" : "&") + "token=" + encodeURIComponent(token);
+	},
+	set_header : function(request) {
+		var token = AUTH.token();
+		if (token) {
+			request.setRequestHeader("Authorization", "Bearer " + token);
+		}
+	},
+	ask_password_once : false,
+	ask_password : function() {
+		if (AUTH.ask_password_once) {
+			return;
+		}
+		AUTH.ask_password_once = true;
+		swal({
+			title: 'Mot de passe',
+			input: 'password',
+			showCancelButton: true,
+			confirmButtonText: 'Valider'
+		}).then(function(password) {
+			localStorage.setItem("toyunda_token", password);
+			window.location.search = "";
+		}, function() {
+			AUTH.ask_password_once = false;
+		});
+	}
+}
+
 var AJAX = {
 	get : function(url,response_fun,error_fun) {
 		var request = new XMLHttpRequest();
 		request.open('GET',url, true);
+		AUTH.set_header(request);
 		request.onload = function() {
+			if (request.status == 401) {
+				AUTH.ask_password();
+				response_fun(request.status,request.responseText);
+				return;
+			}
 			let responseText = JSON.parse(request.responseText) ;
 			if (responseText == null) {
 				responseText = request.responseText ;
@@ -35,7 +84,11 @@ var AJAX = {
 		var request = new XMLHttpRequest();
 		request.open('POST',url, true);
 		request.setRequestHeader("Content-Type", "application/json");
+		AUTH.set_header(request);
 		request.onload = function() {
+			if (request.status == 401) {
+				AUTH.ask_password();
+			}
 			if (response_fun) {
 				response_fun(request.status,request.responseText);
 			}